@@ -0,0 +1,337 @@
+use std::{
+    env,
+    io::{self, Write},
+    process::ExitCode,
+    time::Duration,
+};
+
+use crossterm::{
+    cursor,
+    event::{self, Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers},
+    queue,
+    style::{self, Attribute, ContentStyle, Stylize},
+    terminal,
+};
+use kanii_lib::{
+    client::{
+        view::{ChatView, Line},
+        Connection, Session, SessionState,
+    },
+    logs::export::{Entry, EntryKind},
+    packets::{
+        client::MCHAN,
+        types::{Color, Sockchatable},
+    },
+};
+
+const USAGE: &str = "\
+Usage: kanii-chat URL METHOD [AUTHKEY]
+
+Connects to the Sockchat server at URL, e.g. ws://localhost:6770, and
+authenticates with METHOD and AUTHKEY. The authkey is read from the
+KANII_AUTHKEY environment variable when not given.";
+
+const SIDEBAR_WIDTH: u16 = 24;
+
+struct Options {
+    url: String,
+    method: String,
+    authkey: String,
+}
+
+impl Options {
+    fn parse<I: Iterator<Item = String>>(mut args: I) -> Option<Self> {
+        let url = args.next().filter(|arg| !arg.starts_with('-'))?;
+        let method = args.next()?;
+        let authkey = args.next().or_else(|| env::var("KANII_AUTHKEY").ok())?;
+        Some(Options {
+            url,
+            method,
+            authkey,
+        })
+    }
+
+    fn session(&self) -> Session {
+        Session::new(&self.method, &self.authkey).with_capabilities(&[MCHAN])
+    }
+}
+
+type Row = Vec<(String, ContentStyle)>;
+
+fn user_style(color: &Color) -> ContentStyle {
+    let style = ContentStyle::new().attribute(Attribute::Bold);
+    match color.as_rgb() {
+        Ok([r, g, b]) => style.with(style::Color::Rgb { r, g, b }),
+        Err(_) => style,
+    }
+}
+
+fn clock(timestamp: i64) -> String {
+    let seconds = timestamp.rem_euclid(86_400);
+    format!("{:02}:{:02} ", seconds / 3600, seconds % 3600 / 60)
+}
+
+fn segments(line: &Line) -> Row {
+    let dim = ContentStyle::new().dark_grey();
+    let (timestamp, username, color, kind) = match line {
+        Line::Entry(Entry {
+            timestamp,
+            username,
+            color,
+            kind,
+            ..
+        }) => (timestamp, username, color, kind),
+        Line::Notice { text, error } => {
+            let style = match error {
+                true => ContentStyle::new().red(),
+                false => ContentStyle::new().yellow(),
+            };
+            return vec![(format!("* {}", text), style)];
+        }
+    };
+    let mut row = vec![(clock(*timestamp), dim)];
+    match kind {
+        EntryKind::Message {
+            text,
+            message_flags,
+            ..
+        } => {
+            if message_flags.private {
+                row.push(("(whisper) ".to_string(), ContentStyle::new().magenta()));
+            }
+            let mut style = ContentStyle::new();
+            if message_flags.bold {
+                style = style.attribute(Attribute::Bold);
+            }
+            if message_flags.cursive {
+                style = style.attribute(Attribute::Italic);
+            }
+            if message_flags.underlined {
+                style = style.attribute(Attribute::Underlined);
+            }
+            if message_flags.colon {
+                row.push((username.clone(), user_style(color)));
+                row.push((format!(": {}", text), style));
+            } else {
+                row.push((format!("* {} ", username), user_style(color)));
+                row.push((text.clone(), style.italic()));
+            }
+        }
+        EntryKind::Notice { message } => {
+            row.push((format!("* {}", message.summary()), dim));
+        }
+        EntryKind::Join => {
+            row.push((format!("→ {} joined", username), dim));
+        }
+        EntryKind::Leave { reason } => {
+            let reason = reason.as_ref().map_or(String::new(), |reason| {
+                format!(" ({})", reason.to_sockstr())
+            });
+            row.push((format!("← {} left{}", username, reason), dim));
+        }
+    }
+    row
+}
+
+/// Breaks styled text into rows of at most `width` chars, honouring line breaks.
+fn wrap(segments: Row, width: usize) -> Vec<Row> {
+    let mut rows = vec![Row::new()];
+    let mut used = 0;
+    for (text, style) in segments {
+        let mut current = String::new();
+        for c in text.chars() {
+            if c == '\n' || used == width {
+                rows.last_mut()
+                    .unwrap()
+                    .push((std::mem::take(&mut current), style));
+                rows.push(Row::new());
+                used = 0;
+                if c == '\n' {
+                    continue;
+                }
+            }
+            current.push(c);
+            used += 1;
+        }
+        rows.last_mut().unwrap().push((current, style));
+    }
+    rows
+}
+
+fn draw<W: Write>(out: &mut W, view: &ChatView, connection: &Connection) -> io::Result<()> {
+    let (cols, rows) = terminal::size()?;
+    let sidebar = if cols >= 60 { SIDEBAR_WIDTH } else { 0 };
+    let pane_width = (cols - sidebar).saturating_sub(1).max(1) as usize;
+    let pane_height = rows.saturating_sub(2) as usize;
+    let state = view.state();
+    queue!(out, terminal::Clear(terminal::ClearType::All))?;
+
+    let status = match connection.session().state() {
+        SessionState::Connected => format!(
+            " #{} as {}",
+            connection.session().channel_name().unwrap_or("?"),
+            state
+                .self_id()
+                .and_then(|user_id| state.user(user_id))
+                .map_or("?", |user| user.username.as_str())
+        ),
+        SessionState::Authenticating => " authenticating".to_string(),
+        _ => " offline".to_string(),
+    };
+    queue!(
+        out,
+        cursor::MoveTo(0, 0),
+        style::PrintStyledContent(format!("{:width$}", status, width = cols as usize).reverse())
+    )?;
+
+    let mut pane = Vec::new();
+    let lines = view.lines();
+    let end = lines.len().saturating_sub(view.scroll());
+    for line in lines[..end].iter().rev() {
+        let mut wrapped = wrap(segments(line), pane_width);
+        wrapped.reverse();
+        pane.extend(wrapped);
+        if pane.len() >= pane_height {
+            break;
+        }
+    }
+    pane.truncate(pane_height);
+    for (index, row) in pane.iter().rev().enumerate() {
+        let y = (pane_height - pane.len() + index) as u16 + 1;
+        queue!(out, cursor::MoveTo(0, y))?;
+        for (text, style) in row {
+            queue!(out, style::PrintStyledContent(style.apply(text.as_str())))?;
+        }
+    }
+
+    if sidebar > 0 {
+        let x = cols - sidebar;
+        let mut side = vec![("Channels".to_string(), ContentStyle::new().bold())];
+        for channel in state.channels() {
+            let current = Some(channel.channel_name.as_str()) == state.channel_name();
+            side.push((
+                format!(
+                    "{}#{}",
+                    if current { "*" } else { " " },
+                    channel.channel_name
+                ),
+                ContentStyle::new(),
+            ));
+        }
+        let mut users = match state.channel_name() {
+            Some(channel) => state.members(channel).collect::<Vec<_>>(),
+            None => state.users().collect(),
+        };
+        users.sort_by_key(|user| user.username.to_lowercase());
+        side.push((String::new(), ContentStyle::new()));
+        side.push((
+            format!("Users ({})", users.len()),
+            ContentStyle::new().bold(),
+        ));
+        for user in users {
+            side.push((format!(" {}", user.username), user_style(&user.color)));
+        }
+        for y in 1..=pane_height as u16 {
+            queue!(out, cursor::MoveTo(x - 1, y), style::Print('│'))?;
+        }
+        for (index, (text, style)) in side.into_iter().take(pane_height).enumerate() {
+            let text = text.chars().take(sidebar as usize).collect::<String>();
+            queue!(
+                out,
+                cursor::MoveTo(x, index as u16 + 1),
+                style::PrintStyledContent(style.apply(text))
+            )?;
+        }
+    }
+
+    let input = view.input.text();
+    let visible = (cols as usize).saturating_sub(3);
+    let skip = view.input.cursor().saturating_sub(visible);
+    let shown = input.chars().skip(skip).take(visible).collect::<String>();
+    queue!(
+        out,
+        cursor::MoveTo(0, rows.saturating_sub(1)),
+        style::Print("> "),
+        style::Print(shown),
+        cursor::MoveTo(
+            (view.input.cursor() - skip) as u16 + 2,
+            rows.saturating_sub(1)
+        )
+    )?;
+    out.flush()
+}
+
+/// Applies a key press, returning false once the user wants out.
+fn key(view: &mut ChatView, connection: &mut Connection, key: KeyEvent) -> bool {
+    if key.kind != KeyEventKind::Press {
+        return true;
+    }
+    let page = terminal::size().map_or(10, |(_, rows)| (rows as usize / 2).max(1));
+    match key.code {
+        KeyCode::Char('c') if key.modifiers.contains(KeyModifiers::CONTROL) => return false,
+        KeyCode::Esc => return false,
+        KeyCode::Char(c) if !key.modifiers.contains(KeyModifiers::CONTROL) => view.input.insert(c),
+        KeyCode::Enter => {
+            let action = view.submit(connection.session());
+            return connection.send(view, action);
+        }
+        KeyCode::Backspace => view.input.backspace(),
+        KeyCode::Delete => view.input.delete(),
+        KeyCode::Left => view.input.left(),
+        KeyCode::Right => view.input.right(),
+        KeyCode::Home => view.input.home(),
+        KeyCode::End => view.input.end(),
+        KeyCode::PageUp => view.scroll_up(page),
+        KeyCode::PageDown => view.scroll_down(page),
+        _ => {}
+    }
+    true
+}
+
+fn run(options: Options) -> io::Result<()> {
+    let mut out = io::stdout();
+    let mut view = ChatView::new();
+    let mut connection = Connection::new(&options.url, options.session());
+    draw(&mut out, &view, &connection)?;
+    loop {
+        if connection.poll(&mut view) {
+            draw(&mut out, &view, &connection)?;
+        }
+        let wait = match connection.is_open() {
+            true => Duration::from_millis(20),
+            false => Duration::from_millis(200),
+        };
+        while event::poll(wait)? {
+            match event::read()? {
+                Event::Key(event) => {
+                    if !key(&mut view, &mut connection, event) {
+                        return Ok(());
+                    }
+                }
+                Event::Resize(..) => {}
+                _ => continue,
+            }
+            draw(&mut out, &view, &connection)?;
+        }
+    }
+}
+
+fn main() -> ExitCode {
+    let Some(options) = Options::parse(env::args().skip(1)) else {
+        eprintln!("{}", USAGE);
+        return ExitCode::from(2);
+    };
+    let setup = terminal::enable_raw_mode()
+        .and_then(|_| queue!(io::stdout(), terminal::EnterAlternateScreen));
+    let result = setup.and_then(|_| run(options));
+    let _ = queue!(io::stdout(), terminal::LeaveAlternateScreen);
+    let _ = io::stdout().flush();
+    let _ = terminal::disable_raw_mode();
+    match result {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(error) => {
+            eprintln!("kanii-chat: {}", error);
+            ExitCode::FAILURE
+        }
+    }
+}
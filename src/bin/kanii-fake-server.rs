@@ -0,0 +1,84 @@
+use std::{
+    env,
+    io::{self, BufRead},
+    process::ExitCode,
+};
+
+use kanii_lib::{
+    packets::{
+        server::ServerPacket,
+        types::{unix_timestamp, Color, UserContext},
+    },
+    server::fake::FakeServerConfig,
+};
+
+const USAGE: &str = "\
+Usage: kanii-fake-server [ADDRESS]
+
+Serves a fake Sockchat chat on ADDRESS, 127.0.0.1:6770 by default, that
+accepts any authkey as a username.
+
+Every line typed on stdin is sent to all connections as a raw server packet,
+except for these commands:
+  drop       cut all connections
+  received   print what clients sent so far";
+
+fn user(user_id: &str, username: &str, color: &str) -> UserContext {
+    UserContext {
+        user_id: user_id.to_string(),
+        username: username.to_string(),
+        color: Color {
+            value: color.to_string(),
+        },
+        visible: true,
+        ..Default::default()
+    }
+}
+
+fn main() -> ExitCode {
+    let address = match env::args().nth(1) {
+        Some(arg) if arg.starts_with('-') => {
+            eprintln!("{}", USAGE);
+            return ExitCode::from(2);
+        }
+        Some(address) => address,
+        None => "127.0.0.1:6770".to_string(),
+    };
+    let flash = user("2", "flash", "#3daee9");
+    let misaka = user("3", "misaka", "#f67400");
+    let now = unix_timestamp();
+    let server = match FakeServerConfig::new()
+        .with_user(flash.clone())
+        .with_user(misaka.clone())
+        .with_message(now - 120, &flash, "anyone around?")
+        .with_message(now - 60, &misaka, "always")
+        .start_on(&address)
+    {
+        Ok(server) => server,
+        Err(error) => {
+            eprintln!("kanii-fake-server: {}", error);
+            return ExitCode::FAILURE;
+        }
+    };
+    println!("listening on {}", server.url());
+
+    for line in io::stdin().lock().lines() {
+        let Ok(line) = line else {
+            break;
+        };
+        match line.trim_end_matches('\r') {
+            "" => {}
+            "drop" => server.drop_connections(),
+            "received" => {
+                for packet in server.received() {
+                    println!("{:?}", packet);
+                }
+            }
+            raw => match raw.parse::<ServerPacket>() {
+                Ok(packet) => server.inject(packet),
+                Err(error) => eprintln!("not a server packet: {:?}", error),
+            },
+        }
+    }
+    ExitCode::SUCCESS
+}
@@ -0,0 +1,103 @@
+use std::{env, process::ExitCode};
+
+use kanii_lib::{
+    client::blocking::Client,
+    irc::{Bridge, Gateway},
+};
+
+const USAGE: &str = "\
+Usage: kanii-irc-bridge [OPTIONS] URL METHOD IRC_ADDRESS CHANNEL
+
+Relays the Sockchat server at URL, e.g. ws://localhost:6770, to CHANNEL on
+the IRC server at IRC_ADDRESS, e.g. irc.example.org:6667. The bridge signs in
+with METHOD and the authkey in the KANII_AUTHKEY environment variable.
+
+Options:
+  -n, --nick NICK       nick of the relay connection, kanii by default
+  -s, --suffix SUFFIX   appended to the nicks of Sockchat users, [s] by default
+  -p, --prefix PREFIX   put in front of messages from IRC, {nick} is replaced
+                        with the sender; \"<{nick}> \" by default";
+
+struct Options {
+    url: String,
+    method: String,
+    address: String,
+    channel: String,
+    nick: String,
+    suffix: Option<String>,
+    prefix: Option<String>,
+}
+
+impl Options {
+    fn parse<I: Iterator<Item = String>>(mut args: I) -> Result<Self, String> {
+        let mut positional = Vec::new();
+        let (mut nick, mut suffix, mut prefix) = (None, None, None);
+        while let Some(arg) = args.next() {
+            let slot = match arg.as_str() {
+                "-n" | "--nick" => &mut nick,
+                "-s" | "--suffix" => &mut suffix,
+                "-p" | "--prefix" => &mut prefix,
+                "-h" | "--help" => return Err(String::new()),
+                _ if arg.starts_with('-') => return Err(format!("unknown option {}", arg)),
+                _ => {
+                    positional.push(arg);
+                    continue;
+                }
+            };
+            *slot = Some(
+                args.next()
+                    .ok_or_else(|| format!("{} needs a value", arg))?,
+            );
+        }
+        let [url, method, address, channel] = <[String; 4]>::try_from(positional)
+            .map_err(|_| "expected URL, METHOD, IRC_ADDRESS and CHANNEL".to_string())?;
+        Ok(Options {
+            url,
+            method,
+            address,
+            channel,
+            nick: nick.unwrap_or_else(|| "kanii".to_string()),
+            suffix,
+            prefix,
+        })
+    }
+}
+
+fn main() -> ExitCode {
+    let options = match Options::parse(env::args().skip(1)) {
+        Ok(options) => options,
+        Err(error) if error.is_empty() => {
+            println!("{}", USAGE);
+            return ExitCode::SUCCESS;
+        }
+        Err(error) => {
+            eprintln!("kanii-irc-bridge: {}\n\n{}", error, USAGE);
+            return ExitCode::from(2);
+        }
+    };
+    let Ok(authkey) = env::var("KANII_AUTHKEY") else {
+        eprintln!("kanii-irc-bridge: KANII_AUTHKEY is not set");
+        return ExitCode::from(2);
+    };
+
+    let mut bridge = Bridge::new(&options.channel, &options.nick);
+    if let Some(suffix) = &options.suffix {
+        bridge = bridge.with_nick_suffix(suffix);
+    }
+    if let Some(prefix) = &options.prefix {
+        bridge = bridge.with_prefix(prefix);
+    }
+    let result = Client::connect(&options.url, &options.method, &authkey)
+        .map_err(|error| format!("sockchat: {:?}", error))
+        .and_then(|client| {
+            Gateway::new(client, bridge, &options.address).map_err(|error| format!("{:?}", error))
+        })
+        .and_then(|mut gateway| gateway.run().map_err(|error| format!("{:?}", error)));
+    match result {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(error) => {
+            eprintln!("kanii-irc-bridge: {}", error);
+            ExitCode::FAILURE
+        }
+    }
+}
@@ -0,0 +1,82 @@
+use std::{env, process::ExitCode};
+
+use kanii_lib::irc::IrcServer;
+
+const USAGE: &str = "\
+Usage: kanii-ircd [OPTIONS] URL [ADDRESS]
+
+Lets IRC clients connect to the Sockchat server at URL, e.g.
+ws://localhost:6770, by listening on ADDRESS, 127.0.0.1:6667 by default. Each
+client signs in with its Sockchat authkey as the server password.
+
+Options:
+  -m, --method METHOD   authentication method, Misuzu by default";
+
+struct Options {
+    url: String,
+    address: String,
+    method: Option<String>,
+}
+
+impl Options {
+    fn parse<I: Iterator<Item = String>>(mut args: I) -> Result<Self, String> {
+        let mut positional = Vec::new();
+        let mut method = None;
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "-m" | "--method" => {
+                    method = Some(
+                        args.next()
+                            .ok_or_else(|| format!("{} needs a value", arg))?,
+                    )
+                }
+                "-h" | "--help" => return Err(String::new()),
+                _ if arg.starts_with('-') => return Err(format!("unknown option {}", arg)),
+                _ => positional.push(arg),
+            }
+        }
+        let mut positional = positional.into_iter();
+        let url = positional.next().ok_or("expected URL")?;
+        let address = positional
+            .next()
+            .unwrap_or_else(|| "127.0.0.1:6667".to_string());
+        if positional.next().is_some() {
+            return Err("too many arguments".to_string());
+        }
+        Ok(Options {
+            url,
+            address,
+            method,
+        })
+    }
+}
+
+fn main() -> ExitCode {
+    let options = match Options::parse(env::args().skip(1)) {
+        Ok(options) => options,
+        Err(error) if error.is_empty() => {
+            println!("{}", USAGE);
+            return ExitCode::SUCCESS;
+        }
+        Err(error) => {
+            eprintln!("kanii-ircd: {}\n\n{}", error, USAGE);
+            return ExitCode::from(2);
+        }
+    };
+
+    let result = IrcServer::bind(&options.address, &options.url).and_then(|mut server| {
+        if let Some(method) = &options.method {
+            server = server.with_method(method);
+        }
+        eprintln!("kanii-ircd: listening on {}", server.local_addr()?);
+        server.run(|error| eprintln!("kanii-ircd: cannot accept a connection: {}", error));
+        Ok(())
+    });
+    match result {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(error) => {
+            eprintln!("kanii-ircd: {}", error);
+            ExitCode::FAILURE
+        }
+    }
+}
@@ -0,0 +1,227 @@
+use std::{
+    env,
+    fs::File,
+    io::{self, BufRead, BufReader, Write},
+    process::ExitCode,
+};
+
+use kanii_lib::{
+    packets::inspect::{inspect, FieldIssue, FieldKind, Inspection},
+    recording::{Direction, Frame},
+};
+use serde_json::json;
+
+const USAGE: &str = "\
+Usage: kanii [OPTIONS] [FILE]
+
+Decodes raw Sockchat lines from FILE, or stdin, and prints them field by field.
+
+Options:
+  -c, --client          lines are client packets rather than server ones
+  -r, --recording       input is a session recording
+  -f, --filter ID[:SUB] only show packets with this id and sub-id, repeatable
+  -j, --json            print one JSON object per line
+  -d, --debug           also print the decoded packet
+  -h, --help            print this help";
+
+#[derive(Default)]
+struct Options {
+    client: bool,
+    recording: bool,
+    json: bool,
+    debug: bool,
+    filters: Vec<(String, Option<String>)>,
+    path: Option<String>,
+}
+
+impl Options {
+    fn parse<I: Iterator<Item = String>>(mut args: I) -> Result<Self, String> {
+        let mut options = Options::default();
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "-c" | "--client" => options.client = true,
+                "-r" | "--recording" => options.recording = true,
+                "-j" | "--json" => options.json = true,
+                "-d" | "--debug" => options.debug = true,
+                "-f" | "--filter" => {
+                    let filter = args
+                        .next()
+                        .ok_or_else(|| format!("{} needs a packet id", arg))?;
+                    options.filters.push(match filter.split_once(':') {
+                        Some((id, sub_id)) => (id.to_string(), Some(sub_id.to_string())),
+                        None => (filter, None),
+                    });
+                }
+                "-h" | "--help" => return Err(String::new()),
+                _ if arg.starts_with('-') && arg != "-" => {
+                    return Err(format!("unknown option {}", arg))
+                }
+                _ if options.path.is_none() => options.path = Some(arg),
+                _ => return Err(format!("unexpected argument {}", arg)),
+            }
+        }
+        Ok(options)
+    }
+
+    fn shows(&self, inspection: &Inspection) -> bool {
+        self.filters.is_empty()
+            || self.filters.iter().any(|(id, sub_id)| {
+                *id == inspection.id
+                    && sub_id
+                        .as_ref()
+                        .is_none_or(|sub_id| Some(sub_id) == inspection.sub_id.as_ref())
+            })
+    }
+}
+
+fn value(kind: FieldKind, value: &str) -> &str {
+    match kind {
+        FieldKind::Secret => "<redacted>",
+        _ => value,
+    }
+}
+
+fn describe(issue: &FieldIssue) -> String {
+    match issue {
+        FieldIssue::Unknown => "unknown packet id".to_string(),
+        FieldIssue::UnknownSubId { sub_id } => format!("unknown sub-id {}", sub_id),
+        FieldIssue::Missing { name } => format!("missing field {}", name),
+        FieldIssue::Invalid {
+            index,
+            name,
+            expected,
+        } => format!("field {} ({}) is not {}", index, name, expected),
+        FieldIssue::Unexpected { index } => format!("unexpected fields from field {} on", index),
+    }
+}
+
+fn print_text<W: Write>(
+    out: &mut W,
+    number: usize,
+    inspection: &Inspection,
+    debug: bool,
+) -> io::Result<()> {
+    let direction = match inspection.direction {
+        Direction::Client => "client",
+        Direction::Server => "server",
+    };
+    let id = match &inspection.sub_id {
+        Some(sub_id) => format!("{}:{}", inspection.id, sub_id),
+        None => inspection.id.clone(),
+    };
+    writeln!(
+        out,
+        "#{} {} {} {}",
+        number,
+        direction,
+        id,
+        inspection.name.unwrap_or("?")
+    )?;
+    let width = inspection
+        .fields
+        .iter()
+        .map(|field| field.name.len())
+        .max()
+        .unwrap_or(0);
+    for field in &inspection.fields {
+        writeln!(
+            out,
+            "  {:width$}  {:?}",
+            field.name,
+            value(field.kind, &field.value),
+            width = width
+        )?;
+    }
+    if let Some(issue) = &inspection.issue {
+        writeln!(out, "  ! {}", describe(issue))?;
+    }
+    if let (true, Some(packet)) = (debug, &inspection.packet) {
+        writeln!(out, "{:#?}", packet)?;
+    }
+    writeln!(out)
+}
+
+fn print_json<W: Write>(out: &mut W, number: usize, inspection: &Inspection) -> io::Result<()> {
+    let fields = inspection
+        .fields
+        .iter()
+        .map(|field| json!({ "name": field.name, "value": value(field.kind, &field.value) }))
+        .collect::<Vec<_>>();
+    let object = json!({
+        "line": number,
+        "direction": match inspection.direction {
+            Direction::Client => "client",
+            Direction::Server => "server",
+        },
+        "id": inspection.id,
+        "sub_id": inspection.sub_id,
+        "name": inspection.name,
+        "fields": fields,
+        "error": inspection.issue.as_ref().map(describe),
+    });
+    serde_json::to_writer(&mut *out, &object)?;
+    writeln!(out)
+}
+
+fn run(options: &Options) -> io::Result<()> {
+    let input: Box<dyn BufRead> = match options.path.as_deref() {
+        None | Some("-") => Box::new(io::stdin().lock()),
+        Some(path) => Box::new(BufReader::new(File::open(path)?)),
+    };
+    let default_direction = match options.client {
+        true => Direction::Client,
+        false => Direction::Server,
+    };
+    let mut out = io::stdout().lock();
+    for (index, line) in input.lines().enumerate() {
+        let line = line?;
+        let number = index + 1;
+        let line = line.trim_end_matches('\r');
+        if line.is_empty() || (options.recording && line.starts_with('#')) {
+            continue;
+        }
+        let (direction, raw) = if options.recording {
+            match Frame::from_line(line) {
+                Some(frame) => (frame.direction, frame.raw),
+                None => {
+                    eprintln!("line {}: not a recorded frame", number);
+                    continue;
+                }
+            }
+        } else {
+            (default_direction, line.to_string())
+        };
+        let inspection = inspect(direction, &raw);
+        if !options.shows(&inspection) {
+            continue;
+        }
+        if options.json {
+            print_json(&mut out, number, &inspection)?;
+        } else {
+            print_text(&mut out, number, &inspection, options.debug)?;
+        }
+    }
+    Ok(())
+}
+
+fn main() -> ExitCode {
+    let options = match Options::parse(env::args().skip(1)) {
+        Ok(options) => options,
+        Err(error) if error.is_empty() => {
+            println!("{}", USAGE);
+            return ExitCode::SUCCESS;
+        }
+        Err(error) => {
+            eprintln!("kanii: {}\n\n{}", error, USAGE);
+            return ExitCode::from(2);
+        }
+    };
+    match run(&options) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(error) if error.kind() == io::ErrorKind::BrokenPipe => ExitCode::SUCCESS,
+        Err(error) => {
+            eprintln!("kanii: {}", error);
+            ExitCode::FAILURE
+        }
+    }
+}
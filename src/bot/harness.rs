@@ -0,0 +1,91 @@
+use crate::packets::{
+    client::ClientPacket,
+    server::{ChatMessagePacket, JoinAuthPacket, ServerPacket},
+    types::{Color, MessageFlags, UserPermissions},
+};
+
+use super::Bot;
+
+const HARNESS_BOT_ID: &str = "1000";
+const HARNESS_START: i64 = 1_700_000_000;
+
+/// Drives a `Bot` with synthetic packets, no network involved.
+pub struct Harness {
+    bot: Bot,
+    timestamp: i64,
+    sequence: u64,
+}
+
+impl Harness {
+    /// Logs the bot in as user `1000` in channel `lounge`.
+    pub fn new(bot: Bot) -> Self {
+        let mut harness = Harness {
+            bot,
+            timestamp: HARNESS_START,
+            sequence: 0,
+        };
+        harness.feed(&ServerPacket::JoinAuth(JoinAuthPacket::GoodAuth {
+            user_id: HARNESS_BOT_ID.to_string(),
+            username: "bot".to_string(),
+            color: Color::default(),
+            user_permissions: UserPermissions::default(),
+            channel_name: "lounge".to_string(),
+            max_msg_length: 2000,
+            capabilities: Vec::new(),
+        }));
+        harness
+    }
+
+    pub fn bot(&self) -> &Bot {
+        &self.bot
+    }
+
+    pub fn bot_mut(&mut self) -> &mut Bot {
+        &mut self.bot
+    }
+
+    /// Moves the harness clock, which stamps every generated message.
+    pub fn advance(&mut self, seconds: i64) {
+        self.timestamp += seconds;
+    }
+
+    pub fn feed(&mut self, packet: &ServerPacket) -> Vec<ClientPacket> {
+        self.bot.handle(packet)
+    }
+
+    pub fn join(&mut self, user_id: &str, username: &str, user_permissions: UserPermissions) {
+        let sequence_id = self.next_sequence();
+        self.feed(&ServerPacket::JoinAuth(JoinAuthPacket::Join {
+            timestamp: self.timestamp,
+            user_id: user_id.to_string(),
+            username: username.to_string(),
+            color: Color::default(),
+            user_permissions,
+            sequence_id,
+        }));
+    }
+
+    /// Sends `text` as `user_id` and returns the text of every reply.
+    pub fn say(&mut self, user_id: &str, text: &str) -> Vec<String> {
+        let sequence_id = self.next_sequence();
+        self.feed(&ServerPacket::ChatMessage(ChatMessagePacket {
+            timestamp: self.timestamp,
+            user_id: user_id.to_string(),
+            message: text.to_string(),
+            sequence_id,
+            message_flags: MessageFlags::default(),
+            channel_name: None,
+        }))
+        .into_iter()
+        .filter_map(|packet| match packet {
+            ClientPacket::Message(packet) => Some(packet.message),
+            _ => None,
+        })
+        .collect()
+    }
+
+    fn next_sequence(&mut self) -> String {
+        self.sequence += 1;
+        self.sequence.to_string()
+    }
+}
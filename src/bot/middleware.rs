@@ -0,0 +1,122 @@
+use std::{collections::HashMap, time::Duration};
+
+use super::Context;
+
+/// Decides whether a matched message may reach its handler.
+///
+/// Middleware may reply through the context when it turns a message away.
+pub trait Middleware {
+    fn allow(&mut self, ctx: &mut Context) -> bool;
+
+    /// Called once the handler ran, after every middleware allowed it.
+    fn ran(&mut self, _ctx: &Context) {}
+}
+
+impl<F> Middleware for F
+where
+    F: FnMut(&mut Context) -> bool,
+{
+    fn allow(&mut self, ctx: &mut Context) -> bool {
+        self(ctx)
+    }
+}
+
+/// Rate limits handlers using the server timestamps of incoming messages.
+///
+/// Only messages that actually reached their handler start a cooldown.
+pub struct Cooldown {
+    duration: Duration,
+    per_user: bool,
+    last_used: HashMap<String, i64>,
+}
+
+impl Cooldown {
+    pub fn per_user(duration: Duration) -> Self {
+        Cooldown {
+            duration,
+            per_user: true,
+            last_used: HashMap::new(),
+        }
+    }
+
+    pub fn global(duration: Duration) -> Self {
+        Cooldown {
+            duration,
+            per_user: false,
+            last_used: HashMap::new(),
+        }
+    }
+}
+
+impl Cooldown {
+    fn key(&self, ctx: &Context) -> String {
+        if self.per_user {
+            ctx.sender.user_id.clone()
+        } else {
+            String::new()
+        }
+    }
+}
+
+impl Middleware for Cooldown {
+    fn allow(&mut self, ctx: &mut Context) -> bool {
+        let now = ctx.timestamp();
+        // Timestamps only have whole seconds, so a cooldown below a second
+        // still holds for the rest of the second it started in.
+        !matches!(self.last_used.get(&self.key(ctx)),
+            Some(last_used) if elapsed(*last_used, now) < self.duration)
+    }
+
+    fn ran(&mut self, ctx: &Context) {
+        self.last_used.insert(self.key(ctx), ctx.timestamp());
+    }
+}
+
+fn elapsed(since: i64, now: i64) -> Duration {
+    Duration::from_secs(u64::try_from(now - since).unwrap_or(0))
+}
+
+/// Only lets through senders of at least the given rank, or moderators.
+pub struct RequireRank {
+    rank: u8,
+    moderator: bool,
+    denial: Option<String>,
+}
+
+impl RequireRank {
+    pub fn new(rank: u8) -> Self {
+        RequireRank {
+            rank,
+            moderator: false,
+            denial: None,
+        }
+    }
+
+    pub fn moderator() -> Self {
+        RequireRank {
+            rank: 0,
+            moderator: true,
+            denial: None,
+        }
+    }
+
+    /// Replies with `text` whenever a sender is turned away.
+    pub fn deny_with(mut self, text: &str) -> Self {
+        self.denial = Some(text.to_string());
+        self
+    }
+}
+
+impl Middleware for RequireRank {
+    fn allow(&mut self, ctx: &mut Context) -> bool {
+        let permissions = &ctx.sender.user_permissions;
+        let allowed =
+            permissions.rank >= self.rank && (!self.moderator || permissions.can_moderate);
+        if !allowed {
+            if let Some(denial) = &self.denial {
+                ctx.reply(denial);
+            }
+        }
+        allowed
+    }
+}
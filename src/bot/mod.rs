@@ -0,0 +1,337 @@
+pub mod harness;
+pub mod middleware;
+
+use regex::Regex;
+
+use crate::{
+    client::ChatState,
+    packets::{
+        bot_message::BOT_USER_ID,
+        client::{ClientPacket, MessagePacket},
+        server::{ChatMessagePacket, ServerPacket},
+        types::UserContext,
+    },
+};
+
+pub use harness::Harness;
+pub use middleware::{Cooldown, Middleware, RequireRank};
+
+const DEFAULT_PREFIX: &str = "!";
+
+/// Everything a handler gets to see about a matched message.
+pub struct Context<'a> {
+    pub sender: UserContext,
+    pub channel: Option<String>,
+    pub message: &'a ChatMessagePacket,
+    pub args: Vec<String>,
+    pub state: &'a ChatState,
+    bot_id: &'a str,
+    replies: &'a mut Vec<ClientPacket>,
+}
+
+impl Context<'_> {
+    pub fn text(&self) -> &str {
+        &self.message.message
+    }
+
+    pub fn timestamp(&self) -> i64 {
+        self.message.timestamp
+    }
+
+    pub fn reply(&mut self, text: &str) {
+        self.replies.push(ClientPacket::Message(MessagePacket {
+            user_id: self.bot_id.to_string(),
+            message: text.to_string(),
+        }));
+    }
+}
+
+enum Pattern {
+    Command(String),
+    Regex(Regex),
+}
+
+impl Pattern {
+    fn matches(&self, prefix: &str, text: &str) -> Option<Vec<String>> {
+        match self {
+            Self::Command(name) => {
+                let rest = text.strip_prefix(prefix)?;
+                let (command, args) = rest.split_once(char::is_whitespace).unwrap_or((rest, ""));
+                if !command.eq_ignore_ascii_case(name) {
+                    return None;
+                }
+                Some(args.split_whitespace().map(str::to_string).collect())
+            }
+            Self::Regex(regex) => {
+                let captures = regex.captures(text)?;
+                Some(
+                    captures
+                        .iter()
+                        .skip(1)
+                        .map(|group| group.map_or("", |group| group.as_str()).to_string())
+                        .collect(),
+                )
+            }
+        }
+    }
+}
+
+pub struct Route {
+    pattern: Pattern,
+    middleware: Vec<Box<dyn Middleware>>,
+    handler: Box<dyn FnMut(&mut Context)>,
+}
+
+impl Route {
+    /// Attaches middleware that only guards this route.
+    pub fn with<M: Middleware + 'static>(&mut self, middleware: M) -> &mut Self {
+        self.middleware.push(Box::new(middleware));
+        self
+    }
+}
+
+/// Routes incoming chat messages to registered handlers.
+///
+/// The bot keeps its own `ChatState` so handlers know who is talking, and
+/// returns replies instead of sending them, leaving the transport to the caller.
+pub struct Bot {
+    prefix: String,
+    state: ChatState,
+    middleware: Vec<Box<dyn Middleware>>,
+    routes: Vec<Route>,
+}
+
+impl Default for Bot {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Bot {
+    pub fn new() -> Self {
+        Bot {
+            prefix: DEFAULT_PREFIX.to_string(),
+            state: ChatState::new(),
+            middleware: Vec::new(),
+            routes: Vec::new(),
+        }
+    }
+
+    pub fn with_prefix(mut self, prefix: &str) -> Self {
+        self.prefix = prefix.to_string();
+        self
+    }
+
+    pub fn state(&self) -> &ChatState {
+        &self.state
+    }
+
+    /// Registers a handler for `<prefix><name>`, receiving the following words as args.
+    pub fn command<F>(&mut self, name: &str, handler: F) -> &mut Route
+    where
+        F: FnMut(&mut Context) + 'static,
+    {
+        self.route(Pattern::Command(name.to_string()), Box::new(handler))
+    }
+
+    /// Registers a handler for messages matching `regex`, receiving capture groups as args.
+    pub fn regex<F>(&mut self, regex: Regex, handler: F) -> &mut Route
+    where
+        F: FnMut(&mut Context) + 'static,
+    {
+        self.route(Pattern::Regex(regex), Box::new(handler))
+    }
+
+    /// Attaches middleware that guards every route.
+    pub fn middleware<M: Middleware + 'static>(&mut self, middleware: M) -> &mut Self {
+        self.middleware.push(Box::new(middleware));
+        self
+    }
+
+    pub fn handle(&mut self, packet: &ServerPacket) -> Vec<ClientPacket> {
+        self.state.handle(packet);
+
+        let mut replies = Vec::new();
+        let Bot {
+            prefix,
+            state,
+            middleware,
+            routes,
+        } = self;
+        let ServerPacket::ChatMessage(message) = packet else {
+            return replies;
+        };
+        let Some(bot_id) = state.self_id() else {
+            return replies;
+        };
+        if message.user_id == bot_id || message.user_id == BOT_USER_ID {
+            return replies;
+        }
+
+        for route in routes.iter_mut() {
+            let Some(args) = route.pattern.matches(prefix, &message.message) else {
+                continue;
+            };
+            let sender = state
+                .user(&message.user_id)
+                .cloned()
+                .unwrap_or_else(|| UserContext {
+                    user_id: message.user_id.clone(),
+                    username: message.user_id.clone(),
+                    ..Default::default()
+                });
+            let mut context = Context {
+                sender,
+                channel: state.message_channel(message).map(str::to_string),
+                message,
+                args,
+                state,
+                bot_id,
+                replies: &mut replies,
+            };
+            let allowed = middleware
+                .iter_mut()
+                .chain(route.middleware.iter_mut())
+                .all(|middleware| middleware.allow(&mut context));
+            if allowed {
+                (route.handler)(&mut context);
+                for middleware in middleware.iter_mut().chain(route.middleware.iter_mut()) {
+                    middleware.ran(&context);
+                }
+            }
+            break;
+        }
+        replies
+    }
+
+    fn route(&mut self, pattern: Pattern, handler: Box<dyn FnMut(&mut Context)>) -> &mut Route {
+        self.routes.push(Route {
+            pattern,
+            middleware: Vec::new(),
+            handler,
+        });
+        self.routes.last_mut().unwrap()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+    use crate::packets::types::{MessageFlags, UserPermissions};
+
+    fn rank(rank: u8) -> UserPermissions {
+        UserPermissions {
+            rank,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn routes_commands_and_regexes() {
+        let mut bot = Bot::new();
+        bot.command("echo", |ctx| {
+            let text = ctx.args.join(" ");
+            ctx.reply(&text);
+        });
+        bot.regex(Regex::new(r"(?i)\bhello (\w+)").unwrap(), |ctx| {
+            let text = format!("hi {}, says {}", ctx.args[0], ctx.sender.username);
+            ctx.reply(&text);
+        });
+
+        let mut harness = Harness::new(bot);
+        harness.join("7", "flash", rank(1));
+        assert_eq!(harness.say("7", "!echo a  b"), vec!["a b"]);
+        assert_eq!(harness.say("7", "!ECHO"), vec![""]);
+        assert!(harness.say("7", "!echoes").is_empty());
+        assert!(harness.say("7", "! echo").is_empty());
+        assert_eq!(
+            harness.say("7", "well Hello kanii"),
+            vec!["hi kanii, says flash"]
+        );
+        assert!(harness.say(BOT_USER_ID, "!echo loop").is_empty());
+    }
+
+    #[test]
+    fn gates_on_rank() {
+        let mut bot = Bot::new().with_prefix(".");
+        bot.command("kick", |ctx| ctx.reply("kicked"))
+            .with(RequireRank::new(5).deny_with("no"));
+
+        let mut harness = Harness::new(bot);
+        harness.join("1", "user", rank(1));
+        harness.join("2", "mod", rank(5));
+        assert_eq!(harness.say("1", ".kick x"), vec!["no"]);
+        assert_eq!(harness.say("2", ".kick x"), vec!["kicked"]);
+        assert_eq!(harness.say("3", ".kick x"), vec!["no"]);
+    }
+
+    #[test]
+    fn applies_cooldowns() {
+        let mut bot = Bot::new();
+        bot.middleware(Cooldown::per_user(Duration::from_secs(10)));
+        bot.command("ping", |ctx| ctx.reply("pong"));
+
+        let mut harness = Harness::new(bot);
+        harness.join("1", "a", rank(1));
+        harness.join("2", "b", rank(1));
+        assert_eq!(harness.say("1", "!ping").len(), 1);
+        assert!(harness.say("1", "!ping").is_empty());
+        assert_eq!(harness.say("2", "!ping").len(), 1);
+        harness.advance(10);
+        assert_eq!(harness.say("1", "!ping").len(), 1);
+    }
+
+    #[test]
+    fn denied_messages_keep_the_cooldown() {
+        let mut bot = Bot::new();
+        bot.middleware(Cooldown::per_user(Duration::from_secs(10)));
+        bot.command("kick", |ctx| ctx.reply("kicked"))
+            .with(RequireRank::new(5).deny_with("no"));
+        bot.command("ping", |ctx| ctx.reply("pong"));
+
+        let mut harness = Harness::new(bot);
+        harness.join("1", "user", rank(1));
+        assert_eq!(harness.say("1", "!kick x"), vec!["no"]);
+        assert_eq!(harness.say("1", "!ping"), vec!["pong"]);
+        assert!(harness.say("1", "!ping").is_empty());
+    }
+
+    #[test]
+    fn cooldowns_below_a_second_still_apply() {
+        let mut bot = Bot::new();
+        bot.middleware(Cooldown::global(Duration::from_millis(500)));
+        bot.command("ping", |ctx| ctx.reply("pong"));
+
+        let mut harness = Harness::new(bot);
+        assert_eq!(harness.say("1", "!ping").len(), 1);
+        assert!(harness.say("1", "!ping").is_empty());
+        harness.advance(1);
+        assert_eq!(harness.say("1", "!ping").len(), 1);
+    }
+
+    #[test]
+    fn uses_the_channel_of_the_message() {
+        let mut bot = Bot::new();
+        bot.command("where", |ctx| {
+            let channel = ctx.channel.clone().unwrap_or_default();
+            ctx.reply(&channel);
+        });
+
+        let mut harness = Harness::new(bot);
+        assert_eq!(harness.say("1", "!where"), vec!["lounge"]);
+        let replies = harness.feed(&ServerPacket::ChatMessage(ChatMessagePacket {
+            timestamp: 0,
+            user_id: "1".to_string(),
+            message: "!where".to_string(),
+            sequence_id: "1".to_string(),
+            message_flags: MessageFlags::default(),
+            channel_name: Some("games".to_string()),
+        }));
+        assert!(matches!(
+            replies.as_slice(),
+            [ClientPacket::Message(packet)] if packet.message == "games"
+        ));
+    }
+}
@@ -0,0 +1,297 @@
+use std::{
+    io,
+    net::TcpStream,
+    time::{Duration, Instant},
+};
+
+use tungstenite::{
+    client::{uri_mode, IntoClientRequest},
+    error::UrlError,
+    handshake::HandshakeError,
+    stream::{MaybeTlsStream, Mode},
+    Message, WebSocket,
+};
+
+use crate::packets::{
+    client::ClientPacket,
+    server::ServerPacket,
+    types::{ParsePacketError, Sockchatable},
+};
+
+use super::session::Session;
+
+#[derive(Debug)]
+pub enum Error {
+    WebSocket(Box<tungstenite::Error>),
+    Parse(ParsePacketError),
+    Closed,
+}
+
+impl From<tungstenite::Error> for Error {
+    fn from(error: tungstenite::Error) -> Self {
+        match error {
+            tungstenite::Error::ConnectionClosed | tungstenite::Error::AlreadyClosed => {
+                Error::Closed
+            }
+            error => Error::WebSocket(Box::new(error)),
+        }
+    }
+}
+
+/// Synchronous Sockchat client running on the calling thread.
+///
+/// Keepalive pings are sent from inside `recv` and `recv_timeout`, so the
+/// connection only stays alive while one of them is being called.
+pub struct Client {
+    socket: WebSocket<MaybeTlsStream<TcpStream>>,
+    /// Handle on the socket under whichever TLS layer `socket` uses, for
+    /// setting read timeouts.
+    tcp: TcpStream,
+    session: Session,
+}
+
+impl Client {
+    pub fn connect(url: &str, method: &str, authkey: &str) -> Result<Self, Error> {
+        Self::connect_with(url, Session::new(method, authkey))
+    }
+
+    pub fn connect_with(url: &str, mut session: Session) -> Result<Self, Error> {
+        let request = url.into_client_request()?;
+        let uri = request.uri();
+        // The handshake runs over a socket opened here rather than through
+        // `tungstenite::connect`, so that read timeouts can be set on it no
+        // matter what the WebSocket ends up wrapping it in.
+        if let Mode::Tls = uri_mode(uri)? {
+            return Err(tungstenite::Error::Url(UrlError::TlsFeatureNotEnabled).into());
+        }
+        let host = uri
+            .host()
+            .ok_or(tungstenite::Error::Url(UrlError::NoHostName))?;
+        let host = host.trim_start_matches('[').trim_end_matches(']');
+        let port = uri.port_u16().unwrap_or(80);
+        let stream = TcpStream::connect((host, port)).map_err(tungstenite::Error::Io)?;
+        stream.set_nodelay(true).map_err(tungstenite::Error::Io)?;
+        let tcp = stream.try_clone().map_err(tungstenite::Error::Io)?;
+        let (socket, _) = tungstenite::client(request, MaybeTlsStream::Plain(stream)).map_err(
+            |error| match error {
+                HandshakeError::Failure(error) => error,
+                HandshakeError::Interrupted(_) => io::Error::from(io::ErrorKind::WouldBlock).into(),
+            },
+        )?;
+        let auth = session.authenticate();
+        let mut client = Client {
+            socket,
+            tcp,
+            session,
+        };
+        client.send(auth)?;
+        Ok(client)
+    }
+
+    pub fn session(&self) -> &Session {
+        &self.session
+    }
+
+    pub fn send(&mut self, packet: ClientPacket) -> Result<(), Error> {
+        self.socket.send(Message::Text(packet.to_sockstr()))?;
+        Ok(())
+    }
+
+    /// Blocks until the next packet arrives.
+    pub fn recv(&mut self) -> Result<ServerPacket, Error> {
+        loop {
+            if let Some(packet) = self.read_until(None)? {
+                return Ok(packet);
+            }
+        }
+    }
+
+    /// Blocks for at most `timeout`, returning `None` if nothing arrived.
+    pub fn recv_timeout(&mut self, timeout: Duration) -> Result<Option<ServerPacket>, Error> {
+        self.read_until(Some(Instant::now() + timeout))
+    }
+
+    pub fn close(&mut self) -> Result<(), Error> {
+        self.socket.close(None)?;
+        loop {
+            match self.socket.read() {
+                Ok(_) => continue,
+                Err(error) => match Error::from(error) {
+                    Error::Closed => return Ok(()),
+                    error => return Err(error),
+                },
+            }
+        }
+    }
+
+    fn read_until(&mut self, deadline: Option<Instant>) -> Result<Option<ServerPacket>, Error> {
+        loop {
+            let now = Instant::now();
+            if let Some(ping) = self.session.poll_ping(now) {
+                self.send(ping)?;
+            }
+            if deadline.is_some_and(|deadline| now >= deadline) {
+                return Ok(None);
+            }
+
+            let wake = [deadline, self.session.next_ping()]
+                .into_iter()
+                .flatten()
+                .min()
+                .map(|wake| {
+                    wake.saturating_duration_since(now)
+                        .max(Duration::from_millis(1))
+                });
+            self.set_read_timeout(wake)?;
+
+            match self.socket.read() {
+                Ok(Message::Text(text)) => {
+                    let packet = text.parse::<ServerPacket>().map_err(Error::Parse)?;
+                    self.session.handle(&packet, Instant::now());
+                    return Ok(Some(packet));
+                }
+                Ok(_) => continue,
+                Err(tungstenite::Error::Io(error))
+                    if matches!(
+                        error.kind(),
+                        io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut
+                    ) =>
+                {
+                    continue
+                }
+                Err(error) => return Err(error.into()),
+            }
+        }
+    }
+
+    fn set_read_timeout(&mut self, timeout: Option<Duration>) -> Result<(), Error> {
+        self.tcp
+            .set_read_timeout(timeout)
+            .map_err(|error| Error::from(tungstenite::Error::Io(error)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{net::TcpListener, thread};
+
+    use super::*;
+    use crate::packets::server::JoinAuthPacket;
+
+    fn fake_server<F>(script: F) -> String
+    where
+        F: FnOnce(&mut WebSocket<TcpStream>) + Send + 'static,
+    {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap();
+        thread::spawn(move || {
+            let (stream, _) = listener.accept().unwrap();
+            let mut socket = tungstenite::accept(stream).unwrap();
+            script(&mut socket);
+        });
+        format!("ws://{}", address)
+    }
+
+    fn read_text(socket: &mut WebSocket<TcpStream>) -> String {
+        loop {
+            if let Message::Text(text) = socket.read().unwrap() {
+                return text;
+            }
+        }
+    }
+
+    #[test]
+    fn authenticates_and_exchanges_messages() {
+        let url = fake_server(|socket| {
+            assert_eq!(read_text(socket), "1\tMisuzu\tsecret");
+            socket
+                .send(Message::Text(
+                    "1\ty\t42\tkanii\t#f00\t0 0 0 0 0\tlounge\t2000".to_string(),
+                ))
+                .unwrap();
+            let echo = read_text(socket).replacen("2\t42\t", "2\t1700000000\t42\t", 1);
+            socket
+                .send(Message::Text(format!("{}\t1\t00000", echo)))
+                .unwrap();
+            while socket.read().is_ok() {}
+        });
+
+        let mut client = Client::connect(&url, "Misuzu", "secret").unwrap();
+        assert!(matches!(
+            client.recv().unwrap(),
+            ServerPacket::JoinAuth(JoinAuthPacket::GoodAuth { .. })
+        ));
+        assert!(client.session().is_connected());
+        assert!(client
+            .recv_timeout(Duration::from_millis(20))
+            .unwrap()
+            .is_none());
+
+        let message = client.session().message("hello").unwrap();
+        client.send(message).unwrap();
+        match client.recv().unwrap() {
+            ServerPacket::ChatMessage(packet) => assert_eq!(packet.message, "hello"),
+            packet => panic!("unexpected packet {:?}", packet),
+        }
+        client.close().unwrap();
+    }
+
+    #[test]
+    fn pings_while_waiting() {
+        let url = fake_server(|socket| {
+            read_text(socket);
+            socket
+                .send(Message::Text(
+                    "1\ty\t42\tkanii\t#f00\t0 0 0 0 0\tlounge\t2000".to_string(),
+                ))
+                .unwrap();
+            assert_eq!(read_text(socket), "0\t42");
+            socket.send(Message::Text("0\tpong".to_string())).unwrap();
+            while socket.read().is_ok() {}
+        });
+
+        let session =
+            Session::new("Misuzu", "secret").with_ping_interval(Duration::from_millis(10));
+        let mut client = Client::connect_with(&url, session).unwrap();
+        client.recv().unwrap();
+        match client.recv_timeout(Duration::from_secs(5)).unwrap() {
+            Some(ServerPacket::Pong(packet)) => assert_eq!(packet.text, "pong"),
+            packet => panic!("unexpected packet {:?}", packet),
+        }
+    }
+
+    #[test]
+    fn reports_closed_connection() {
+        let url = fake_server(|socket| {
+            read_text(socket);
+            socket.close(None).unwrap();
+            while socket.read().is_ok() {}
+        });
+
+        let mut client = Client::connect(&url, "Misuzu", "secret").unwrap();
+        assert!(matches!(client.recv(), Err(Error::Closed)));
+    }
+
+    #[test]
+    fn reports_truncated_packets() {
+        let url = fake_server(|socket| {
+            read_text(socket);
+            for text in ["5", "4", "7\t9", "8", "8\t9", "8\t4"] {
+                socket.send(Message::Text(text.to_string())).unwrap();
+            }
+            while socket.read().is_ok() {}
+        });
+
+        let mut client = Client::connect(&url, "Misuzu", "secret").unwrap();
+        for _ in 0..5 {
+            assert!(matches!(
+                client.recv(),
+                Err(Error::Parse(ParsePacketError::WrongFormat))
+            ));
+        }
+        assert!(matches!(
+            client.recv(),
+            Ok(ServerPacket::ContextClearing(_))
+        ));
+    }
+}
@@ -0,0 +1,256 @@
+use std::time::{Duration, Instant};
+
+use super::{
+    blocking::{Client, Error},
+    session::{Session, SessionState},
+    view::{Action, ChatView},
+};
+
+const INITIAL_BACKOFF: Duration = Duration::from_secs(1);
+const MAX_BACKOFF: Duration = Duration::from_secs(60);
+
+/// Blocking connection for interactive clients that reconnects by itself,
+/// doubling the delay after every failed attempt.
+///
+/// What happens to the connection is reported to a `ChatView` as notices,
+/// and its packets are fed into it.
+pub struct Connection {
+    url: String,
+    /// Copied for every attempt; also stands in for the client's session
+    /// while disconnected.
+    session: Session,
+    client: Option<Client>,
+    retry_at: Option<Instant>,
+    backoff: Duration,
+    initial_backoff: Duration,
+    max_backoff: Duration,
+}
+
+impl Connection {
+    /// Connects on the first `poll`.
+    pub fn new(url: &str, session: Session) -> Self {
+        Connection {
+            url: url.to_string(),
+            session,
+            client: None,
+            retry_at: Some(Instant::now()),
+            backoff: INITIAL_BACKOFF,
+            initial_backoff: INITIAL_BACKOFF,
+            max_backoff: MAX_BACKOFF,
+        }
+    }
+
+    pub fn with_backoff(mut self, initial: Duration, max: Duration) -> Self {
+        self.backoff = initial;
+        self.initial_backoff = initial;
+        self.max_backoff = max;
+        self
+    }
+
+    pub fn session(&self) -> &Session {
+        self.client
+            .as_ref()
+            .map_or(&self.session, |client| client.session())
+    }
+
+    /// Whether a socket is open, authenticated or not.
+    pub fn is_open(&self) -> bool {
+        self.client.is_some()
+    }
+
+    /// Connects or reads what arrived, returning whether anything changed.
+    pub fn poll(&mut self, view: &mut ChatView) -> bool {
+        if self.client.is_none() {
+            let due = self
+                .retry_at
+                .is_some_and(|retry_at| Instant::now() >= retry_at);
+            if due {
+                self.connect(view);
+            }
+            return due;
+        }
+        let mut changed = false;
+        let mut timeout = Duration::from_millis(30);
+        for _ in 0..100 {
+            let client = self.client.as_mut().unwrap();
+            match client.recv_timeout(timeout) {
+                Ok(Some(packet)) => view.handle(&packet),
+                Ok(None) => break,
+                // The server closing the socket after rejecting or kicking
+                // us is handled below, without retrying.
+                Err(_)
+                    if matches!(
+                        client.session().state(),
+                        SessionState::Rejected(_) | SessionState::Disconnected
+                    ) =>
+                {
+                    changed = true;
+                    break;
+                }
+                Err(error) => {
+                    self.disconnected(view, error);
+                    return true;
+                }
+            }
+            changed = true;
+            timeout = Duration::from_millis(1);
+        }
+        let client = self.client.as_ref().unwrap();
+        match client.session().state() {
+            SessionState::Connected => self.backoff = self.initial_backoff,
+            SessionState::Rejected(reason) => {
+                view.error(&format!("authentication failed: {}", reason));
+                self.drop_client(None);
+            }
+            SessionState::Disconnected => {
+                view.error("disconnected by the server, /reconnect to try again");
+                self.drop_client(None);
+            }
+            _ => {}
+        }
+        changed
+    }
+
+    /// Carries out what the view asked for, returning false once the user
+    /// wants out.
+    pub fn send(&mut self, view: &mut ChatView, action: Action) -> bool {
+        match action {
+            Action::None => {}
+            Action::Send(packets) => {
+                for packet in packets {
+                    let Some(client) = self.client.as_mut() else {
+                        break;
+                    };
+                    if let Err(error) = client.send(packet) {
+                        self.disconnected(view, error);
+                    }
+                }
+            }
+            Action::Reconnect => {
+                self.drop_client(Some(Instant::now()));
+                self.backoff = self.initial_backoff;
+            }
+            Action::Quit => {
+                self.drop_client(None);
+                return false;
+            }
+        }
+        true
+    }
+
+    fn connect(&mut self, view: &mut ChatView) {
+        view.notice(&format!("connecting to {}", self.url));
+        match Client::connect_with(&self.url, self.session.clone()) {
+            Ok(client) => {
+                view.reset();
+                self.client = Some(client);
+                self.retry_at = None;
+            }
+            Err(error) => {
+                view.error(&format!("connection failed: {:?}", error));
+                self.schedule_retry(view);
+            }
+        }
+    }
+
+    fn disconnected(&mut self, view: &mut ChatView, error: Error) {
+        view.error(&format!("connection lost: {:?}", error));
+        self.client = None;
+        self.schedule_retry(view);
+    }
+
+    fn schedule_retry(&mut self, view: &mut ChatView) {
+        view.notice(&format!("reconnecting in {}s", self.backoff.as_secs_f32()));
+        self.retry_at = Some(Instant::now() + self.backoff);
+        self.backoff = (self.backoff * 2).min(self.max_backoff);
+    }
+
+    fn drop_client(&mut self, retry_at: Option<Instant>) {
+        if let Some(mut client) = self.client.take() {
+            let _ = client.close();
+        }
+        self.retry_at = retry_at;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        packets::{client::ClientPacket, types::BadAuthReason},
+        server::fake::{AuthOutcome, FakeServerConfig},
+    };
+
+    fn poll_until<F>(connection: &mut Connection, view: &mut ChatView, mut condition: F) -> bool
+    where
+        F: FnMut(&Connection) -> bool,
+    {
+        let deadline = Instant::now() + Duration::from_secs(5);
+        while Instant::now() < deadline {
+            connection.poll(view);
+            if condition(connection) {
+                return true;
+            }
+            std::thread::sleep(Duration::from_millis(5));
+        }
+        false
+    }
+
+    #[test]
+    fn reconnects_after_a_drop() {
+        let server = FakeServerConfig::new().start().unwrap();
+        let mut view = ChatView::new();
+        let mut connection = Connection::new(&server.url(), Session::new("Misuzu", "kanii"))
+            .with_backoff(Duration::from_millis(10), Duration::from_millis(40));
+        assert!(!connection.session().is_connected());
+        assert!(poll_until(&mut connection, &mut view, |connection| {
+            connection.session().is_connected()
+        }));
+
+        server.drop_connections();
+        assert!(poll_until(&mut connection, &mut view, |connection| {
+            !connection.is_open()
+        }));
+        assert!(poll_until(&mut connection, &mut view, |connection| {
+            connection.session().is_connected()
+        }));
+
+        for c in "hello again".chars() {
+            view.input.insert(c);
+        }
+        let action = view.submit(connection.session());
+        assert!(connection.send(&mut view, action));
+        assert!(server.wait_for(Duration::from_secs(5), |received| {
+            let authentications = received
+                .iter()
+                .filter(|packet| matches!(packet, ClientPacket::Authentication(_)))
+                .count();
+            authentications == 2
+                && received.last().is_some_and(|packet| {
+                    matches!(packet, ClientPacket::Message(message) if message.message == "hello again")
+                })
+        }));
+        assert!(!connection.send(&mut view, Action::Quit));
+        assert!(!connection.is_open());
+    }
+
+    #[test]
+    fn stops_retrying_when_rejected() {
+        let server = FakeServerConfig::new()
+            .with_default_auth(AuthOutcome::Reject(BadAuthReason::AuthFail))
+            .start()
+            .unwrap();
+        let mut view = ChatView::new();
+        let mut connection = Connection::new(&server.url(), Session::new("Misuzu", "kanii"))
+            .with_backoff(Duration::from_millis(10), Duration::from_millis(40));
+        assert!(poll_until(&mut connection, &mut view, |connection| {
+            connection.is_open()
+        }));
+        assert!(poll_until(&mut connection, &mut view, |connection| {
+            !connection.is_open()
+        }));
+        std::thread::sleep(Duration::from_millis(50));
+        assert!(!connection.poll(&mut view));
+        assert!(!connection.is_open());
+    }
+}
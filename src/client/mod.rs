@@ -0,0 +1,15 @@
+#[cfg(feature = "blocking")]
+pub mod blocking;
+#[cfg(feature = "blocking")]
+pub mod connection;
+pub mod session;
+pub mod state;
+pub mod view;
+pub mod whisper;
+
+#[cfg(feature = "blocking")]
+pub use connection::Connection;
+pub use session::{SendError, Session, SessionState};
+pub use state::ChatState;
+pub use view::ChatView;
+pub use whisper::{Conversations, Thread, Whisper};
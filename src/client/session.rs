@@ -0,0 +1,360 @@
+use std::time::{Duration, Instant};
+
+use crate::packets::{
+    client::{
+        AuthenticationPacket, ClientPacket, MessageError, MessageLimits, MessagePacket, PingPacket,
+        MCHAN,
+    },
+    server::{ChannelEventPacket, ChannelSwitchingPacket, JoinAuthPacket, ServerPacket},
+    types::BadAuthReason,
+};
+
+const DEFAULT_PING_INTERVAL: Duration = Duration::from_secs(30);
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum SessionState {
+    Idle,
+    Authenticating,
+    Connected,
+    Rejected(BadAuthReason),
+    Disconnected,
+}
+
+/// Why a session could not build a packet.
+#[derive(Debug, Clone, PartialEq)]
+pub enum SendError {
+    /// Not authenticated yet, or no longer connected.
+    NotConnected,
+    /// The server did not confirm the capability the packet needs.
+    Unsupported(&'static str),
+    Message(MessageError),
+}
+
+impl From<MessageError> for SendError {
+    fn from(error: MessageError) -> Self {
+        SendError::Message(error)
+    }
+}
+
+/// Connection-independent client session logic.
+///
+/// A session never touches the network: transports feed it every received
+/// `ServerPacket` through `handle` and send whatever `ClientPacket`s it hands
+/// out, so the same logic backs every client flavour.
+#[derive(Clone)]
+pub struct Session {
+    method: String,
+    authkey: String,
+    capabilities: Vec<String>,
+    multi_channel: bool,
+    state: SessionState,
+    user_id: Option<String>,
+    channel_name: Option<String>,
+    max_msg_length: Option<i64>,
+    limits: MessageLimits,
+    ping_interval: Duration,
+    last_ping: Option<Instant>,
+}
+
+impl std::fmt::Debug for Session {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Session")
+            .field("method", &self.method)
+            .field("authkey", &"<redacted>")
+            .field("capabilities", &self.capabilities)
+            .field("state", &self.state)
+            .field("user_id", &self.user_id)
+            .field("channel_name", &self.channel_name)
+            .finish_non_exhaustive()
+    }
+}
+
+impl Session {
+    pub fn new(method: &str, authkey: &str) -> Self {
+        Session {
+            method: method.to_string(),
+            authkey: authkey.to_string(),
+            capabilities: Vec::new(),
+            multi_channel: false,
+            state: SessionState::Idle,
+            user_id: None,
+            channel_name: None,
+            max_msg_length: None,
+            limits: MessageLimits::default(),
+            ping_interval: DEFAULT_PING_INTERVAL,
+            last_ping: None,
+        }
+    }
+
+    pub fn with_ping_interval(mut self, ping_interval: Duration) -> Self {
+        self.ping_interval = ping_interval;
+        self
+    }
+
+    /// Capabilities to announce when authenticating, such as `MCHAN`.
+    pub fn with_capabilities(mut self, capabilities: &[&str]) -> Self {
+        self.capabilities = capabilities.iter().map(|c| c.to_string()).collect();
+        self
+    }
+
+    /// Whether the server confirmed `MCHAN` when accepting the session.
+    pub fn is_multi_channel(&self) -> bool {
+        self.multi_channel
+    }
+
+    /// Rules `messages` applies; the maximum length is taken over from the
+    /// server once authenticated.
+    pub fn with_limits(mut self, limits: MessageLimits) -> Self {
+        self.limits = limits;
+        self
+    }
+
+    pub fn limits(&self) -> &MessageLimits {
+        &self.limits
+    }
+
+    pub fn state(&self) -> &SessionState {
+        &self.state
+    }
+
+    pub fn is_connected(&self) -> bool {
+        self.state == SessionState::Connected
+    }
+
+    pub fn user_id(&self) -> Option<&str> {
+        self.user_id.as_deref()
+    }
+
+    pub fn channel_name(&self) -> Option<&str> {
+        self.channel_name.as_deref()
+    }
+
+    pub fn max_msg_length(&self) -> Option<i64> {
+        self.max_msg_length
+    }
+
+    /// Returns the packet that has to be sent right after the socket opens.
+    pub fn authenticate(&mut self) -> ClientPacket {
+        self.state = SessionState::Authenticating;
+        ClientPacket::Authentication(AuthenticationPacket {
+            method: self.method.clone(),
+            authkey: self.authkey.clone(),
+            capabilities: self.capabilities.clone(),
+        })
+    }
+
+    /// Takes in a packet received at `now`.
+    pub fn handle(&mut self, packet: &ServerPacket, now: Instant) {
+        match packet {
+            ServerPacket::JoinAuth(
+                good_auth @ JoinAuthPacket::GoodAuth {
+                    user_id,
+                    channel_name,
+                    max_msg_length,
+                    ..
+                },
+            ) => {
+                self.state = SessionState::Connected;
+                self.multi_channel = good_auth.supports(MCHAN);
+                self.user_id = Some(user_id.clone());
+                self.channel_name = Some(channel_name.clone());
+                self.max_msg_length = Some(*max_msg_length);
+                self.limits.max_length = usize::try_from(*max_msg_length).ok();
+                self.last_ping = Some(now);
+            }
+            ServerPacket::JoinAuth(JoinAuthPacket::BadAuth { reason, .. }) => {
+                self.state = SessionState::Rejected(reason.clone());
+            }
+            ServerPacket::ChannelSwitching(ChannelSwitchingPacket::ForcedSwitch {
+                channel_name,
+            }) => {
+                self.channel_name = Some(channel_name.clone());
+            }
+            ServerPacket::ChannelEvent(ChannelEventPacket::Update {
+                channel_name,
+                new_name,
+                ..
+            }) if self.channel_name.as_ref() == Some(channel_name) => {
+                self.channel_name = Some(new_name.clone());
+            }
+            ServerPacket::ForcedDisconnect(_) => {
+                self.state = SessionState::Disconnected;
+            }
+            _ => {}
+        }
+    }
+
+    /// Builds a single message packet according to the session's limits,
+    /// refusing text they would split.
+    pub fn message(&self, text: &str) -> Result<ClientPacket, SendError> {
+        let limits = MessageLimits {
+            split: false,
+            ..self.limits.clone()
+        };
+        let mut packets = MessagePacket::checked(self.connected_user_id()?, text, &limits)?;
+        Ok(ClientPacket::Message(packets.remove(0)))
+    }
+
+    /// Builds the packets for `text` according to the session's limits.
+    pub fn messages(&self, text: &str) -> Result<Vec<ClientPacket>, SendError> {
+        Ok(
+            MessagePacket::checked(self.connected_user_id()?, text, &self.limits)?
+                .into_iter()
+                .map(ClientPacket::Message)
+                .collect(),
+        )
+    }
+
+    /// Builds a private message to `username`, checked like `messages`.
+    pub fn whisper(&self, username: &str, text: &str) -> Result<Vec<ClientPacket>, SendError> {
+        self.messages(&format!("/msg {} {}", username, text))
+    }
+
+    /// Builds the command entering `channel`, on top of the current ones when
+    /// multi-channel support was negotiated.
+    pub fn join_channel(
+        &self,
+        channel: &str,
+        password: Option<&str>,
+    ) -> Result<ClientPacket, SendError> {
+        match password {
+            Some(password) => self.message(&format!("/join {} {}", channel, password)),
+            None => self.message(&format!("/join {}", channel)),
+        }
+    }
+
+    /// Builds the command leaving `channel`, once the server confirmed `MCHAN`.
+    pub fn leave_channel(&self, channel: &str) -> Result<ClientPacket, SendError> {
+        self.connected_user_id()?;
+        if !self.is_multi_channel() {
+            return Err(SendError::Unsupported(MCHAN));
+        }
+        self.message(&format!("/leave {}", channel))
+    }
+
+    fn connected_user_id(&self) -> Result<&str, SendError> {
+        self.user_id
+            .as_deref()
+            .filter(|_| self.is_connected())
+            .ok_or(SendError::NotConnected)
+    }
+
+    /// Point in time at which the next keepalive ping is due.
+    pub fn next_ping(&self) -> Option<Instant> {
+        let last_ping = self.last_ping.filter(|_| self.is_connected())?;
+        Some(last_ping + self.ping_interval)
+    }
+
+    /// Hands out a ping packet if one is due at `now`.
+    pub fn poll_ping(&mut self, now: Instant) -> Option<ClientPacket> {
+        let user_id = self.user_id.as_ref().filter(|_| self.is_connected())?;
+        if self.next_ping().is_some_and(|next_ping| now < next_ping) {
+            return None;
+        }
+        self.last_ping = Some(now);
+        Some(ClientPacket::Ping(PingPacket {
+            user_id: user_id.clone(),
+        }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::packets::types::Sockchatable;
+
+    #[test]
+    fn authenticates_and_pings() {
+        let mut session = Session::new("Misuzu", "secret");
+        assert_eq!(session.authenticate().to_sockstr(), "1\tMisuzu\tsecret");
+        assert_eq!(session.message("hi"), Err(SendError::NotConnected));
+        assert_eq!(session.messages("hi"), Err(SendError::NotConnected));
+
+        let packet = "1\ty\t42\tkanii\t#f00\t0 0 0 0 0\tlounge\t2000"
+            .parse::<ServerPacket>()
+            .unwrap();
+        let now = Instant::now();
+        session.handle(&packet, now);
+        assert!(session.is_connected());
+        assert_eq!(session.channel_name(), Some("lounge"));
+        session.handle(
+            &"4\t1\tlounge\tfoyer\t0\t0".parse::<ServerPacket>().unwrap(),
+            now,
+        );
+        assert_eq!(session.channel_name(), Some("foyer"));
+        assert_eq!(session.message("hi").unwrap().to_sockstr(), "2\t42\thi");
+        assert_eq!(
+            session.whisper("flash", "psst").unwrap()[0].to_sockstr(),
+            "2\t42\t/msg flash psst"
+        );
+
+        assert_eq!(session.limits().max_length, Some(2000));
+        assert_eq!(
+            session.messages(&"a".repeat(2001)),
+            Err(SendError::Message(MessageError::TooLong {
+                length: 2001,
+                max_length: 2000
+            }))
+        );
+
+        let due = session.next_ping().unwrap();
+        assert_eq!(due, now + DEFAULT_PING_INTERVAL);
+        assert!(session.poll_ping(now).is_none());
+        assert_eq!(session.poll_ping(due).unwrap().to_sockstr(), "0\t42");
+        assert!(session.poll_ping(due).is_none());
+    }
+
+    #[test]
+    fn negotiates_multi_channel() {
+        let mut session = Session::new("Misuzu", "secret").with_capabilities(&[MCHAN]);
+        assert_eq!(
+            session.authenticate().to_sockstr(),
+            "1\tMisuzu\tsecret\tMCHAN"
+        );
+        let good_auth = "1\ty\t42\tkanii\t#f00\t0 0 0 0 0\tlounge\t2000";
+        session.handle(&good_auth.parse::<ServerPacket>().unwrap(), Instant::now());
+        assert!(!session.is_multi_channel());
+        assert_eq!(
+            session.leave_channel("games"),
+            Err(SendError::Unsupported(MCHAN))
+        );
+
+        session.handle(
+            &format!("{}\tMCHAN", good_auth)
+                .parse::<ServerPacket>()
+                .unwrap(),
+            Instant::now(),
+        );
+        assert!(session.is_multi_channel());
+        assert_eq!(
+            session
+                .join_channel("games", Some("pw"))
+                .unwrap()
+                .to_sockstr(),
+            "2\t42\t/join games pw"
+        );
+        assert_eq!(
+            session.leave_channel("games").unwrap().to_sockstr(),
+            "2\t42\t/leave games"
+        );
+        assert_eq!(
+            Session::new("Misuzu", "secret").leave_channel("games"),
+            Err(SendError::NotConnected)
+        );
+    }
+
+    #[test]
+    fn remembers_rejection() {
+        let mut session = Session::new("Misuzu", "wrong");
+        session.authenticate();
+        session.handle(
+            &"1\tn\tauthfail\t0".parse::<ServerPacket>().unwrap(),
+            Instant::now(),
+        );
+        assert_eq!(
+            session.state(),
+            &SessionState::Rejected(BadAuthReason::AuthFail)
+        );
+        assert!(session.poll_ping(Instant::now()).is_none());
+    }
+}
@@ -0,0 +1,360 @@
+use std::collections::{HashMap, HashSet};
+
+use crate::packets::{
+    client::MCHAN,
+    server::{
+        ChannelEventPacket, ChannelSwitchingPacket, ChatMessagePacket, ContextInformationPacket,
+        JoinAuthPacket, ServerPacket,
+    },
+    types::{ChannelContext, UserContext},
+};
+
+/// Users and channels as seen from a client, rebuilt from server packets.
+///
+/// Membership is tracked per channel. Joins, departures and messages count
+/// towards the channel the server names, falling back to the current one for
+/// servers without multi-channel support.
+#[derive(Debug, Default)]
+pub struct ChatState {
+    self_id: Option<String>,
+    channel_name: Option<String>,
+    /// Whether the server confirmed `MCHAN`, which keeps earlier channels
+    /// joined on forced switches.
+    multi_channel: bool,
+    joined: Vec<String>,
+    members: HashMap<String, HashSet<String>>,
+    users: HashMap<String, UserContext>,
+    channels: Vec<ChannelContext>,
+}
+
+impl ChatState {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Channels the client is in, in the order they were joined.
+    pub fn joined_channels(&self) -> &[String] {
+        &self.joined
+    }
+
+    pub fn members(&self, channel_name: &str) -> impl Iterator<Item = &UserContext> {
+        self.members
+            .get(channel_name)
+            .into_iter()
+            .flatten()
+            .filter_map(|user_id| self.users.get(user_id))
+    }
+
+    /// Channel a chat message belongs to.
+    pub fn message_channel<'a>(&'a self, packet: &'a ChatMessagePacket) -> Option<&'a str> {
+        packet.channel_name.as_deref().or(self.channel_name())
+    }
+
+    pub fn self_id(&self) -> Option<&str> {
+        self.self_id.as_deref()
+    }
+
+    pub fn channel_name(&self) -> Option<&str> {
+        self.channel_name.as_deref()
+    }
+
+    pub fn user(&self, user_id: &str) -> Option<&UserContext> {
+        self.users.get(user_id)
+    }
+
+    pub fn user_by_name(&self, username: &str) -> Option<&UserContext> {
+        self.users
+            .values()
+            .find(|user| user.username.eq_ignore_ascii_case(username))
+    }
+
+    pub fn users(&self) -> impl Iterator<Item = &UserContext> {
+        self.users.values()
+    }
+
+    pub fn channels(&self) -> &[ChannelContext] {
+        &self.channels
+    }
+
+    pub fn handle(&mut self, packet: &ServerPacket) {
+        match packet {
+            ServerPacket::JoinAuth(
+                good_auth @ JoinAuthPacket::GoodAuth {
+                    user_id,
+                    username,
+                    color,
+                    user_permissions,
+                    channel_name,
+                    ..
+                },
+            ) => {
+                self.multi_channel = good_auth.supports(MCHAN);
+                self.self_id = Some(user_id.clone());
+                self.channel_name = Some(channel_name.clone());
+                self.joined = vec![channel_name.clone()];
+                self.insert(
+                    Some(channel_name),
+                    UserContext {
+                        user_id: user_id.clone(),
+                        username: username.clone(),
+                        color: color.clone(),
+                        user_permissions: user_permissions.clone(),
+                        visible: true,
+                    },
+                );
+            }
+
+            ServerPacket::JoinAuth(JoinAuthPacket::Join {
+                user_id,
+                username,
+                color,
+                user_permissions,
+                ..
+            }) => self.insert(
+                self.channel_name.clone().as_ref(),
+                UserContext {
+                    user_id: user_id.clone(),
+                    username: username.clone(),
+                    color: color.clone(),
+                    user_permissions: user_permissions.clone(),
+                    visible: true,
+                },
+            ),
+
+            ServerPacket::ChannelSwitching(ChannelSwitchingPacket::Join {
+                user_id,
+                username,
+                color,
+                user_permissions,
+                channel_name,
+                ..
+            }) => {
+                let channel_name = channel_name.clone().or(self.channel_name.clone());
+                if let Some(channel_name) = &channel_name {
+                    if self.self_id.as_ref() == Some(user_id) && !self.joined.contains(channel_name)
+                    {
+                        self.joined.push(channel_name.clone());
+                    }
+                }
+                self.insert(
+                    channel_name.as_ref(),
+                    UserContext {
+                        user_id: user_id.clone(),
+                        username: username.clone(),
+                        color: color.clone(),
+                        user_permissions: user_permissions.clone(),
+                        visible: true,
+                    },
+                );
+            }
+
+            ServerPacket::ChannelSwitching(ChannelSwitchingPacket::Departure {
+                user_id,
+                channel_name,
+                ..
+            }) => {
+                let Some(channel_name) = channel_name.clone().or(self.channel_name.clone()) else {
+                    return;
+                };
+                if self.self_id.as_ref() == Some(user_id) {
+                    self.leave(&channel_name);
+                } else if let Some(members) = self.members.get_mut(&channel_name) {
+                    members.remove(user_id);
+                }
+                self.forget_strays();
+            }
+
+            ServerPacket::ChannelSwitching(ChannelSwitchingPacket::ForcedSwitch {
+                channel_name,
+            }) => {
+                if !self.multi_channel {
+                    self.joined.clear();
+                    self.members.clear();
+                }
+                if !self.joined.contains(channel_name) {
+                    self.joined.push(channel_name.clone());
+                }
+                self.channel_name = Some(channel_name.clone());
+                if let Some(self_id) = self.self_id.clone() {
+                    self.members
+                        .entry(channel_name.clone())
+                        .or_default()
+                        .insert(self_id);
+                }
+                self.forget_strays();
+            }
+
+            ServerPacket::UserDisconnect(packet) => {
+                self.users.remove(&packet.user_id);
+                for members in self.members.values_mut() {
+                    members.remove(&packet.user_id);
+                }
+            }
+
+            ServerPacket::UserUpdate(packet) => {
+                if let Some(user) = self.users.get_mut(&packet.user_id) {
+                    user.username = packet.username.clone();
+                    user.color = packet.color.clone();
+                    user.user_permissions = packet.user_permissions.clone();
+                }
+            }
+
+            ServerPacket::ContextInformation(ContextInformationPacket::ExistingUsers {
+                contexts,
+                ..
+            }) => {
+                let channel_name = self.channel_name.clone();
+                for context in contexts {
+                    self.insert(channel_name.as_ref(), context.clone());
+                }
+            }
+
+            ServerPacket::ContextInformation(ContextInformationPacket::Channels {
+                contexts,
+                ..
+            }) => {
+                self.channels = contexts.clone();
+            }
+
+            ServerPacket::ContextClearing(packet) => {
+                if packet.user_list {
+                    let self_id = self.self_id.clone();
+                    if let Some(members) = self
+                        .channel_name
+                        .as_ref()
+                        .and_then(|channel_name| self.members.get_mut(channel_name))
+                    {
+                        members.retain(|user_id| Some(user_id) == self_id.as_ref());
+                    }
+                    self.forget_strays();
+                }
+                if packet.channel_list {
+                    self.channels.clear();
+                }
+            }
+
+            ServerPacket::ChannelEvent(ChannelEventPacket::Creation {
+                channel_name,
+                is_protected,
+                is_temporary,
+            }) => self.channels.push(ChannelContext {
+                channel_name: channel_name.clone(),
+                password_protected: *is_protected,
+                temporary: *is_temporary,
+            }),
+
+            ServerPacket::ChannelEvent(ChannelEventPacket::Update {
+                channel_name,
+                new_name,
+                is_protected,
+                is_temporary,
+            }) => {
+                if let Some(channel) = self
+                    .channels
+                    .iter_mut()
+                    .find(|channel| &channel.channel_name == channel_name)
+                {
+                    channel.channel_name = new_name.clone();
+                    channel.password_protected = *is_protected;
+                    channel.temporary = *is_temporary;
+                }
+                if self.channel_name.as_ref() == Some(channel_name) {
+                    self.channel_name = Some(new_name.clone());
+                }
+                for joined in self
+                    .joined
+                    .iter_mut()
+                    .filter(|joined| *joined == channel_name)
+                {
+                    *joined = new_name.clone();
+                }
+                if let Some(members) = self.members.remove(channel_name) {
+                    self.members.insert(new_name.clone(), members);
+                }
+            }
+
+            ServerPacket::ChannelEvent(ChannelEventPacket::Deletion { channel_name }) => {
+                self.channels
+                    .retain(|channel| &channel.channel_name != channel_name);
+                self.leave(channel_name);
+                self.forget_strays();
+            }
+
+            _ => {}
+        }
+    }
+
+    fn insert(&mut self, channel_name: Option<&String>, user: UserContext) {
+        if let Some(channel_name) = channel_name {
+            self.members
+                .entry(channel_name.clone())
+                .or_default()
+                .insert(user.user_id.clone());
+        }
+        self.users.insert(user.user_id.clone(), user);
+    }
+
+    fn leave(&mut self, channel_name: &str) {
+        self.joined.retain(|joined| joined != channel_name);
+        self.members.remove(channel_name);
+        if self.channel_name.as_deref() == Some(channel_name) {
+            self.channel_name = self.joined.first().cloned();
+        }
+    }
+
+    /// Drops users no longer sharing any channel with us.
+    fn forget_strays(&mut self) {
+        let members = &self.members;
+        let self_id = &self.self_id;
+        self.users.retain(|user_id, _| {
+            Some(user_id) == self_id.as_ref()
+                || members.values().any(|members| members.contains(user_id))
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn feed(state: &mut ChatState, packets: &[&str]) {
+        for packet in packets {
+            state.handle(&packet.parse::<ServerPacket>().unwrap());
+        }
+    }
+
+    #[test]
+    fn tracks_members_per_channel() {
+        let mut state = ChatState::new();
+        feed(
+            &mut state,
+            &[
+                "1\ty\t1\tkanii\t#f00\t0 0 0 0 0\tlounge\t2000\tMCHAN",
+                "5\t0\t1\tkanii\t#f00\t0 0 0 0 0\t2\tgames",
+                "5\t0\t3\tmisaka\t#00f\t0 0 0 0 0\t3\tgames",
+            ],
+        );
+        let flash = UserContext {
+            user_id: "2".to_string(),
+            username: "flash".to_string(),
+            ..Default::default()
+        };
+        state.handle(&JoinAuthPacket::join(0, &flash, "1").into());
+        assert_eq!(state.joined_channels(), ["lounge", "games"]);
+        assert_eq!(state.members("lounge").count(), 2);
+        assert_eq!(state.members("games").count(), 2);
+
+        let message = "2\t0\t3\thi\t4\t10010\tgames"
+            .parse::<ServerPacket>()
+            .unwrap();
+        let ServerPacket::ChatMessage(message) = message else {
+            panic!("not a chat message");
+        };
+        assert_eq!(state.message_channel(&message), Some("games"));
+
+        feed(&mut state, &["5\t1\t1\t5\tgames"]);
+        assert_eq!(state.joined_channels(), ["lounge"]);
+        assert!(state.user("3").is_none());
+        assert!(state.user("2").is_some());
+    }
+}
@@ -0,0 +1,367 @@
+use crate::{
+    logs::export::{Entry, EntryKind, Transcriber},
+    packets::{
+        bot_message::BOT_USER_ID,
+        client::ClientPacket,
+        server::{ContextClearingPacket, ServerPacket},
+    },
+};
+
+use super::{
+    session::{SendError, Session},
+    state::ChatState,
+};
+
+const HELP: &str = "/w <user> <text>, /join <channel> [password], /leave <channel>, \
+                    /reconnect, /clear, /quit; other commands go to the server";
+
+/// What a line typed into the input box asks for.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Command {
+    /// Text, or a command the server handles, sent as a message.
+    Say(String),
+    Whisper {
+        username: String,
+        text: String,
+    },
+    Join {
+        channel: String,
+        password: Option<String>,
+    },
+    Leave(String),
+    Reconnect,
+    Clear,
+    Help,
+    Quit,
+}
+
+impl Command {
+    pub fn parse(input: &str) -> Option<Self> {
+        if input.trim().is_empty() {
+            return None;
+        }
+        let Some(command) = input.strip_prefix('/') else {
+            return Some(Self::Say(input.to_string()));
+        };
+        let mut words = command.splitn(3, ' ');
+        let name = words.next().unwrap_or_default().to_ascii_lowercase();
+        let first = words.next().filter(|word| !word.is_empty());
+        let rest = words.next().filter(|rest| !rest.trim().is_empty());
+        match (name.as_str(), first, rest) {
+            ("w" | "msg" | "whisper", Some(username), Some(text)) => Some(Self::Whisper {
+                username: username.to_string(),
+                text: text.to_string(),
+            }),
+            ("join", Some(channel), password) => Some(Self::Join {
+                channel: channel.to_string(),
+                password: password.map(str::to_string),
+            }),
+            ("leave" | "part", Some(channel), None) => Some(Self::Leave(channel.to_string())),
+            ("reconnect", None, None) => Some(Self::Reconnect),
+            ("clear", None, None) => Some(Self::Clear),
+            ("help", None, None) => Some(Self::Help),
+            ("quit" | "exit", None, None) => Some(Self::Quit),
+            _ => Some(Self::Say(input.to_string())),
+        }
+    }
+}
+
+/// What the transport has to do after the user pressed enter.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Action {
+    None,
+    Send(Vec<ClientPacket>),
+    Reconnect,
+    Quit,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Line {
+    Entry(Entry),
+    /// Bot messages and the client's own status messages.
+    Notice {
+        text: String,
+        error: bool,
+    },
+}
+
+/// A single-line text input with a cursor.
+#[derive(Debug, Clone, Default)]
+pub struct Input {
+    text: Vec<char>,
+    cursor: usize,
+}
+
+impl Input {
+    pub fn text(&self) -> String {
+        self.text.iter().collect()
+    }
+
+    /// Cursor position, in chars.
+    pub fn cursor(&self) -> usize {
+        self.cursor
+    }
+
+    pub fn insert(&mut self, c: char) {
+        self.text.insert(self.cursor, c);
+        self.cursor += 1;
+    }
+
+    pub fn backspace(&mut self) {
+        if self.cursor > 0 {
+            self.cursor -= 1;
+            self.text.remove(self.cursor);
+        }
+    }
+
+    pub fn delete(&mut self) {
+        if self.cursor < self.text.len() {
+            self.text.remove(self.cursor);
+        }
+    }
+
+    pub fn left(&mut self) {
+        self.cursor = self.cursor.saturating_sub(1);
+    }
+
+    pub fn right(&mut self) {
+        self.cursor = (self.cursor + 1).min(self.text.len());
+    }
+
+    pub fn home(&mut self) {
+        self.cursor = 0;
+    }
+
+    pub fn end(&mut self) {
+        self.cursor = self.text.len();
+    }
+
+    pub fn take(&mut self) -> String {
+        self.cursor = 0;
+        std::mem::take(&mut self.text).into_iter().collect()
+    }
+}
+
+/// Everything an interactive client shows, independent of how it is drawn.
+///
+/// Keeps the scrollback, the user and channel lists through a `ChatState`,
+/// and the input line, and turns submitted input into packets.
+#[derive(Debug)]
+pub struct ChatView {
+    transcriber: Transcriber,
+    lines: Vec<Line>,
+    max_lines: usize,
+    /// Lines scrolled up from the bottom.
+    scroll: usize,
+    pub input: Input,
+}
+
+impl Default for ChatView {
+    fn default() -> Self {
+        ChatView {
+            transcriber: Transcriber::new().include_private(),
+            lines: Vec::new(),
+            max_lines: 1000,
+            scroll: 0,
+            input: Input::default(),
+        }
+    }
+}
+
+impl ChatView {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_max_lines(mut self, max_lines: usize) -> Self {
+        self.max_lines = max_lines;
+        self
+    }
+
+    pub fn state(&self) -> &ChatState {
+        self.transcriber.state()
+    }
+
+    pub fn lines(&self) -> &[Line] {
+        &self.lines
+    }
+
+    pub fn scroll(&self) -> usize {
+        self.scroll
+    }
+
+    pub fn scroll_up(&mut self, lines: usize) {
+        self.scroll = (self.scroll + lines).min(self.lines.len().saturating_sub(1));
+    }
+
+    pub fn scroll_down(&mut self, lines: usize) {
+        self.scroll = self.scroll.saturating_sub(lines);
+    }
+
+    pub fn handle(&mut self, packet: &ServerPacket) {
+        if let ServerPacket::ContextClearing(ContextClearingPacket {
+            message_history: true,
+            ..
+        }) = packet
+        {
+            self.lines.clear();
+            self.scroll = 0;
+        }
+        match self.transcriber.entry(packet) {
+            Some(Entry {
+                kind: EntryKind::Notice { message },
+                ..
+            }) => self.push(Line::Notice {
+                text: message.summary(),
+                error: message.error,
+            }),
+            Some(Entry {
+                user_id,
+                kind: EntryKind::Message { text, .. },
+                ..
+            }) if user_id == BOT_USER_ID => self.notice(&text),
+            Some(entry) => self.push(Line::Entry(entry)),
+            None => {}
+        }
+    }
+
+    pub fn notice(&mut self, text: &str) {
+        self.push(Line::Notice {
+            text: text.to_string(),
+            error: false,
+        });
+    }
+
+    pub fn error(&mut self, text: &str) {
+        self.push(Line::Notice {
+            text: text.to_string(),
+            error: true,
+        });
+    }
+
+    /// Forgets users and channels before reconnecting; the server sends
+    /// them again after authenticating. The scrollback is kept.
+    pub fn reset(&mut self) {
+        self.transcriber = Transcriber::new().include_private();
+    }
+
+    /// Takes the input line and turns it into what `session` should send.
+    pub fn submit(&mut self, session: &Session) -> Action {
+        let Some(command) = Command::parse(&self.input.take()) else {
+            return Action::None;
+        };
+        self.scroll = 0;
+        let packets = match command {
+            Command::Say(text) => session.messages(&text),
+            Command::Whisper { username, text } => session.whisper(&username, &text),
+            Command::Join { channel, password } => session
+                .join_channel(&channel, password.as_deref())
+                .map(|packet| vec![packet]),
+            Command::Leave(channel) => session.leave_channel(&channel).map(|packet| vec![packet]),
+            Command::Reconnect => return Action::Reconnect,
+            Command::Quit => return Action::Quit,
+            Command::Clear => {
+                self.lines.clear();
+                return Action::None;
+            }
+            Command::Help => {
+                self.notice(HELP);
+                return Action::None;
+            }
+        };
+        match packets {
+            Ok(packets) => return Action::Send(packets),
+            Err(SendError::NotConnected) => self.error("not connected"),
+            Err(SendError::Unsupported(_)) => {
+                self.error("the server does not support leaving channels")
+            }
+            Err(SendError::Message(error)) => self.error(&format!("not sent: {:?}", error)),
+        }
+        Action::None
+    }
+
+    fn push(&mut self, line: Line) {
+        self.lines.push(line);
+        if self.scroll > 0 {
+            self.scroll += 1;
+        }
+        if self.lines.len() > self.max_lines {
+            let excess = self.lines.len() - self.max_lines;
+            self.lines.drain(..excess);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Instant;
+
+    use super::*;
+    use crate::packets::types::Sockchatable;
+
+    #[test]
+    fn parses_commands() {
+        assert_eq!(Command::parse("  "), None);
+        assert_eq!(
+            Command::parse("/w flash hi there"),
+            Some(Command::Whisper {
+                username: "flash".to_string(),
+                text: "hi there".to_string()
+            })
+        );
+        assert_eq!(
+            Command::parse("/join games"),
+            Some(Command::Join {
+                channel: "games".to_string(),
+                password: None
+            })
+        );
+        assert_eq!(
+            Command::parse("/kick flash"),
+            Some(Command::Say("/kick flash".to_string()))
+        );
+        assert_eq!(Command::parse("/QUIT"), Some(Command::Quit));
+    }
+
+    #[test]
+    fn follows_the_chat() {
+        let mut session = Session::new("Misuzu", "secret");
+        session.authenticate();
+        let mut view = ChatView::new();
+        for packet in [
+            "1\ty\t1\tkanii\t#f00\t0 0 0 0 0\tlounge\t2000",
+            "7\t0\t1\t2\tflash\t#0f0\t0 0 0 0 0\t1",
+            "7\t2\t2\tlounge\t0\t0\tgames\t1\t0",
+            "2\t100\t2\thi\ts1\t10010",
+            "2\t101\t-1\t0\u{c}join\u{c}misaka\ts2\t10010",
+        ] {
+            let packet = packet.parse::<ServerPacket>().unwrap();
+            session.handle(&packet, Instant::now());
+            view.handle(&packet);
+        }
+        assert_eq!(view.state().users().count(), 2);
+        assert_eq!(view.state().channels().len(), 2);
+        assert_eq!(view.lines().len(), 2);
+        assert_eq!(
+            view.lines()[1],
+            Line::Notice {
+                text: "join: misaka".to_string(),
+                error: false
+            }
+        );
+
+        for c in "/w flash hey".chars() {
+            view.input.insert(c);
+        }
+        match view.submit(&session) {
+            Action::Send(packets) => assert_eq!(packets[0].to_sockstr(), "2\t1\t/msg flash hey"),
+            action => panic!("unexpected {:?}", action),
+        }
+        assert_eq!(view.input.text(), "");
+
+        view.scroll_up(5);
+        assert_eq!(view.scroll(), 1);
+        view.handle(&"8\t0".parse::<ServerPacket>().unwrap());
+        assert!(view.lines().is_empty());
+    }
+}
@@ -0,0 +1,257 @@
+use std::collections::HashMap;
+
+pub use crate::packets::bot_message::WHISPER_ECHO;
+use crate::packets::{
+    bot_message::{BotMessage, BOT_USER_ID},
+    server::{ChatMessagePacket, ServerPacket},
+};
+
+use super::state::ChatState;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Whisper {
+    pub timestamp: i64,
+    pub sequence_id: String,
+    /// Whether we sent this one rather than received it.
+    pub outgoing: bool,
+    pub text: String,
+}
+
+/// Private messages exchanged with a single user.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Thread {
+    /// User id of the peer, `None` while only echoes of our own whispers
+    /// named them.
+    pub peer_id: Option<String>,
+    /// Username of the peer, or their user id until it is known.
+    pub peer: String,
+    pub whispers: Vec<Whisper>,
+    pub unread: usize,
+}
+
+/// Groups private messages into per-peer threads.
+///
+/// Threads are keyed by the peer's user id. Our own echoed whispers only
+/// name the recipient, so their username is resolved through `ChatState` or
+/// the existing threads; echoes to users seen nowhere yet wait in a thread of
+/// their own, which is merged in once a whisper ties the name to an id.
+#[derive(Debug, Default)]
+pub struct Conversations {
+    threads: HashMap<String, Thread>,
+    /// Threads without a known user id, by lowercased username.
+    unresolved: HashMap<String, Thread>,
+}
+
+impl Conversations {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Records `packet` if it is a private message, returning its thread.
+    ///
+    /// `state` resolves usernames and should already have seen the packet's
+    /// predecessors.
+    pub fn handle(&mut self, packet: &ServerPacket, state: &ChatState) -> Option<&Thread> {
+        let ServerPacket::ChatMessage(packet) = packet else {
+            return None;
+        };
+        if !packet.message_flags.private {
+            return None;
+        }
+        let (peer_id, peer, outgoing, text) = self.classify(packet, state)?;
+        let thread = match peer_id {
+            Some(peer_id) => {
+                let pending = peer
+                    .as_ref()
+                    .and_then(|peer| self.unresolved.remove(&peer.to_lowercase()));
+                let thread = self
+                    .threads
+                    .entry(peer_id.clone())
+                    .or_insert_with(|| Thread {
+                        peer: peer_id.clone(),
+                        peer_id: Some(peer_id),
+                        ..Default::default()
+                    });
+                if let Some(peer) = peer {
+                    thread.peer = peer;
+                }
+                if let Some(pending) = pending {
+                    thread.whispers.extend(pending.whispers);
+                    thread.whispers.sort_by_key(|whisper| whisper.timestamp);
+                    thread.unread += pending.unread;
+                }
+                thread
+            }
+            None => {
+                let peer = peer?;
+                self.unresolved
+                    .entry(peer.to_lowercase())
+                    .or_insert_with(|| Thread {
+                        peer,
+                        ..Default::default()
+                    })
+            }
+        };
+        if !outgoing {
+            thread.unread += 1;
+        }
+        thread.whispers.push(Whisper {
+            timestamp: packet.timestamp,
+            sequence_id: packet.sequence_id.clone(),
+            outgoing,
+            text,
+        });
+        Some(thread)
+    }
+
+    /// Looks a thread up by the peer's user id or username.
+    pub fn thread(&self, peer: &str) -> Option<&Thread> {
+        self.threads
+            .get(peer)
+            .or_else(|| self.by_name(peer))
+            .or_else(|| self.unresolved.get(&peer.to_lowercase()))
+    }
+
+    pub fn threads(&self) -> impl Iterator<Item = &Thread> {
+        self.threads.values().chain(self.unresolved.values())
+    }
+
+    /// Total of unread whispers across all threads.
+    pub fn unread(&self) -> usize {
+        self.threads().map(|thread| thread.unread).sum()
+    }
+
+    pub fn mark_read(&mut self, peer: &str) {
+        let thread = match self.thread(peer).and_then(|thread| thread.peer_id.clone()) {
+            Some(peer_id) => self.threads.get_mut(&peer_id),
+            None => self.unresolved.get_mut(&peer.to_lowercase()),
+        };
+        if let Some(thread) = thread {
+            thread.unread = 0;
+        }
+    }
+
+    fn by_name(&self, username: &str) -> Option<&Thread> {
+        self.threads
+            .values()
+            .find(|thread| thread.peer.eq_ignore_ascii_case(username))
+    }
+
+    /// Works out the peer's user id and username, whether the whisper is
+    /// ours and its text.
+    fn classify(
+        &self,
+        packet: &ChatMessagePacket,
+        state: &ChatState,
+    ) -> Option<(Option<String>, Option<String>, bool, String)> {
+        if packet.user_id == BOT_USER_ID {
+            let echo = packet.message.parse::<BotMessage>().ok()?;
+            let [peer, text, ..] = echo.args.as_slice() else {
+                return None;
+            };
+            if echo.id != WHISPER_ECHO {
+                return None;
+            }
+            let (peer_id, peer) = match state.user_by_name(peer) {
+                Some(user) => (Some(user.user_id.clone()), user.username.clone()),
+                None => (
+                    self.by_name(peer).and_then(|thread| thread.peer_id.clone()),
+                    peer.clone(),
+                ),
+            };
+            return Some((peer_id, Some(peer), true, text.clone()));
+        }
+        if Some(packet.user_id.as_str()) == state.self_id() {
+            return None;
+        }
+        let peer = state
+            .user(&packet.user_id)
+            .map(|user| user.username.clone());
+        Some((
+            Some(packet.user_id.clone()),
+            peer,
+            false,
+            packet.message.clone(),
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn threads_whispers_by_peer() {
+        let mut state = ChatState::new();
+        for packet in [
+            "1\ty\t1\tkanii\t#f00\t0 0 0 0 0\tlounge\t2000",
+            "7\t0\t1\t2\tflash\t#0f0\t0 0 0 0 0\t1",
+        ] {
+            state.handle(&packet.parse::<ServerPacket>().unwrap());
+        }
+        let mut conversations = Conversations::new();
+
+        let incoming = "2\t10\t2\they\t1\t00001".parse::<ServerPacket>().unwrap();
+        let echo = "2\t11\t-1\t0\u{c}whisper\u{c}Flash\u{c}hi back\t2\t00001"
+            .parse::<ServerPacket>()
+            .unwrap();
+        let public = "2\t12\t2\tpublic\t3\t00000"
+            .parse::<ServerPacket>()
+            .unwrap();
+        assert_eq!(conversations.handle(&incoming, &state).unwrap().unread, 1);
+        assert!(conversations.handle(&echo, &state).is_some());
+        assert!(conversations.handle(&public, &state).is_none());
+
+        let thread = conversations.thread("flash").unwrap();
+        assert_eq!(thread.whispers.len(), 2);
+        assert!(thread.whispers[1].outgoing);
+        assert_eq!(thread.whispers[1].text, "hi back");
+        assert_eq!(conversations.unread(), 1);
+        conversations.mark_read("FLASH");
+        assert_eq!(conversations.unread(), 0);
+    }
+
+    #[test]
+    fn keeps_strangers_in_one_thread() {
+        let mut state = ChatState::new();
+        state.handle(
+            &"1\ty\t1\tkanii\t#f00\t0 0 0 0 0\tlounge\t2000"
+                .parse::<ServerPacket>()
+                .unwrap(),
+        );
+        let mut conversations = Conversations::new();
+        let handle = |conversations: &mut Conversations, state: &ChatState, packet: &str| {
+            let packet = packet.parse::<ServerPacket>().unwrap();
+            conversations.handle(&packet, state).unwrap().peer.clone()
+        };
+
+        assert_eq!(
+            handle(&mut conversations, &state, "2\t10\t3\they\t1\t00001"),
+            "3"
+        );
+        let echo = "2\t11\t-1\t0\u{c}whisper\u{c}Misaka\u{c}who?\t2\t00001";
+        assert_eq!(handle(&mut conversations, &state, echo), "Misaka");
+        assert_eq!(conversations.threads().count(), 2);
+
+        state.handle(
+            &"5\t0\t3\tmisaka\t#00f\t0 0 0 0 0\t3"
+                .parse::<ServerPacket>()
+                .unwrap(),
+        );
+        let echo = "2\t12\t-1\t0\u{c}whisper\u{c}MISAKA\u{c}oh, hi\t4\t00001";
+        assert_eq!(handle(&mut conversations, &state, echo), "misaka");
+        let thread = conversations.thread("3").unwrap();
+        assert_eq!(
+            thread
+                .whispers
+                .iter()
+                .map(|whisper| whisper.text.as_str())
+                .collect::<Vec<_>>(),
+            ["hey", "who?", "oh, hi"]
+        );
+        assert_eq!(conversations.threads().count(), 1);
+        assert_eq!(conversations.unread(), 1);
+        conversations.mark_read("Misaka");
+        assert_eq!(conversations.unread(), 0);
+    }
+}
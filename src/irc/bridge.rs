@@ -0,0 +1,489 @@
+use std::collections::BTreeMap;
+
+use crate::{
+    client::ChatState,
+    packets::{
+        bot_message::{BotMessage, BOT_USER_ID},
+        client::{ClientPacket, MessagePacket},
+        server::{ChatMessagePacket, ServerPacket},
+        types::DisconnectReason,
+    },
+    text::entities,
+};
+
+use super::message::{nickname, split_text, IrcMessage};
+
+/// Prefix length to leave room for when splitting, `nick!user@host`.
+const PREFIX_ROOM: usize = 100;
+
+/// One of the IRC connections a bridge talks through.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Link {
+    /// The bridge's own connection, which also listens to the channel.
+    Relay,
+    /// The connection standing in for the Sockchat user with this id.
+    Puppet(String),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Output {
+    /// A line to send over a link, opening the link first if needed. Links
+    /// are closed after sending them a `QUIT`.
+    Irc(Link, IrcMessage),
+    Sockchat(ClientPacket),
+}
+
+#[derive(Debug)]
+struct Peer {
+    nick: String,
+    /// Sockchat username, to notice renames.
+    username: String,
+    registered: bool,
+    /// Lines held back until the peer is in the channel.
+    queue: Vec<IrcMessage>,
+}
+
+impl Peer {
+    fn new(nick: String, username: &str) -> Self {
+        Peer {
+            nick,
+            username: username.to_string(),
+            registered: false,
+            queue: Vec::new(),
+        }
+    }
+}
+
+/// Connection-independent logic relaying one Sockchat channel to one IRC
+/// channel.
+///
+/// Every Sockchat user in the channel gets a puppet IRC connection, so their
+/// joins, departures and renames show up as `JOIN`, `PART` and `NICK`, and
+/// their messages as `PRIVMSG`s of their own. Bot messages are `NOTICE`s from
+/// the relay, and IRC messages go back to Sockchat from the bridge's user,
+/// behind a prefix naming the IRC nick.
+#[derive(Debug)]
+pub struct Bridge {
+    channel: String,
+    nick_suffix: String,
+    prefix: String,
+    state: ChatState,
+    relay: Peer,
+    puppets: BTreeMap<String, Peer>,
+}
+
+impl Bridge {
+    pub fn new(channel: &str, nick: &str) -> Self {
+        Bridge {
+            channel: channel.to_string(),
+            nick_suffix: "[s]".to_string(),
+            prefix: "<{nick}> ".to_string(),
+            state: ChatState::new(),
+            relay: Peer::new(nick.to_string(), ""),
+            puppets: BTreeMap::new(),
+        }
+    }
+
+    /// Appended to puppet nicknames, `[s]` by default.
+    pub fn with_nick_suffix(mut self, suffix: &str) -> Self {
+        self.nick_suffix = suffix.to_string();
+        self
+    }
+
+    /// Put in front of messages relayed to Sockchat, with `{nick}` replaced
+    /// by the IRC nick; `<{nick}> ` by default.
+    pub fn with_prefix(mut self, prefix: &str) -> Self {
+        self.prefix = prefix.to_string();
+        self
+    }
+
+    pub fn channel(&self) -> &str {
+        &self.channel
+    }
+
+    pub fn state(&self) -> &ChatState {
+        &self.state
+    }
+
+    /// Current nick of a link, if it is open.
+    pub fn nick(&self, link: &Link) -> Option<&str> {
+        self.peer(link).map(|peer| peer.nick.as_str())
+    }
+
+    /// Registers the relay; the first thing to send.
+    pub fn start(&self) -> Vec<Output> {
+        register(Link::Relay, &self.relay.nick)
+    }
+
+    pub fn sockchat(&mut self, packet: &ServerPacket) -> Vec<Output> {
+        let reason = match packet {
+            ServerPacket::UserDisconnect(packet) => Some(packet.reason.clone()),
+            _ => None,
+        };
+        self.state.handle(packet);
+        let mut out = Vec::new();
+        self.sync(&mut out, reason);
+        if let ServerPacket::ChatMessage(message) = packet {
+            self.relay_message(&mut out, message);
+        }
+        out
+    }
+
+    /// Forgets a link that closed or failed without being told to `QUIT`.
+    ///
+    /// A puppet is registered again on the next Sockchat packet if its user
+    /// is still around; the relay has to register again through `start`.
+    pub fn closed(&mut self, link: &Link) {
+        match link {
+            Link::Relay => {
+                self.relay.registered = false;
+                self.relay.queue.clear();
+            }
+            Link::Puppet(user_id) => {
+                self.puppets.remove(user_id);
+            }
+        }
+    }
+
+    pub fn irc(&mut self, link: &Link, message: &IrcMessage) -> Vec<Output> {
+        let mut out = Vec::new();
+        match message.command.as_str() {
+            "PING" => out.push(Output::Irc(
+                link.clone(),
+                IrcMessage {
+                    prefix: None,
+                    command: "PONG".to_string(),
+                    params: message.params.clone(),
+                },
+            )),
+            "001" => {
+                let channel = self.channel.clone();
+                if let Some(peer) = self.peer_mut(link) {
+                    peer.registered = true;
+                    out.push(Output::Irc(
+                        link.clone(),
+                        IrcMessage::new("JOIN", &[&channel]),
+                    ));
+                    for queued in peer.queue.drain(..) {
+                        out.push(Output::Irc(link.clone(), queued));
+                    }
+                }
+            }
+            "433" => {
+                if let Some(peer) = self.peer_mut(link) {
+                    peer.nick.push('_');
+                    let nick = IrcMessage::new("NICK", &[&peer.nick]);
+                    out.push(Output::Irc(link.clone(), nick));
+                }
+            }
+            "PRIVMSG" if *link == Link::Relay => {
+                if let Some(packet) = self.relayed_packet(message) {
+                    out.push(Output::Sockchat(packet));
+                }
+            }
+            _ => {}
+        }
+        out
+    }
+
+    fn peer(&self, link: &Link) -> Option<&Peer> {
+        match link {
+            Link::Relay => Some(&self.relay),
+            Link::Puppet(user_id) => self.puppets.get(user_id),
+        }
+    }
+
+    fn peer_mut(&mut self, link: &Link) -> Option<&mut Peer> {
+        match link {
+            Link::Relay => Some(&mut self.relay),
+            Link::Puppet(user_id) => self.puppets.get_mut(user_id),
+        }
+    }
+
+    fn relayed_packet(&self, message: &IrcMessage) -> Option<ClientPacket> {
+        let nick = message.nick()?;
+        let own = nick.eq_ignore_ascii_case(&self.relay.nick)
+            || self
+                .puppets
+                .values()
+                .any(|puppet| puppet.nick.eq_ignore_ascii_case(nick));
+        if own || !message.param(0)?.eq_ignore_ascii_case(&self.channel) {
+            return None;
+        }
+        let text = message.param(1)?;
+        let prefix = self.prefix.replace("{nick}", nick);
+        let message = match text
+            .strip_prefix("\u{1}ACTION ")
+            .map(|action| action.trim_end_matches('\u{1}'))
+        {
+            Some(action) => format!("* {} {}", nick, action),
+            None if text.starts_with('\u{1}') => return None,
+            None => format!("{}{}", prefix, text),
+        };
+        Some(ClientPacket::Message(MessagePacket {
+            user_id: self.state.self_id()?.to_string(),
+            message,
+        }))
+    }
+
+    /// Opens, renames and closes puppets to match the channel's members.
+    fn sync(&mut self, out: &mut Vec<Output>, reason: Option<DisconnectReason>) {
+        let Some(channel_name) = self.state.channel_name() else {
+            return;
+        };
+        let self_id = self.state.self_id();
+        let members = self
+            .state
+            .members(channel_name)
+            .filter(|user| Some(user.user_id.as_str()) != self_id)
+            .map(|user| (user.user_id.clone(), user.username.clone()))
+            .collect::<BTreeMap<_, _>>();
+
+        let gone = self
+            .puppets
+            .keys()
+            .filter(|user_id| !members.contains_key(*user_id))
+            .cloned()
+            .collect::<Vec<_>>();
+        for user_id in gone {
+            let puppet = self.puppets.remove(&user_id).unwrap();
+            let link = Link::Puppet(user_id);
+            let reason = match &reason {
+                Some(DisconnectReason::Timeout) => "timed out",
+                Some(DisconnectReason::Kick) => "kicked",
+                Some(DisconnectReason::Flood) => "flood",
+                _ => "left",
+            };
+            if puppet.registered {
+                let part = IrcMessage::new("PART", &[&self.channel, reason]);
+                out.push(Output::Irc(link.clone(), part));
+            }
+            out.push(Output::Irc(link, IrcMessage::new("QUIT", &[reason])));
+        }
+
+        for (user_id, username) in members {
+            let link = Link::Puppet(user_id.clone());
+            match self.puppets.get_mut(&user_id) {
+                None => {
+                    let nick = nickname(&username, &self.nick_suffix);
+                    out.extend(register(link, &nick));
+                    self.puppets.insert(user_id, Peer::new(nick, &username));
+                }
+                Some(puppet) if puppet.username != username => {
+                    puppet.username = username;
+                    puppet.nick = nickname(&puppet.username, &self.nick_suffix);
+                    out.push(Output::Irc(link, IrcMessage::new("NICK", &[&puppet.nick])));
+                }
+                Some(_) => {}
+            }
+        }
+    }
+
+    fn relay_message(&mut self, out: &mut Vec<Output>, packet: &ChatMessagePacket) {
+        let current = self.state.channel_name();
+        if packet.message_flags.private
+            || self.state.message_channel(packet) != current
+            || Some(packet.user_id.as_str()) == self.state.self_id()
+        {
+            return;
+        }
+
+        let (link, command, text) = if packet.user_id == BOT_USER_ID {
+            let text = match packet.message.parse::<BotMessage>() {
+                Ok(bot) if bot.args.is_empty() => bot.id,
+                Ok(bot) => format!("{}: {}", bot.id, bot.args.join(", ")),
+                Err(_) => packet.message.clone(),
+            };
+            (Link::Relay, "NOTICE", text)
+        } else if self.puppets.contains_key(&packet.user_id) {
+            let link = Link::Puppet(packet.user_id.clone());
+            (link, "PRIVMSG", entities::decode(&packet.message))
+        } else {
+            let username = self
+                .state
+                .user(&packet.user_id)
+                .map_or(packet.user_id.as_str(), |user| user.username.as_str());
+            let text = format!("<{}> {}", username, entities::decode(&packet.message));
+            (Link::Relay, "PRIVMSG", text)
+        };
+        let action = !packet.message_flags.colon && command == "PRIVMSG";
+
+        let channel = self.channel.clone();
+        let Some(peer) = self.peer_mut(&link) else {
+            return;
+        };
+        let room = PREFIX_ROOM + if action { "\u{1}ACTION \u{1}".len() } else { 0 };
+        for piece in split_text(&text, &channel, room) {
+            let piece = match action {
+                true => format!("\u{1}ACTION {}\u{1}", piece),
+                false => piece,
+            };
+            let message = IrcMessage::new(command, &[&channel, &piece]);
+            match peer.registered {
+                true => out.push(Output::Irc(link.clone(), message)),
+                false => peer.queue.push(message),
+            }
+        }
+    }
+}
+
+fn register(link: Link, nick: &str) -> Vec<Output> {
+    vec![
+        Output::Irc(link.clone(), IrcMessage::new("NICK", &[nick])),
+        Output::Irc(
+            link,
+            IrcMessage::new("USER", &[nick, "0", "*", "kanii bridge"]),
+        ),
+    ]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn feed(bridge: &mut Bridge, lines: &[&str]) -> Vec<Output> {
+        lines
+            .iter()
+            .flat_map(|line| bridge.sockchat(&line.parse::<ServerPacket>().unwrap()))
+            .collect()
+    }
+
+    fn irc(link: Link, line: &str) -> Output {
+        Output::Irc(link, line.parse::<IrcMessage>().unwrap())
+    }
+
+    #[test]
+    fn puppets_sockchat_users() {
+        let mut bridge = Bridge::new("#lounge", "kanii");
+        assert_eq!(bridge.start()[0], irc(Link::Relay, "NICK kanii"));
+        assert_eq!(
+            bridge.irc(&Link::Relay, &":irc 001 kanii :hi".parse().unwrap()),
+            vec![irc(Link::Relay, "JOIN #lounge")]
+        );
+
+        let flash = Link::Puppet("2".to_string());
+        let out = feed(
+            &mut bridge,
+            &[
+                "1\ty\t1\tkanii\t#f00\t0 0 0 0 0\tlounge\t2000",
+                "7\t0\t1\t2\tflash wave\t#0f0\t0 0 0 0 0\t1",
+                "2\t100\t2\thi &lt;3\t10\t10010",
+            ],
+        );
+        assert_eq!(
+            out,
+            vec![
+                irc(flash.clone(), "NICK flash_wave[s]"),
+                irc(flash.clone(), "USER flash_wave[s] 0 * :kanii bridge"),
+            ]
+        );
+        assert_eq!(
+            bridge.irc(&flash, &":irc 001 flash_wave[s] :hi".parse().unwrap()),
+            vec![
+                irc(flash.clone(), "JOIN #lounge"),
+                irc(flash.clone(), "PRIVMSG #lounge :hi <3"),
+            ]
+        );
+
+        let out = feed(
+            &mut bridge,
+            &[
+                "2\t101\t2\twaves\t11\t11000",
+                "2\t102\t-1\t0\u{c}say\u{c}welcome\t12\t10010",
+                "10\t2\tflash\t#0f0\t0 0 0 0 0",
+                "3\t2\tflash\ttimeout\t103\t13",
+            ],
+        );
+        assert_eq!(
+            out,
+            vec![
+                irc(flash.clone(), "PRIVMSG #lounge :\u{1}ACTION waves\u{1}"),
+                irc(Link::Relay, "NOTICE #lounge :say: welcome"),
+                irc(flash.clone(), "NICK flash[s]"),
+                irc(flash.clone(), "PART #lounge :timed out"),
+                irc(flash.clone(), "QUIT :timed out"),
+            ]
+        );
+        assert_eq!(bridge.nick(&flash), None);
+    }
+
+    #[test]
+    fn keeps_line_breaks_out_of_lines() {
+        let mut bridge = Bridge::new("#lounge", "kanii");
+        bridge.start();
+        bridge.irc(&Link::Relay, &":irc 001 kanii :hi".parse().unwrap());
+        let out = feed(
+            &mut bridge,
+            &[
+                "1\ty\t1\tkanii\t#f00\t0 0 0 0 0\tlounge\t2000",
+                "2\t100\t5\thi&#13;QUIT :pwned\t10\t10010",
+            ],
+        );
+        assert_eq!(
+            out,
+            vec![
+                irc(Link::Relay, "PRIVMSG #lounge :<5> hi"),
+                irc(Link::Relay, "PRIVMSG #lounge :QUIT :pwned"),
+            ]
+        );
+    }
+
+    #[test]
+    fn registers_closed_puppets_again() {
+        let mut bridge = Bridge::new("#lounge", "kanii");
+        let flash = Link::Puppet("2".to_string());
+        feed(
+            &mut bridge,
+            &[
+                "1\ty\t1\tkanii\t#f00\t0 0 0 0 0\tlounge\t2000",
+                "7\t0\t1\t2\tflash\t#0f0\t0 0 0 0 0\t1",
+            ],
+        );
+        bridge.irc(&flash, &":irc 001 flash[s] :hi".parse().unwrap());
+
+        bridge.closed(&flash);
+        assert_eq!(bridge.nick(&flash), None);
+        assert_eq!(
+            feed(&mut bridge, &["2\t100\t2\tstill here\t10\t10010"]),
+            vec![
+                irc(flash.clone(), "NICK flash[s]"),
+                irc(flash.clone(), "USER flash[s] 0 * :kanii bridge"),
+            ]
+        );
+        assert_eq!(
+            bridge.irc(&flash, &":irc 001 flash[s] :hi".parse().unwrap()),
+            vec![
+                irc(flash.clone(), "JOIN #lounge"),
+                irc(flash.clone(), "PRIVMSG #lounge :still here"),
+            ]
+        );
+    }
+
+    #[test]
+    fn relays_irc_messages_back() {
+        let mut bridge = Bridge::new("#lounge", "kanii").with_prefix("[irc] {nick}: ");
+        feed(
+            &mut bridge,
+            &[
+                "1\ty\t1\tkanii\t#f00\t0 0 0 0 0\tlounge\t2000",
+                "7\t0\t1\t2\tflash\t#0f0\t0 0 0 0 0\t1",
+            ],
+        );
+        let say = |bridge: &mut Bridge, line: &str| {
+            bridge.irc(&Link::Relay, &line.parse::<IrcMessage>().unwrap())
+        };
+        assert_eq!(
+            say(&mut bridge, ":misaka!m@host PRIVMSG #Lounge :hello"),
+            vec![Output::Sockchat(ClientPacket::Message(MessagePacket {
+                user_id: "1".to_string(),
+                message: "[irc] misaka: hello".to_string(),
+            }))]
+        );
+        assert!(say(&mut bridge, ":flash[s]!f@host PRIVMSG #lounge :echo").is_empty());
+        assert!(say(&mut bridge, ":misaka!m@host PRIVMSG kanii :private").is_empty());
+        assert_eq!(
+            say(&mut bridge, "PING :irc.local"),
+            vec![irc(Link::Relay, "PONG irc.local")]
+        );
+    }
+}
@@ -0,0 +1,767 @@
+use crate::{
+    client::{ChatState, SendError, Session},
+    packets::{
+        bot_message::{BotMessage, BOT_USER_ID},
+        client::ClientPacket,
+        server::{
+            ChannelSwitchingPacket, ChatMessagePacket, ContextInformationPacket,
+            ForcedDisconnectPacket, JoinAuthPacket, ServerPacket,
+        },
+        types::{DisconnectReason, UserContext},
+    },
+    text::entities,
+};
+
+use super::message::{nickname, split_text, IrcMessage};
+
+/// Host part of the prefixes given to Sockchat users.
+const USER_HOST: &str = "sockchat";
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Output {
+    Irc(IrcMessage),
+    Sockchat(ClientPacket),
+    /// Opens the Sockchat connection, signing in with `authkey`.
+    Connect {
+        authkey: String,
+    },
+    /// Closes both connections.
+    Close,
+}
+
+/// Connection-independent logic presenting a Sockchat session as an IRC
+/// server to one IRC client.
+///
+/// The client's `PASS` is the authkey; registering with `NICK` and `USER`
+/// asks the transport to connect, and the nick is replaced with the
+/// Sockchat username once authenticated. Channels are the Sockchat ones
+/// behind a `#`, and moderators are channel operators.
+///
+/// IRC lines are handled with the Sockchat `Session` at hand, if connected,
+/// which is what the packets they turn into are built from.
+#[derive(Debug)]
+pub struct Frontend {
+    server_name: String,
+    nick: Option<String>,
+    user: bool,
+    authkey: Option<String>,
+    connecting: bool,
+    state: ChatState,
+    /// Channel whose member list is still owed to the client.
+    names_pending: Option<String>,
+}
+
+impl Frontend {
+    pub fn new(server_name: &str) -> Self {
+        Frontend {
+            server_name: server_name.to_string(),
+            nick: None,
+            user: false,
+            authkey: None,
+            connecting: false,
+            state: ChatState::new(),
+            names_pending: None,
+        }
+    }
+
+    pub fn state(&self) -> &ChatState {
+        &self.state
+    }
+
+    /// Nick the client goes by.
+    pub fn nick(&self) -> Option<&str> {
+        self.nick.as_deref()
+    }
+
+    pub fn irc(&mut self, message: &IrcMessage, session: Option<&Session>) -> Vec<Output> {
+        let mut out = Vec::new();
+        let param = |index: usize| message.param(index).unwrap_or_default();
+        match message.command.as_str() {
+            "CAP" if param(0).eq_ignore_ascii_case("LS") => {
+                out.push(self.reply("CAP", &["*", "LS", ""]))
+            }
+            "CAP" => {}
+            "PING" => out.push(Output::Irc(
+                IrcMessage::new("PONG", &[&self.server_name, param(0)])
+                    .with_prefix(&self.server_name),
+            )),
+            "QUIT" => out.push(Output::Close),
+            "PASS" if !self.connecting => self.authkey = Some(param(0).to_string()),
+            "NICK" if !self.connecting => {
+                self.nick = Some(param(0).to_string());
+                self.register(&mut out);
+            }
+            "USER" if !self.connecting => {
+                self.user = true;
+                self.register(&mut out);
+            }
+            _ if !self.is_connected(session) => {
+                out.push(self.numeric("451", &["You have not registered"]))
+            }
+            "NICK" => {
+                if let Some(session) = session {
+                    self.forward(&mut out, session.message(&format!("/nick {}", param(0))));
+                }
+            }
+            "PRIVMSG" => self.privmsg(&mut out, param(0), param(1), session),
+            "JOIN" => {
+                let keys = param(1).split(',').collect::<Vec<_>>();
+                for (index, channel) in param(0).split(',').enumerate() {
+                    let key = keys.get(index).copied().filter(|key| !key.is_empty());
+                    let name = self.sockchat_channel(channel);
+                    if let Some(session) = session {
+                        self.forward(&mut out, session.join_channel(&name, key));
+                    }
+                }
+            }
+            "PART" => {
+                for channel in param(0).split(',') {
+                    let name = self.sockchat_channel(channel);
+                    if let Some(session) = session {
+                        self.forward(&mut out, session.leave_channel(&name));
+                    }
+                }
+            }
+            "NAMES" => {
+                for channel in param(0).split(',').filter(|channel| !channel.is_empty()) {
+                    let name = self.sockchat_channel(channel);
+                    self.names(&mut out, &name);
+                }
+            }
+            "WHO" => {
+                let name = self.sockchat_channel(param(0));
+                let channel = self.irc_channel(&name);
+                for user in self.members(&name) {
+                    let nick = nickname(&user.username, "");
+                    let flags = match user.user_permissions.can_moderate {
+                        true => "H@",
+                        false => "H",
+                    };
+                    let real_name = format!("0 {}", user.username);
+                    out.push(self.numeric(
+                        "352",
+                        &[
+                            &channel,
+                            &user.user_id,
+                            USER_HOST,
+                            &self.server_name,
+                            &nick,
+                            flags,
+                            &real_name,
+                        ],
+                    ));
+                }
+                out.push(self.numeric("315", &[param(0), "End of WHO list"]));
+            }
+            "MODE" if param(0).starts_with('#') => match param(1) {
+                "" => out.push(self.numeric("324", &[param(0), "+nt"])),
+                "b" => out.push(self.numeric("368", &[param(0), "End of channel ban list"])),
+                _ => out.push(self.numeric("482", &[param(0), "Modes are set on Sockchat"])),
+            },
+            "MODE" => out.push(self.numeric("221", &["+"])),
+            "TOPIC" => out.push(self.numeric("331", &[param(0), "No topic is set"])),
+            "NOTICE" | "PONG" | "USERHOST" | "ISON" => {}
+            command => out.push(self.numeric("421", &[command, "Unknown command"])),
+        }
+        out
+    }
+
+    pub fn sockchat(&mut self, packet: &ServerPacket) -> Vec<Output> {
+        let mut out = Vec::new();
+        let before = self.state.channel_name().map(str::to_string);
+        // Users as they were before leaving or being updated.
+        let previous = match packet {
+            ServerPacket::ChannelSwitching(ChannelSwitchingPacket::Departure {
+                user_id, ..
+            }) => self.state.user(user_id).cloned(),
+            ServerPacket::UserDisconnect(packet) => self.state.user(&packet.user_id).cloned(),
+            ServerPacket::UserUpdate(packet) => self.state.user(&packet.user_id).cloned(),
+            _ => None,
+        };
+
+        match packet {
+            ServerPacket::JoinAuth(JoinAuthPacket::BadAuth { reason, .. }) => {
+                out.push(self.numeric("464", &[&format!("Sockchat said {:?}", reason)]));
+                out.push(Output::Irc(IrcMessage::new(
+                    "ERROR",
+                    &["Authentication failed"],
+                )));
+                out.push(Output::Close);
+                return out;
+            }
+            ServerPacket::ForcedDisconnect(ForcedDisconnectPacket { ban, .. }) => {
+                let reason = match ban {
+                    true => "Banned",
+                    false => "Kicked",
+                };
+                out.push(Output::Irc(IrcMessage::new("ERROR", &[reason])));
+                out.push(Output::Close);
+                return out;
+            }
+            _ => {}
+        }
+
+        self.state.handle(packet);
+        match packet {
+            ServerPacket::JoinAuth(JoinAuthPacket::GoodAuth {
+                username,
+                channel_name,
+                ..
+            }) => self.welcome(&mut out, username, channel_name),
+
+            ServerPacket::JoinAuth(JoinAuthPacket::Join { user_id, .. }) => {
+                self.joined(&mut out, user_id, before.as_deref())
+            }
+
+            ServerPacket::ChannelSwitching(ChannelSwitchingPacket::Join {
+                user_id,
+                channel_name,
+                ..
+            }) => self.joined(
+                &mut out,
+                user_id,
+                channel_name.as_deref().or(before.as_deref()),
+            ),
+
+            ServerPacket::ChannelSwitching(ChannelSwitchingPacket::Departure {
+                channel_name,
+                ..
+            }) => {
+                let channel = channel_name.clone().or(before.clone());
+                if let (Some(user), Some(channel)) = (previous, channel) {
+                    let part = IrcMessage::new("PART", &[&self.irc_channel(&channel)]);
+                    out.push(Output::Irc(part.with_prefix(&prefix(&user))));
+                }
+            }
+
+            ServerPacket::ChannelSwitching(ChannelSwitchingPacket::ForcedSwitch {
+                channel_name,
+            }) => {
+                let Some(me) = self.me() else {
+                    return out;
+                };
+                if let Some(before) = before.filter(|before| {
+                    !self.state.joined_channels().contains(before) && before != channel_name
+                }) {
+                    let part = IrcMessage::new("PART", &[&self.irc_channel(&before)]);
+                    out.push(Output::Irc(part.with_prefix(&prefix(&me))));
+                }
+                let join = IrcMessage::new("JOIN", &[&self.irc_channel(channel_name)]);
+                out.push(Output::Irc(join.with_prefix(&prefix(&me))));
+                self.names_pending = Some(channel_name.clone());
+            }
+
+            ServerPacket::UserDisconnect(packet) => {
+                if let Some(user) = previous {
+                    let reason = match packet.reason {
+                        DisconnectReason::Leave => "Leaving",
+                        DisconnectReason::Timeout => "Timed out",
+                        DisconnectReason::Kick => "Kicked",
+                        DisconnectReason::Flood => "Flood",
+                    };
+                    let quit = IrcMessage::new("QUIT", &[reason]);
+                    out.push(Output::Irc(quit.with_prefix(&prefix(&user))));
+                }
+            }
+
+            ServerPacket::UserUpdate(packet) => {
+                let (Some(old), Some(new)) = (previous, self.state.user(&packet.user_id)) else {
+                    return out;
+                };
+                let new = new.clone();
+                let (old_nick, new_nick) =
+                    (nickname(&old.username, ""), nickname(&new.username, ""));
+                if old_nick != new_nick {
+                    let rename = IrcMessage::new("NICK", &[&new_nick]);
+                    out.push(Output::Irc(rename.with_prefix(&prefix(&old))));
+                    if Some(new.user_id.as_str()) == self.state.self_id() {
+                        self.nick = Some(new_nick.clone());
+                    }
+                }
+                let was = old.user_permissions.can_moderate;
+                if was != new.user_permissions.can_moderate {
+                    let mode = if was { "-o" } else { "+o" };
+                    for channel in self.channels_of(&new.user_id) {
+                        let channel = self.irc_channel(&channel);
+                        let message = IrcMessage::new("MODE", &[&channel, mode, &new_nick]);
+                        out.push(Output::Irc(message.with_prefix(&self.server_name)));
+                    }
+                }
+            }
+
+            ServerPacket::ChatMessage(message) => self.message(&mut out, message),
+
+            ServerPacket::ContextInformation(ContextInformationPacket::ExistingMessage {
+                timestamp,
+                user_id,
+                message,
+                sequence_id,
+                message_flags,
+                ..
+            }) => self.message(
+                &mut out,
+                &ChatMessagePacket::new(
+                    *timestamp,
+                    user_id,
+                    message,
+                    sequence_id,
+                    message_flags.clone(),
+                ),
+            ),
+
+            ServerPacket::ContextInformation(ContextInformationPacket::ExistingUsers {
+                ..
+            }) => {
+                if let Some(channel) = self.names_pending.take() {
+                    self.names(&mut out, &channel);
+                }
+            }
+
+            _ => {}
+        }
+        out
+    }
+
+    fn is_connected(&self, session: Option<&Session>) -> bool {
+        session.is_some_and(Session::is_connected)
+    }
+
+    fn register(&mut self, out: &mut Vec<Output>) {
+        if self.nick.is_none() || !self.user {
+            return;
+        }
+        match self.authkey.clone() {
+            Some(authkey) => {
+                self.connecting = true;
+                out.push(Output::Connect { authkey });
+            }
+            None => {
+                out.push(self.numeric("464", &["Send your Sockchat authkey with PASS"]));
+                out.push(Output::Irc(IrcMessage::new(
+                    "ERROR",
+                    &["Password required"],
+                )));
+                out.push(Output::Close);
+            }
+        }
+    }
+
+    fn welcome(&mut self, out: &mut Vec<Output>, username: &str, channel_name: &str) {
+        let nick = nickname(username, "");
+        if let Some(old) = self.nick.replace(nick.clone()).filter(|old| *old != nick) {
+            let rename = IrcMessage::new("NICK", &[&nick]).with_prefix(&old);
+            out.push(Output::Irc(rename));
+        }
+        let welcome = format!("Welcome to Sockchat, {}", username);
+        let host = format!("Your host is {}, running kanii", self.server_name);
+        out.push(self.numeric("001", &[&welcome]));
+        out.push(self.numeric("002", &[&host]));
+        out.push(self.numeric("003", &["This server relays a Sockchat session"]));
+        out.push(self.numeric("004", &[&self.server_name, "kanii", "i", "ov"]));
+        out.push(self.numeric(
+            "005",
+            &["CHANTYPES=#", "PREFIX=(o)@", "are supported by this server"],
+        ));
+        out.push(self.numeric("422", &["MOTD File is missing"]));
+        if let Some(me) = self.me() {
+            let join = IrcMessage::new("JOIN", &[&self.irc_channel(channel_name)]);
+            out.push(Output::Irc(join.with_prefix(&prefix(&me))));
+        }
+        self.names_pending = Some(channel_name.to_string());
+    }
+
+    /// Announces `user_id` entering `channel`; the client itself also gets
+    /// the member list.
+    fn joined(&self, out: &mut Vec<Output>, user_id: &str, channel: Option<&str>) {
+        let (Some(user), Some(channel_name)) = (self.state.user(user_id), channel) else {
+            return;
+        };
+        let channel = self.irc_channel(channel_name);
+        let join = IrcMessage::new("JOIN", &[&channel]).with_prefix(&prefix(user));
+        out.push(Output::Irc(join));
+        if Some(user_id) == self.state.self_id() {
+            self.names(out, channel_name);
+        } else if user.user_permissions.can_moderate {
+            let nick = nickname(&user.username, "");
+            let mode = IrcMessage::new("MODE", &[&channel, "+o", &nick]);
+            out.push(Output::Irc(mode.with_prefix(&self.server_name)));
+        }
+    }
+
+    fn names(&self, out: &mut Vec<Output>, channel_name: &str) {
+        let channel = self.irc_channel(channel_name);
+        let mut names = self
+            .members(channel_name)
+            .map(|user| match user.user_permissions.can_moderate {
+                true => format!("@{}", nickname(&user.username, "")),
+                false => nickname(&user.username, ""),
+            })
+            .collect::<Vec<_>>();
+        names.sort();
+        for chunk in names.chunks(20) {
+            out.push(self.numeric("353", &["=", &channel, &chunk.join(" ")]));
+        }
+        out.push(self.numeric("366", &[&channel, "End of /NAMES list"]));
+    }
+
+    fn message(&self, out: &mut Vec<Output>, packet: &ChatMessagePacket) {
+        let own = Some(packet.user_id.as_str()) == self.state.self_id();
+        if own {
+            return;
+        }
+        let Some(me) = self.nick.clone() else {
+            return;
+        };
+
+        let (source, command, text) = if packet.user_id == BOT_USER_ID {
+            let text = match packet.message.parse::<BotMessage>() {
+                Ok(bot) if bot.args.is_empty() => bot.id,
+                Ok(bot) => format!("{}: {}", bot.id, bot.args.join(", ")),
+                Err(_) => packet.message.clone(),
+            };
+            (self.server_name.clone(), "NOTICE", text)
+        } else {
+            let source = match self.state.user(&packet.user_id) {
+                Some(user) => prefix(user),
+                None => format!("{}!{}@{}", packet.user_id, packet.user_id, USER_HOST),
+            };
+            (source, "PRIVMSG", entities::decode(&packet.message))
+        };
+        let target = match packet.message_flags.private {
+            true => me,
+            false => match self.state.message_channel(packet) {
+                Some(channel) => self.irc_channel(channel),
+                None => return,
+            },
+        };
+        let action = !packet.message_flags.colon && command == "PRIVMSG";
+        for piece in split_text(&text, &target, source.len() + 12) {
+            let piece = match action {
+                true => format!("\u{1}ACTION {}\u{1}", piece),
+                false => piece,
+            };
+            let message = IrcMessage::new(command, &[&target, &piece]).with_prefix(&source);
+            out.push(Output::Irc(message));
+        }
+    }
+
+    fn privmsg(&self, out: &mut Vec<Output>, target: &str, text: &str, session: Option<&Session>) {
+        let Some(session) = session else {
+            return;
+        };
+        let text = match text.strip_prefix("\u{1}ACTION ") {
+            Some(action) => format!("/me {}", action.trim_end_matches('\u{1}')),
+            None if text.starts_with('\u{1}') => return,
+            None => text.to_string(),
+        };
+        if target.starts_with('#') {
+            // Sockchat posts to the current channel and runs text starting
+            // with a slash as a command, so neither is left to chance.
+            let name = self.sockchat_channel(target);
+            if !self.state.joined_channels().contains(&name) {
+                out.push(self.numeric("442", &[target, "You're not on that channel"]));
+                return;
+            }
+            if self.state.channel_name() != Some(name.as_str()) {
+                let reason = match self.state.channel_name() {
+                    Some(current) => {
+                        format!("Sockchat only posts to {}", self.irc_channel(current))
+                    }
+                    None => "Sockchat has no current channel".to_string(),
+                };
+                out.push(self.numeric("404", &[target, &reason]));
+                return;
+            }
+            if !text.starts_with("/me ") && text.starts_with('/') {
+                let reason = "Sockchat would run this as a command";
+                out.push(self.numeric("404", &[target, reason]));
+                return;
+            }
+            match session.messages(&text) {
+                Ok(packets) => out.extend(packets.into_iter().map(Output::Sockchat)),
+                Err(error) => out.push(self.numeric("404", &[target, &format!("{:?}", error)])),
+            }
+            return;
+        }
+        let user = self
+            .state
+            .users()
+            .find(|user| nickname(&user.username, "").eq_ignore_ascii_case(target));
+        let Some(user) = user else {
+            out.push(self.numeric("401", &[target, "No such nick"]));
+            return;
+        };
+        match session.whisper(&user.username, &text) {
+            Ok(packets) => out.extend(packets.into_iter().map(Output::Sockchat)),
+            Err(error) => out.push(self.numeric("404", &[target, &format!("{:?}", error)])),
+        }
+    }
+
+    fn me(&self) -> Option<UserContext> {
+        self.state.user(self.state.self_id()?).cloned()
+    }
+
+    fn members(&self, channel_name: &str) -> impl Iterator<Item = &UserContext> {
+        self.state.members(channel_name)
+    }
+
+    fn channels_of(&self, user_id: &str) -> Vec<String> {
+        self.state
+            .joined_channels()
+            .iter()
+            .filter(|channel| self.members(channel).any(|user| user.user_id == user_id))
+            .cloned()
+            .collect()
+    }
+
+    /// IRC name of a Sockchat channel.
+    fn irc_channel(&self, channel_name: &str) -> String {
+        let forbidden = [' ', ',', '\u{7}', '\r', '\n', '\0'];
+        format!("#{}", channel_name.replace(forbidden, "_"))
+    }
+
+    /// Sockchat channel an IRC channel name stands for.
+    fn sockchat_channel(&self, channel: &str) -> String {
+        self.state
+            .channels()
+            .iter()
+            .map(|context| context.channel_name.as_str())
+            .chain(self.state.joined_channels().iter().map(String::as_str))
+            .find(|name| self.irc_channel(name).eq_ignore_ascii_case(channel))
+            .map_or_else(
+                || channel.trim_start_matches('#').to_string(),
+                str::to_string,
+            )
+    }
+
+    fn reply(&self, command: &str, params: &[&str]) -> Output {
+        Output::Irc(IrcMessage::new(command, params).with_prefix(&self.server_name))
+    }
+
+    /// Passes on a packet the session built, or tells the client why there is none.
+    fn forward(&self, out: &mut Vec<Output>, packet: Result<ClientPacket, SendError>) {
+        out.push(match packet {
+            Ok(packet) => Output::Sockchat(packet),
+            Err(SendError::NotConnected) => self.notice("Not connected to the Sockchat server"),
+            Err(SendError::Unsupported(_)) => {
+                self.notice("The Sockchat server does not support leaving channels")
+            }
+            Err(SendError::Message(error)) => self.notice(&format!("Not sent: {:?}", error)),
+        });
+    }
+
+    fn notice(&self, text: &str) -> Output {
+        let nick = self.nick.clone().unwrap_or_else(|| "*".to_string());
+        self.reply("NOTICE", &[&nick, text])
+    }
+
+    fn numeric(&self, code: &str, params: &[&str]) -> Output {
+        let mut message = IrcMessage::new(code, params).with_prefix(&self.server_name);
+        message
+            .params
+            .insert(0, self.nick.clone().unwrap_or_else(|| "*".to_string()));
+        Output::Irc(message)
+    }
+}
+
+fn prefix(user: &UserContext) -> String {
+    format!(
+        "{}!{}@{}",
+        nickname(&user.username, ""),
+        user.user_id,
+        USER_HOST
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Instant;
+
+    use super::*;
+
+    fn irc(line: &str) -> Output {
+        Output::Irc(line.parse::<IrcMessage>().unwrap())
+    }
+
+    fn feed(frontend: &mut Frontend, session: &mut Session, lines: &[&str]) -> Vec<Output> {
+        let mut out = Vec::new();
+        for line in lines {
+            let packet = line.parse::<ServerPacket>().unwrap();
+            session.handle(&packet, Instant::now());
+            out.extend(frontend.sockchat(&packet));
+        }
+        out
+    }
+
+    fn connected() -> (Frontend, Session) {
+        let mut frontend = Frontend::new("kanii");
+        let mut session = Session::new("Misuzu", "secret");
+        for line in ["PASS secret", "NICK mikoto"] {
+            assert_eq!(frontend.irc(&line.parse().unwrap(), None), vec![]);
+        }
+        assert_eq!(
+            frontend.irc(&"USER mikoto 0 * :Mikoto".parse().unwrap(), None),
+            vec![Output::Connect {
+                authkey: "secret".to_string()
+            }]
+        );
+        session.authenticate();
+        (frontend, session)
+    }
+
+    #[test]
+    fn registers_and_joins_the_sockchat_channel() {
+        let (mut frontend, mut session) = connected();
+        let out = feed(
+            &mut frontend,
+            &mut session,
+            &[
+                "1\ty\t1\tmisaka\t#f00\t0 0 0 0 0\tlounge\t2000",
+                "7\t0\t1\t2\tflash\t#0f0\t0 1 0 0 0\t1",
+            ],
+        );
+        assert_eq!(out[0], irc(":mikoto NICK misaka"));
+        assert_eq!(
+            out[1],
+            irc(":kanii 001 misaka :Welcome to Sockchat, misaka")
+        );
+        assert_eq!(
+            out[out.len() - 3..],
+            [
+                irc(":misaka!1@sockchat JOIN #lounge"),
+                irc(":kanii 353 misaka = #lounge :@flash misaka"),
+                irc(":kanii 366 misaka #lounge :End of /NAMES list"),
+            ]
+        );
+        assert_eq!(frontend.nick(), Some("misaka"));
+
+        let out = feed(
+            &mut frontend,
+            &mut session,
+            &[
+                "1\t100\t3\tkuroko\t#00f\t0 1 0 0 0\t3",
+                "10\t2\tflash\t#0f0\t0 0 0 0 0",
+            ],
+        );
+        assert_eq!(
+            out,
+            vec![
+                irc(":kuroko!3@sockchat JOIN #lounge"),
+                irc(":kanii MODE #lounge +o kuroko"),
+                irc(":kanii MODE #lounge -o flash"),
+            ]
+        );
+    }
+
+    #[test]
+    fn translates_messages_both_ways() {
+        let (mut frontend, mut session) = connected();
+        feed(
+            &mut frontend,
+            &mut session,
+            &[
+                "1\ty\t1\tmisaka\t#f00\t0 0 0 0 0\tlounge\t2000",
+                "7\t0\t1\t2\tflash\t#0f0\t0 0 0 0 0\t1",
+            ],
+        );
+
+        let out = feed(
+            &mut frontend,
+            &mut session,
+            &[
+                "2\t100\t2\thi &lt;3\t10\t10010",
+                "2\t101\t2\twaves\t11\t11000",
+                "2\t102\t1\tmine\t12\t10010",
+            ],
+        );
+        assert_eq!(
+            out,
+            vec![
+                irc(":flash!2@sockchat PRIVMSG #lounge :hi <3"),
+                irc(":flash!2@sockchat PRIVMSG #lounge :\u{1}ACTION waves\u{1}"),
+            ]
+        );
+        assert_eq!(
+            feed(
+                &mut frontend,
+                &mut session,
+                &["2\t103\t2\thi&#13;:kanii 464 misaka :bye\t13\t10010"],
+            ),
+            vec![
+                irc(":flash!2@sockchat PRIVMSG #lounge :hi"),
+                irc(":flash!2@sockchat PRIVMSG #lounge ::kanii 464 misaka :bye"),
+            ]
+        );
+
+        let sent = |frontend: &mut Frontend, line: &str| {
+            frontend
+                .irc(&line.parse().unwrap(), Some(&session))
+                .into_iter()
+                .map(|output| match output {
+                    Output::Sockchat(ClientPacket::Message(packet)) => packet.message,
+                    output => panic!("unexpected {:?}", output),
+                })
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(sent(&mut frontend, "PRIVMSG #lounge :hello"), ["hello"]);
+        assert_eq!(
+            sent(&mut frontend, "PRIVMSG #lounge :\u{1}ACTION waves\u{1}"),
+            ["/me waves"]
+        );
+        assert_eq!(
+            sent(&mut frontend, "PRIVMSG Flash :psst"),
+            ["/msg flash psst"]
+        );
+        assert_eq!(
+            frontend.irc(&"PRIVMSG kuroko :hi".parse().unwrap(), Some(&session)),
+            vec![irc(":kanii 401 misaka kuroko :No such nick")]
+        );
+    }
+
+    #[test]
+    fn sends_only_to_the_current_channel() {
+        let (mut frontend, mut session) = connected();
+        feed(
+            &mut frontend,
+            &mut session,
+            &[
+                "1\ty\t1\tmisaka\t#f00\t0 0 0 0 0\tlounge\t2000\tMCHAN",
+                "5\t0\t1\tmisaka\t#f00\t0 0 0 0 0\t2\tgames",
+            ],
+        );
+        assert_eq!(frontend.state().joined_channels(), ["lounge", "games"]);
+        let mut privmsg = |line: &str| frontend.irc(&line.parse().unwrap(), Some(&session));
+
+        assert!(matches!(
+            privmsg("PRIVMSG #Lounge :hello")[..],
+            [Output::Sockchat(ClientPacket::Message(_))]
+        ));
+        assert_eq!(
+            privmsg("PRIVMSG #games :hello"),
+            vec![irc(
+                ":kanii 404 misaka #games :Sockchat only posts to #lounge"
+            )]
+        );
+        assert_eq!(
+            privmsg("PRIVMSG #nowhere :hello"),
+            vec![irc(
+                ":kanii 442 misaka #nowhere :You're not on that channel"
+            )]
+        );
+        assert_eq!(
+            privmsg("PRIVMSG #lounge :/kick flash"),
+            vec![irc(
+                ":kanii 404 misaka #lounge :Sockchat would run this as a command"
+            )]
+        );
+    }
+
+    #[test]
+    fn refuses_commands_before_registering() {
+        let mut frontend = Frontend::new("kanii");
+        assert_eq!(
+            frontend.irc(&"JOIN #lounge".parse().unwrap(), None),
+            vec![irc(":kanii 451 * :You have not registered")]
+        );
+    }
+}
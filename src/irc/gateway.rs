@@ -0,0 +1,267 @@
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    io,
+    time::Duration,
+};
+
+use crate::client::blocking::{self, Client};
+
+use super::{
+    bridge::{Bridge, Link, Output},
+    message::IrcMessage,
+    stream::LineStream,
+};
+
+/// How long each IRC connection is polled for per round.
+const IRC_POLL: Duration = Duration::from_millis(1);
+
+#[derive(Debug)]
+pub enum Error {
+    Sockchat(blocking::Error),
+    Irc(io::Error),
+}
+
+impl From<blocking::Error> for Error {
+    fn from(error: blocking::Error) -> Self {
+        Error::Sockchat(error)
+    }
+}
+
+impl From<io::Error> for Error {
+    fn from(error: io::Error) -> Self {
+        Error::Irc(error)
+    }
+}
+
+/// Runs a `Bridge` between a blocking Sockchat client and an IRC server,
+/// opening one connection per puppet on the calling thread.
+pub struct Gateway {
+    client: Client,
+    bridge: Bridge,
+    address: String,
+    links: BTreeMap<Link, LineStream>,
+}
+
+impl Gateway {
+    /// Connects the relay to the IRC server at `address`, e.g.
+    /// `irc.example.org:6667`.
+    pub fn new(client: Client, bridge: Bridge, address: &str) -> Result<Self, Error> {
+        let mut gateway = Gateway {
+            client,
+            bridge,
+            address: address.to_string(),
+            links: BTreeMap::new(),
+        };
+        let start = gateway.bridge.start();
+        gateway.dispatch(start)?;
+        Ok(gateway)
+    }
+
+    pub fn bridge(&self) -> &Bridge {
+        &self.bridge
+    }
+
+    pub fn client(&self) -> &Client {
+        &self.client
+    }
+
+    /// Waits up to `timeout` for a Sockchat packet, then handles whatever
+    /// the IRC connections received.
+    pub fn poll(&mut self, timeout: Duration) -> Result<(), Error> {
+        if let Some(packet) = self.client.recv_timeout(timeout)? {
+            let out = self.bridge.sockchat(&packet);
+            self.dispatch(out)?;
+        }
+
+        let mut out = Vec::new();
+        let mut closed = Vec::new();
+        for (link, connection) in &mut self.links {
+            let Some(lines) = connection.lines() else {
+                closed.push(link.clone());
+                continue;
+            };
+            for line in lines {
+                if let Ok(message) = line.parse::<IrcMessage>() {
+                    out.extend(self.bridge.irc(link, &message));
+                }
+            }
+        }
+        for link in closed {
+            self.links.remove(&link);
+            if link == Link::Relay {
+                return Err(Error::Irc(io::ErrorKind::ConnectionAborted.into()));
+            }
+            self.bridge.closed(&link);
+        }
+        self.dispatch(out)
+    }
+
+    /// Relays until either side fails.
+    pub fn run(&mut self) -> Result<(), Error> {
+        loop {
+            self.poll(Duration::from_millis(50))?;
+        }
+    }
+
+    fn dispatch(&mut self, out: Vec<Output>) -> Result<(), Error> {
+        // Puppets that failed in this batch, so their remaining lines are
+        // dropped instead of reconnecting for each of them.
+        let mut failed = BTreeSet::new();
+        for output in out {
+            match output {
+                Output::Sockchat(packet) => self.client.send(packet)?,
+                Output::Irc(link, _) if failed.contains(&link) => {}
+                Output::Irc(link, message) => {
+                    let sent = self.send(&link, &message);
+                    if message.command == "QUIT" {
+                        self.links.remove(&link);
+                    } else if let Err(error) = sent {
+                        if link == Link::Relay {
+                            return Err(error.into());
+                        }
+                        self.links.remove(&link);
+                        self.bridge.closed(&link);
+                        failed.insert(link);
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Sends `message` over `link`, connecting it first if needed.
+    fn send(&mut self, link: &Link, message: &IrcMessage) -> io::Result<()> {
+        if !self.links.contains_key(link) {
+            let connection = LineStream::connect(&self.address, IRC_POLL)?;
+            self.links.insert(link.clone(), connection);
+        }
+        self.links.get_mut(link).unwrap().send(message)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        io::{BufRead, BufReader},
+        net::TcpListener,
+        sync::{Arc, Mutex},
+        thread,
+        time::Instant,
+    };
+
+    use super::*;
+    use crate::{
+        packets::{
+            client::ClientPacket,
+            types::{Color, UserContext},
+        },
+        server::fake::FakeServerConfig,
+    };
+    use std::{io::Write, net::TcpStream};
+
+    /// Stand-in IRC server that welcomes every connection and writes down
+    /// what each of them sent, one log per connection.
+    struct StandIn {
+        address: String,
+        logs: Arc<Mutex<Vec<Vec<String>>>>,
+        streams: Arc<Mutex<Vec<TcpStream>>>,
+    }
+
+    impl StandIn {
+        fn start() -> Self {
+            let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+            let stand_in = StandIn {
+                address: listener.local_addr().unwrap().to_string(),
+                logs: Arc::default(),
+                streams: Arc::default(),
+            };
+            let (logs, streams) = (stand_in.logs.clone(), stand_in.streams.clone());
+            thread::spawn(move || {
+                for stream in listener.incoming() {
+                    let Ok(stream) = stream else {
+                        break;
+                    };
+                    let index = {
+                        let mut logs = logs.lock().unwrap();
+                        logs.push(Vec::new());
+                        logs.len() - 1
+                    };
+                    streams.lock().unwrap().push(stream.try_clone().unwrap());
+                    let logs = logs.clone();
+                    thread::spawn(move || {
+                        let mut writer = stream.try_clone().unwrap();
+                        for line in BufReader::new(stream).lines() {
+                            let Ok(line) = line else {
+                                break;
+                            };
+                            if line.starts_with("USER ") {
+                                let _ = writer.write_all(b":irc.local 001 you :Welcome\r\n");
+                            }
+                            logs.lock().unwrap()[index].push(line);
+                        }
+                    });
+                }
+            });
+            stand_in
+        }
+
+        fn logged(&self, connection: usize, line: &str) -> bool {
+            self.logs
+                .lock()
+                .unwrap()
+                .get(connection)
+                .is_some_and(|log| log.iter().any(|logged| logged == line))
+        }
+
+        fn send(&self, connection: usize, line: &str) {
+            self.streams.lock().unwrap()[connection]
+                .write_all(format!("{}\r\n", line).as_bytes())
+                .unwrap();
+        }
+    }
+
+    fn wait_for<F: FnMut() -> bool>(gateway: &mut Gateway, mut condition: F) {
+        let deadline = Instant::now() + Duration::from_secs(5);
+        while !condition() {
+            assert!(Instant::now() < deadline, "timed out");
+            gateway.poll(Duration::from_millis(10)).unwrap();
+        }
+    }
+
+    #[test]
+    fn bridges_a_fake_server_to_a_stand_in() {
+        let flash = UserContext {
+            user_id: "2".to_string(),
+            username: "flash".to_string(),
+            color: Color {
+                value: "#0f0".to_string(),
+            },
+            visible: true,
+            ..Default::default()
+        };
+        let server = FakeServerConfig::new().with_user(flash).start().unwrap();
+        let irc = StandIn::start();
+        let client = Client::connect(&server.url(), "Misuzu", "kanii").unwrap();
+        let bridge = Bridge::new("#lounge", "kanii");
+        let mut gateway = Gateway::new(client, bridge, &irc.address).unwrap();
+
+        // The relay connects first, flash's puppet second.
+        wait_for(&mut gateway, || irc.logged(1, "JOIN #lounge"));
+        assert_eq!(irc.logs.lock().unwrap()[1][0], "NICK flash[s]");
+
+        server.inject("2\t1700000000\t2\thello irc\t10\t10010".parse().unwrap());
+        wait_for(&mut gateway, || irc.logged(1, "PRIVMSG #lounge :hello irc"));
+
+        irc.send(0, ":misaka!m@host PRIVMSG #lounge :hi from irc");
+        wait_for(&mut gateway, || {
+            server.received().iter().any(|packet| {
+                matches!(packet, ClientPacket::Message(sent)
+                    if sent.message == "<misaka> hi from irc")
+            })
+        });
+
+        server.inject("3\t2\tflash\tleave\t1700000001\t11".parse().unwrap());
+        wait_for(&mut gateway, || irc.logged(1, "QUIT left"));
+        assert!(irc.logged(1, "PART #lounge left"));
+    }
+}
@@ -0,0 +1,217 @@
+use std::str::FromStr;
+
+/// Longest line, CR LF included, IRC servers have to accept.
+pub const MAX_LINE: usize = 512;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ParseIrcError {
+    Empty,
+    MissingCommand,
+}
+
+/// Why a message cannot be written out as a single line.
+#[derive(Debug, Clone, PartialEq)]
+pub enum LineError {
+    /// A part holds a CR, LF or NUL, any of which servers may take as the
+    /// end of the line.
+    LineBreak(String),
+}
+
+/// A single IRC protocol line, without its CR LF.
+///
+/// Message tags are accepted and dropped.
+#[derive(Debug, Clone, PartialEq)]
+pub struct IrcMessage {
+    /// `nick!user@host` or a server name.
+    pub prefix: Option<String>,
+    pub command: String,
+    pub params: Vec<String>,
+}
+
+impl IrcMessage {
+    pub fn new(command: &str, params: &[&str]) -> Self {
+        IrcMessage {
+            prefix: None,
+            command: command.to_string(),
+            params: params.iter().map(|param| param.to_string()).collect(),
+        }
+    }
+
+    pub fn with_prefix(mut self, prefix: &str) -> Self {
+        self.prefix = Some(prefix.to_string());
+        self
+    }
+
+    /// Nickname part of the prefix.
+    pub fn nick(&self) -> Option<&str> {
+        self.prefix
+            .as_deref()
+            .map(|prefix| prefix.split(['!', '@']).next().unwrap_or(prefix))
+    }
+
+    pub fn param(&self, index: usize) -> Option<&str> {
+        self.params.get(index).map(String::as_str)
+    }
+
+    pub fn to_line(&self) -> Result<String, LineError> {
+        let parts = self
+            .prefix
+            .iter()
+            .chain([&self.command])
+            .chain(&self.params);
+        for part in parts {
+            if part.contains(['\r', '\n', '\0']) {
+                return Err(LineError::LineBreak(part.clone()));
+            }
+        }
+        let mut line = String::new();
+        if let Some(prefix) = &self.prefix {
+            line.push(':');
+            line.push_str(prefix);
+            line.push(' ');
+        }
+        line.push_str(&self.command);
+        for (index, param) in self.params.iter().enumerate() {
+            line.push(' ');
+            let last = index + 1 == self.params.len();
+            if last && (param.is_empty() || param.contains(' ') || param.starts_with(':')) {
+                line.push(':');
+            }
+            line.push_str(param);
+        }
+        Ok(line)
+    }
+}
+
+impl FromStr for IrcMessage {
+    type Err = ParseIrcError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut rest = s.trim_end_matches(['\r', '\n']);
+        if rest.starts_with('@') {
+            rest = rest.split_once(' ').map_or("", |(_, rest)| rest);
+        }
+        rest = rest.trim_start_matches(' ');
+        if rest.is_empty() {
+            return Err(ParseIrcError::Empty);
+        }
+
+        let mut prefix = None;
+        if let Some(prefixed) = rest.strip_prefix(':') {
+            let (name, after) = prefixed.split_once(' ').unwrap_or((prefixed, ""));
+            prefix = Some(name.to_string());
+            rest = after.trim_start_matches(' ');
+        }
+
+        let (command, mut rest) = rest.split_once(' ').unwrap_or((rest, ""));
+        if command.is_empty() {
+            return Err(ParseIrcError::MissingCommand);
+        }
+        let mut params = Vec::new();
+        loop {
+            rest = rest.trim_start_matches(' ');
+            if rest.is_empty() {
+                break;
+            }
+            if let Some(trailing) = rest.strip_prefix(':') {
+                params.push(trailing.to_string());
+                break;
+            }
+            let (param, after) = rest.split_once(' ').unwrap_or((rest, ""));
+            params.push(param.to_string());
+            rest = after;
+        }
+
+        Ok(IrcMessage {
+            prefix,
+            command: command.to_ascii_uppercase(),
+            params,
+        })
+    }
+}
+
+/// Turns a Sockchat username into a valid nickname, ending with `suffix`.
+pub fn nickname(username: &str, suffix: &str) -> String {
+    let allowed = |c: char| c.is_ascii_alphanumeric() || "[]\\`_^{|}-".contains(c);
+    let mut nick = username
+        .chars()
+        .map(|c| if allowed(c) { c } else { '_' })
+        .take(30usize.saturating_sub(suffix.len()).max(1))
+        .collect::<String>();
+    if nick.is_empty() || nick.starts_with(|c: char| c.is_ascii_digit() || c == '-') {
+        nick.insert(0, '_');
+    }
+    nick.push_str(suffix);
+    nick
+}
+
+/// Splits `text` into pieces that fit in a message to `target` once the
+/// server adds a prefix of up to `prefix_len` bytes.
+pub fn split_text(text: &str, target: &str, prefix_len: usize) -> Vec<String> {
+    let budget = MAX_LINE
+        .saturating_sub(prefix_len + target.len() + "PRIVMSG  :\r\n".len())
+        .max(16);
+    let mut pieces = Vec::new();
+    for line in text.split(['\r', '\n']) {
+        let mut piece = String::new();
+        for c in line.chars().filter(|c| *c != '\0') {
+            if piece.len() + c.len_utf8() > budget {
+                pieces.push(std::mem::take(&mut piece));
+            }
+            piece.push(c);
+        }
+        if !piece.is_empty() {
+            pieces.push(piece);
+        }
+    }
+    pieces
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_and_writes_lines() {
+        let message = "@time=2024 :bob!b@host PRIVMSG #lounge :hi there\r\n"
+            .parse::<IrcMessage>()
+            .unwrap();
+        assert_eq!(message.prefix.as_deref(), Some("bob!b@host"));
+        assert_eq!(message.nick(), Some("bob"));
+        assert_eq!(message.command, "PRIVMSG");
+        assert_eq!(message.params, ["#lounge", "hi there"]);
+        assert_eq!(
+            message.to_line().unwrap(),
+            ":bob!b@host PRIVMSG #lounge :hi there"
+        );
+
+        let ping = "ping  irc.local".parse::<IrcMessage>().unwrap();
+        assert_eq!(ping, IrcMessage::new("PING", &["irc.local"]));
+        assert_eq!(
+            IrcMessage::new("PART", &["#lounge", ""]).to_line(),
+            Ok("PART #lounge :".to_string())
+        );
+        assert_eq!(
+            IrcMessage::new("PRIVMSG", &["#lounge", "hi\rQUIT :pwned"]).to_line(),
+            Err(LineError::LineBreak("hi\rQUIT :pwned".to_string()))
+        );
+        assert_eq!("".parse::<IrcMessage>(), Err(ParseIrcError::Empty));
+    }
+
+    #[test]
+    fn makes_nicknames_and_splits_text() {
+        assert_eq!(nickname("flash wave", "[s]"), "flash_wave[s]");
+        assert_eq!(nickname("1337", ""), "_1337");
+        assert_eq!(nickname("ユーザー", "|sc"), "____|sc");
+
+        let long = "a".repeat(1000);
+        let pieces = split_text(&format!("{}\nsecond", long), "#lounge", 100);
+        assert_eq!(pieces.len(), 4);
+        assert!(pieces.iter().all(|piece| piece.len() < MAX_LINE - 100));
+        assert_eq!(pieces[3], "second");
+        assert_eq!(
+            split_text("hi\rQUIT :pwned\r\nbye\0!", "#lounge", 100),
+            ["hi", "QUIT :pwned", "bye!"]
+        );
+    }
+}
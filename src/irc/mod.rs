@@ -0,0 +1,17 @@
+pub mod bridge;
+pub mod frontend;
+#[cfg(feature = "blocking")]
+pub mod gateway;
+pub mod message;
+#[cfg(feature = "blocking")]
+pub mod server;
+#[cfg(feature = "blocking")]
+mod stream;
+
+pub use bridge::{Bridge, Link, Output};
+pub use frontend::Frontend;
+#[cfg(feature = "blocking")]
+pub use gateway::Gateway;
+pub use message::{IrcMessage, LineError, ParseIrcError};
+#[cfg(feature = "blocking")]
+pub use server::IrcServer;
@@ -0,0 +1,300 @@
+use std::{
+    io,
+    net::{SocketAddr, TcpListener, TcpStream},
+    thread,
+    time::Duration,
+};
+
+use crate::client::blocking::Client;
+
+use super::{
+    frontend::{Frontend, Output},
+    message::IrcMessage,
+    stream::LineStream,
+};
+
+/// How long each side of a connection is waited on per round.
+const POLL: Duration = Duration::from_millis(10);
+/// How long further Sockchat packets are waited on once one arrived.
+const DRAIN: Duration = Duration::from_millis(1);
+
+/// Local IRC server handing every connection its own Sockchat session on
+/// the server at `url`, through a `Frontend`.
+#[derive(Debug)]
+pub struct IrcServer {
+    listener: TcpListener,
+    url: String,
+    method: String,
+    server_name: String,
+}
+
+impl IrcServer {
+    /// Listens on `address`, e.g. `127.0.0.1:6667`, for IRC clients of the
+    /// Sockchat server at `url`.
+    pub fn bind(address: &str, url: &str) -> io::Result<Self> {
+        Ok(IrcServer {
+            listener: TcpListener::bind(address)?,
+            url: url.to_string(),
+            method: "Misuzu".to_string(),
+            server_name: "kanii".to_string(),
+        })
+    }
+
+    /// Authentication method sent along with the authkeys, `Misuzu` by
+    /// default.
+    pub fn with_method(mut self, method: &str) -> Self {
+        self.method = method.to_string();
+        self
+    }
+
+    pub fn with_server_name(mut self, server_name: &str) -> Self {
+        self.server_name = server_name.to_string();
+        self
+    }
+
+    pub fn local_addr(&self) -> io::Result<SocketAddr> {
+        self.listener.local_addr()
+    }
+
+    /// Accepts connections forever, serving each on its own thread.
+    ///
+    /// Failing to accept one connection, e.g. when out of file descriptors,
+    /// does not stop the server: the error is handed to `failed` instead.
+    pub fn run<F: FnMut(io::Error)>(&self, mut failed: F) {
+        for stream in self.listener.incoming() {
+            let stream = match stream {
+                Ok(stream) => stream,
+                Err(error) => {
+                    failed(error);
+                    thread::sleep(POLL);
+                    continue;
+                }
+            };
+            let (url, method) = (self.url.clone(), self.method.clone());
+            let frontend = Frontend::new(&self.server_name);
+            thread::spawn(move || serve(stream, frontend, &url, &method));
+        }
+    }
+}
+
+struct Connection<'a> {
+    irc: LineStream,
+    client: Option<Client>,
+    url: &'a str,
+    method: &'a str,
+}
+
+impl Connection<'_> {
+    /// Carries out `out`, returning `false` once the connection is over.
+    fn dispatch(&mut self, out: Vec<Output>) -> bool {
+        for output in out {
+            let sent = match output {
+                Output::Irc(message) => self.irc.send(&message).is_ok(),
+                Output::Sockchat(packet) => match &mut self.client {
+                    Some(client) => client.send(packet).is_ok(),
+                    None => true,
+                },
+                Output::Connect { authkey } => {
+                    match Client::connect(self.url, self.method, &authkey) {
+                        Ok(client) => {
+                            self.client = Some(client);
+                            true
+                        }
+                        Err(error) => {
+                            let reason = format!("Cannot reach Sockchat: {:?}", error);
+                            let _ = self.irc.send(&IrcMessage::new("ERROR", &[&reason]));
+                            false
+                        }
+                    }
+                }
+                Output::Close => false,
+            };
+            if !sent {
+                return false;
+            }
+        }
+        true
+    }
+}
+
+fn serve(stream: TcpStream, mut frontend: Frontend, url: &str, method: &str) {
+    let Ok(irc) = LineStream::new(stream, POLL) else {
+        return;
+    };
+    let mut connection = Connection {
+        irc,
+        client: None,
+        url,
+        method,
+    };
+    while let Some(lines) = connection.irc.lines() {
+        for line in lines {
+            let Ok(message) = line.parse::<IrcMessage>() else {
+                continue;
+            };
+            let session = connection.client.as_ref().map(Client::session);
+            let out = frontend.irc(&message, session);
+            if !connection.dispatch(out) {
+                return close(connection);
+            }
+        }
+
+        // Everything Sockchat sent is handled before IRC is read again.
+        let mut wait = POLL;
+        while let Some(client) = &mut connection.client {
+            let out = match client.recv_timeout(wait) {
+                Ok(Some(packet)) => frontend.sockchat(&packet),
+                Ok(None) => break,
+                Err(error) => {
+                    let reason = format!("Sockchat connection lost: {:?}", error);
+                    let _ = connection.irc.send(&IrcMessage::new("ERROR", &[&reason]));
+                    return close(connection);
+                }
+            };
+            if !connection.dispatch(out) {
+                return close(connection);
+            }
+            wait = DRAIN;
+        }
+    }
+    close(connection)
+}
+
+fn close(mut connection: Connection) {
+    if let Some(client) = &mut connection.client {
+        let _ = client.close();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        io::{BufRead, BufReader, Write},
+        time::Instant,
+    };
+
+    use super::*;
+    use crate::{
+        packets::{
+            client::ClientPacket,
+            types::{Color, UserContext, UserPermissions},
+        },
+        server::fake::{AuthOutcome, FakeServerConfig},
+    };
+
+    struct IrcClient {
+        reader: BufReader<TcpStream>,
+        writer: TcpStream,
+    }
+
+    impl IrcClient {
+        fn connect(address: SocketAddr) -> Self {
+            let writer = TcpStream::connect(address).unwrap();
+            writer
+                .set_read_timeout(Some(Duration::from_secs(5)))
+                .unwrap();
+            IrcClient {
+                reader: BufReader::new(writer.try_clone().unwrap()),
+                writer,
+            }
+        }
+
+        fn send(&mut self, line: &str) {
+            self.writer
+                .write_all(format!("{}\r\n", line).as_bytes())
+                .unwrap();
+        }
+
+        /// Reads lines until one contains `text`, returning it.
+        fn expect(&mut self, text: &str) -> String {
+            loop {
+                let mut line = String::new();
+                self.reader.read_line(&mut line).unwrap();
+                assert!(!line.is_empty(), "closed before {:?}", text);
+                if line.contains(text) {
+                    return line.trim_end().to_string();
+                }
+            }
+        }
+    }
+
+    fn user(user_id: &str, username: &str, can_moderate: bool) -> UserContext {
+        UserContext {
+            user_id: user_id.to_string(),
+            username: username.to_string(),
+            color: Color {
+                value: "inherit".to_string(),
+            },
+            user_permissions: UserPermissions {
+                can_moderate,
+                ..Default::default()
+            },
+            visible: true,
+        }
+    }
+
+    #[test]
+    fn serves_irc_clients_a_sockchat_session() {
+        let sockchat = FakeServerConfig::new()
+            .with_user(user("2", "flash", true))
+            .with_auth("secret", AuthOutcome::Accept(user("1", "misaka", false)))
+            .start()
+            .unwrap();
+        let server = IrcServer::bind("127.0.0.1:0", &sockchat.url()).unwrap();
+        let address = server.local_addr().unwrap();
+        thread::spawn(move || server.run(|_| {}));
+
+        let mut irc = IrcClient::connect(address);
+        irc.send("CAP LS 302");
+        irc.send("PASS secret");
+        irc.send("NICK mikoto");
+        irc.send("USER mikoto 0 * :Mikoto");
+        assert_eq!(irc.expect("NICK"), ":mikoto NICK misaka");
+        irc.expect(" 001 misaka ");
+        irc.expect("JOIN #lounge");
+        assert_eq!(
+            irc.expect(" 353 "),
+            ":kanii 353 misaka = #lounge :@flash misaka"
+        );
+
+        irc.send("PRIVMSG #lounge :hello from irc");
+        irc.send("PRIVMSG flash :psst");
+        let deadline = Instant::now() + Duration::from_secs(5);
+        let sent = |message: &str| {
+            sockchat.received().iter().any(
+                |packet| matches!(packet, ClientPacket::Message(sent) if sent.message == message),
+            )
+        };
+        while !(sent("hello from irc") && sent("/msg flash psst")) {
+            assert!(Instant::now() < deadline, "timed out");
+            thread::sleep(Duration::from_millis(10));
+        }
+
+        sockchat.inject(
+            "2\t1700000000\t2\thi &amp; welcome\t10\t10010"
+                .parse()
+                .unwrap(),
+        );
+        assert_eq!(
+            irc.expect("PRIVMSG"),
+            ":flash!2@sockchat PRIVMSG #lounge :hi & welcome"
+        );
+        sockchat.inject("10\t2\tflash\tinherit\t0 0 0 0 0".parse().unwrap());
+        assert_eq!(irc.expect("MODE"), ":kanii MODE #lounge -o flash");
+        sockchat.inject("3\t2\tflash\tleave\t1700000001\t11".parse().unwrap());
+        assert_eq!(irc.expect("QUIT"), ":flash!2@sockchat QUIT Leaving");
+    }
+
+    #[test]
+    fn turns_away_clients_without_an_authkey() {
+        let server = IrcServer::bind("127.0.0.1:0", "ws://127.0.0.1:1").unwrap();
+        let address = server.local_addr().unwrap();
+        thread::spawn(move || server.run(|_| {}));
+
+        let mut irc = IrcClient::connect(address);
+        irc.send("NICK mikoto");
+        irc.send("USER mikoto 0 * :Mikoto");
+        irc.expect(" 464 mikoto ");
+        irc.expect("ERROR");
+    }
+}
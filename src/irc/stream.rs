@@ -0,0 +1,107 @@
+use std::{
+    io::{self, Read, Write},
+    net::TcpStream,
+    time::Duration,
+};
+
+use super::message::{IrcMessage, MAX_LINE};
+
+/// A TCP connection carrying IRC lines, read without blocking for long.
+///
+/// Lines longer than `MAX_LINE` are dropped, without waiting for their end
+/// to arrive.
+pub(crate) struct LineStream {
+    stream: TcpStream,
+    buffer: Vec<u8>,
+    /// Whether the rest of an overlong line is still to be skipped.
+    skipping: bool,
+}
+
+impl LineStream {
+    /// Wraps `stream`, waiting at most `poll` for data on every read.
+    pub fn new(stream: TcpStream, poll: Duration) -> io::Result<Self> {
+        stream.set_read_timeout(Some(poll))?;
+        Ok(LineStream {
+            stream,
+            buffer: Vec::new(),
+            skipping: false,
+        })
+    }
+
+    pub fn connect(address: &str, poll: Duration) -> io::Result<Self> {
+        Self::new(TcpStream::connect(address)?, poll)
+    }
+
+    pub fn send(&mut self, message: &IrcMessage) -> io::Result<()> {
+        let line = message
+            .to_line()
+            .map_err(|error| io::Error::new(io::ErrorKind::InvalidInput, format!("{:?}", error)))?;
+        self.stream.write_all(format!("{}\r\n", line).as_bytes())
+    }
+
+    /// Complete lines received so far, `None` once the other side hung up.
+    pub fn lines(&mut self) -> Option<Vec<String>> {
+        let mut chunk = [0; 4096];
+        let mut lines = Vec::new();
+        while lines.is_empty() {
+            match self.stream.read(&mut chunk) {
+                Ok(0) => return None,
+                Ok(read) => {
+                    self.buffer.extend_from_slice(&chunk[..read]);
+                    self.take_lines(&mut lines);
+                }
+                Err(error)
+                    if matches!(
+                        error.kind(),
+                        io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut
+                    ) =>
+                {
+                    break
+                }
+                Err(_) => return None,
+            }
+        }
+        Some(lines)
+    }
+
+    fn take_lines(&mut self, lines: &mut Vec<String>) {
+        while let Some(end) = self.buffer.iter().position(|byte| *byte == b'\n') {
+            let line = self.buffer.drain(..=end).collect::<Vec<_>>();
+            if !std::mem::take(&mut self.skipping) && line.len() <= MAX_LINE {
+                lines.push(String::from_utf8_lossy(&line).into_owned());
+            }
+        }
+        if self.buffer.len() > MAX_LINE {
+            self.buffer.clear();
+            self.skipping = true;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::TcpListener;
+
+    use super::*;
+
+    #[test]
+    fn drops_overlong_lines() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let mut client = TcpStream::connect(listener.local_addr().unwrap()).unwrap();
+        let (stream, _) = listener.accept().unwrap();
+        let mut lines = LineStream::new(stream, Duration::from_millis(10)).unwrap();
+
+        client.write_all(&[b'a'; 4 * MAX_LINE]).unwrap();
+        while !lines.skipping {
+            assert_eq!(lines.lines(), Some(vec![]));
+        }
+        assert!(lines.buffer.len() <= MAX_LINE);
+
+        client.write_all(b"aaa\r\nPING x\r\n").unwrap();
+        let mut received = Vec::new();
+        while received.is_empty() {
+            received = lines.lines().unwrap();
+        }
+        assert_eq!(received, ["PING x\r\n"]);
+    }
+}
@@ -1,9 +1,22 @@
 #![allow(dead_code)]
+pub mod bot;
+pub mod client;
+pub mod clock;
+pub mod irc;
+pub mod logs;
 pub mod packets;
+pub mod recording;
+pub mod script;
+pub mod server;
+pub mod text;
 
 #[cfg(test)]
 mod tests {
-    use std::{fs::File, io::{self, BufRead}, path::Path};
+    use std::{
+        fs::{self, File},
+        io::{self, BufRead},
+        path::Path,
+    };
 
     use packets::{server::ServerPacket, types::Sockchatable, Packet};
 
@@ -26,16 +39,37 @@ mod tests {
 
     #[test]
     fn server_tripple_conversion() {
-        let lines = read_lines("server_packets.txt").expect("Could not read from file");
+        let mut dialects = fs::read_dir("tests/corpus/v1")
+            .expect("Could not read the corpus directory")
+            .map(|entry| entry.expect("Could not read a corpus entry").path())
+            .filter(|path| path.is_dir())
+            .collect::<Vec<_>>();
+        dialects.sort();
+        assert!(!dialects.is_empty(), "no dialects in the corpus");
 
-        for (i, line) in lines.iter().enumerate() {
-            // println!("packeting line {}: {}", i, line);
-            let packet = Packet::Server(line.parse::<ServerPacket>().unwrap());
+        let mut lines = Vec::new();
+        for dialect in dialects {
+            lines.extend(read_lines(dialect.join("server.txt")).expect("Could not read from file"));
+        }
+
+        for (i, line) in lines
+            .iter()
+            .filter(|line| !line.is_empty() && !line.starts_with('#'))
+            .enumerate()
+        {
+            let packet = match line.parse::<ServerPacket>() {
+                Ok(packet) => Packet::Server(packet),
+                Err(error) => panic!("line {} does not parse ({:?}): {}", i, error, line),
+            };
             let converted = packet.to_sockstr();
-            // println!("converted line {}: {}", i, converted);
-            // println!("reconverting from {:#?}", packet);
-            let reconverted = Packet::Server(converted.parse::<ServerPacket>().unwrap()).to_sockstr();
-            println!("original {}: {}\nconverted: {}\nreconverted: {}\n", i, line, converted, reconverted);
+            let reconverted = match converted.parse::<ServerPacket>() {
+                Ok(packet) => Packet::Server(packet).to_sockstr(),
+                Err(error) => panic!("line {} does not reparse ({:?}): {}", i, error, converted),
+            };
+            println!(
+                "original {}: {}\nconverted: {}\nreconverted: {}\n",
+                i, line, converted, reconverted
+            );
             assert_eq!(converted, reconverted, "tripple conversion mismatch");
         }
     }
@@ -0,0 +1,632 @@
+use std::{
+    fmt,
+    io::{self, Write},
+};
+
+use crate::{
+    client::state::ChatState,
+    clock::{Clock, SystemClock},
+    packets::{
+        bot_message::{BotMessage, BOT_USER_ID},
+        server::{ChannelSwitchingPacket, ContextInformationPacket, JoinAuthPacket, ServerPacket},
+        types::{Color, DisconnectReason, MessageFlags, Sockchatable},
+    },
+    text::entities::{decode, encode},
+};
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum EntryKind {
+    Message {
+        sequence_id: String,
+        /// Decoded text, without the server's HTML entities.
+        text: String,
+        message_flags: MessageFlags,
+    },
+    /// Message from the server itself, sent as user `BOT_USER_ID`.
+    Notice {
+        message: BotMessage,
+    },
+    Join,
+    /// `reason` is only known for disconnects, not for leaving a channel.
+    Leave {
+        reason: Option<DisconnectReason>,
+    },
+}
+
+/// One line of a transcript.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Entry {
+    pub timestamp: i64,
+    pub channel: Option<String>,
+    pub user_id: String,
+    pub username: String,
+    pub color: Color,
+    pub kind: EntryKind,
+}
+
+/// Turns server packets into transcript entries.
+///
+/// Private messages are left out unless asked for, since transcripts are
+/// usually meant for publishing. Joins and departures within a channel carry
+/// no timestamp and are stamped with the transcriber's clock.
+pub struct Transcriber {
+    state: ChatState,
+    include_private: bool,
+    clock: Box<dyn Clock>,
+}
+
+impl Default for Transcriber {
+    fn default() -> Self {
+        Transcriber {
+            state: ChatState::new(),
+            include_private: false,
+            clock: Box::new(SystemClock),
+        }
+    }
+}
+
+impl fmt::Debug for Transcriber {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Transcriber")
+            .field("state", &self.state)
+            .field("include_private", &self.include_private)
+            .finish_non_exhaustive()
+    }
+}
+
+impl Transcriber {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn include_private(mut self) -> Self {
+        self.include_private = true;
+        self
+    }
+
+    /// Replaces the clock stamping channel joins and departures, e.g. with
+    /// one following the recording being exported.
+    pub fn with_clock<C: Clock + 'static>(mut self, clock: C) -> Self {
+        self.clock = Box::new(clock);
+        self
+    }
+
+    /// Users and channels as of the last packet seen.
+    pub fn state(&self) -> &ChatState {
+        &self.state
+    }
+
+    pub fn entry(&mut self, packet: &ServerPacket) -> Option<Entry> {
+        let entry = self.describe(packet);
+        self.state.handle(packet);
+        entry
+    }
+
+    fn describe(&self, packet: &ServerPacket) -> Option<Entry> {
+        let channel = self.state.channel_name().map(str::to_string);
+        let user = |user_id: &str| {
+            self.state
+                .user(user_id)
+                .map_or((user_id.to_string(), Color::default()), |user| {
+                    (user.username.clone(), user.color.clone())
+                })
+        };
+        match packet {
+            ServerPacket::ChatMessage(message) => {
+                if message.message_flags.private && !self.include_private {
+                    return None;
+                }
+                let (username, color) = user(&message.user_id);
+                Some(Entry {
+                    timestamp: message.timestamp,
+                    channel: self.state.message_channel(message).map(str::to_string),
+                    user_id: message.user_id.clone(),
+                    username,
+                    color,
+                    kind: message_kind(
+                        &message.user_id,
+                        &message.sequence_id,
+                        &message.message,
+                        &message.message_flags,
+                    ),
+                })
+            }
+
+            ServerPacket::ContextInformation(ContextInformationPacket::ExistingMessage {
+                timestamp,
+                user_id,
+                username,
+                color,
+                message,
+                sequence_id,
+                message_flags,
+                ..
+            }) => {
+                if message_flags.private && !self.include_private {
+                    return None;
+                }
+                Some(Entry {
+                    timestamp: *timestamp,
+                    channel,
+                    user_id: user_id.clone(),
+                    username: username.clone(),
+                    color: color.clone(),
+                    kind: message_kind(user_id, sequence_id, message, message_flags),
+                })
+            }
+
+            ServerPacket::JoinAuth(JoinAuthPacket::Join {
+                timestamp,
+                user_id,
+                username,
+                color,
+                ..
+            }) => Some(Entry {
+                timestamp: *timestamp,
+                channel,
+                user_id: user_id.clone(),
+                username: username.clone(),
+                color: color.clone(),
+                kind: EntryKind::Join,
+            }),
+
+            ServerPacket::ChannelSwitching(
+                switching @ ChannelSwitchingPacket::Join {
+                    user_id,
+                    username,
+                    color,
+                    ..
+                },
+            ) => Some(Entry {
+                timestamp: self.clock.now(),
+                channel: switching.channel_name().map(str::to_string).or(channel),
+                user_id: user_id.clone(),
+                username: username.clone(),
+                color: color.clone(),
+                kind: EntryKind::Join,
+            }),
+
+            ServerPacket::ChannelSwitching(
+                switching @ ChannelSwitchingPacket::Departure { user_id, .. },
+            ) => {
+                let (username, color) = user(user_id);
+                Some(Entry {
+                    timestamp: self.clock.now(),
+                    channel: switching.channel_name().map(str::to_string).or(channel),
+                    user_id: user_id.clone(),
+                    username,
+                    color,
+                    kind: EntryKind::Leave { reason: None },
+                })
+            }
+
+            ServerPacket::UserDisconnect(disconnect) => Some(Entry {
+                timestamp: disconnect.timestamp,
+                channel,
+                user_id: disconnect.user_id.clone(),
+                username: disconnect.username.clone(),
+                color: user(&disconnect.user_id).1,
+                kind: EntryKind::Leave {
+                    reason: Some(disconnect.reason.clone()),
+                },
+            }),
+
+            _ => None,
+        }
+    }
+}
+
+/// Bot messages become notices, as long as they parse.
+fn message_kind(
+    user_id: &str,
+    sequence_id: &str,
+    message: &str,
+    message_flags: &MessageFlags,
+) -> EntryKind {
+    let text = decode(message);
+    match text.parse::<BotMessage>() {
+        Ok(message) if user_id == BOT_USER_ID => EntryKind::Notice { message },
+        _ => EntryKind::Message {
+            sequence_id: sequence_id.to_string(),
+            text,
+            message_flags: message_flags.clone(),
+        },
+    }
+}
+
+/// Writes transcript entries in some output format.
+pub trait Exporter {
+    fn write_entry(&mut self, entry: &Entry) -> io::Result<()>;
+
+    /// Writes whatever has to follow the last entry.
+    fn finish(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Runs `packets` through a `Transcriber` into `exporter`, then finishes it.
+pub fn export<'a, I, E>(
+    packets: I,
+    transcriber: &mut Transcriber,
+    exporter: &mut E,
+) -> io::Result<()>
+where
+    I: IntoIterator<Item = &'a ServerPacket>,
+    E: Exporter,
+{
+    for packet in packets {
+        if let Some(entry) = transcriber.entry(packet) {
+            exporter.write_entry(&entry)?;
+        }
+    }
+    exporter.finish()
+}
+
+/// `HH:MM` of a Unix timestamp, in UTC.
+fn clock(timestamp: i64) -> String {
+    let seconds = timestamp.rem_euclid(86_400);
+    format!("{:02}:{:02}", seconds / 3600, seconds % 3600 / 60)
+}
+
+/// IRC-style log, e.g. `[12:34] <flash> hello`.
+///
+/// Messages without the colon flag are written as actions, bot messages as
+/// `-!-` notices, line breaks in a message become spaces.
+pub struct PlainTextExporter<W: Write> {
+    writer: W,
+}
+
+impl<W: Write> PlainTextExporter<W> {
+    pub fn new(writer: W) -> Self {
+        PlainTextExporter { writer }
+    }
+
+    pub fn into_inner(self) -> W {
+        self.writer
+    }
+}
+
+impl<W: Write> Exporter for PlainTextExporter<W> {
+    fn write_entry(&mut self, entry: &Entry) -> io::Result<()> {
+        let time = clock(entry.timestamp);
+        match &entry.kind {
+            EntryKind::Message {
+                text,
+                message_flags,
+                ..
+            } => {
+                let text = text.replace(['\r', '\n'], " ");
+                if message_flags.colon {
+                    writeln!(self.writer, "[{}] <{}> {}", time, entry.username, text)
+                } else {
+                    writeln!(self.writer, "[{}] * {} {}", time, entry.username, text)
+                }
+            }
+            EntryKind::Notice { message } => {
+                let text = message.summary().replace(['\r', '\n'], " ");
+                writeln!(self.writer, "[{}] -!- {}", time, text)
+            }
+            EntryKind::Join => {
+                writeln!(self.writer, "[{}] *** {} has joined", time, entry.username)
+            }
+            EntryKind::Leave { reason: None } => {
+                writeln!(self.writer, "[{}] *** {} has left", time, entry.username)
+            }
+            EntryKind::Leave {
+                reason: Some(reason),
+            } => writeln!(
+                self.writer,
+                "[{}] *** {} has left ({})",
+                time,
+                entry.username,
+                reason.to_sockstr()
+            ),
+        }
+    }
+}
+
+/// One JSON object per line.
+///
+/// Every object has the keys `type` (`message`, `notice`, `join` or
+/// `leave`), `timestamp`, `channel`, `user_id`, `username` and `color`.
+/// Messages add `sequence_id`, `text` and `flags`, notices add `id`, `args`
+/// and `error`, leaves add `reason`. Missing values
+/// are `null` rather than left out.
+#[cfg(feature = "json")]
+pub struct JsonLinesExporter<W: Write> {
+    writer: W,
+}
+
+#[cfg(feature = "json")]
+impl<W: Write> JsonLinesExporter<W> {
+    pub fn new(writer: W) -> Self {
+        JsonLinesExporter { writer }
+    }
+
+    pub fn into_inner(self) -> W {
+        self.writer
+    }
+}
+
+#[cfg(feature = "json")]
+impl<W: Write> Exporter for JsonLinesExporter<W> {
+    fn write_entry(&mut self, entry: &Entry) -> io::Result<()> {
+        use serde_json::{json, Value};
+
+        let mut object = json!({
+            "timestamp": entry.timestamp,
+            "channel": entry.channel,
+            "user_id": entry.user_id,
+            "username": entry.username,
+            "color": entry.color.as_hex().ok(),
+        });
+        let fields = match &entry.kind {
+            EntryKind::Message {
+                sequence_id,
+                text,
+                message_flags,
+            } => json!({
+                "type": "message",
+                "sequence_id": sequence_id,
+                "text": text,
+                "flags": {
+                    "bold": message_flags.bold,
+                    "cursive": message_flags.cursive,
+                    "underlined": message_flags.underlined,
+                    "colon": message_flags.colon,
+                    "private": message_flags.private,
+                },
+            }),
+            EntryKind::Notice { message } => json!({
+                "type": "notice",
+                "id": message.id,
+                "args": message.args,
+                "error": message.error,
+            }),
+            EntryKind::Join => json!({ "type": "join" }),
+            EntryKind::Leave { reason } => json!({
+                "type": "leave",
+                "reason": reason.as_ref().map(Sockchatable::to_sockstr),
+            }),
+        };
+        if let (Value::Object(object), Value::Object(fields)) = (&mut object, fields) {
+            object.extend(fields);
+        }
+        serde_json::to_writer(&mut self.writer, &object)?;
+        writeln!(self.writer)
+    }
+}
+
+const HTML_HEAD: &str = r#"<!DOCTYPE html>
+<html>
+<head>
+<meta charset="utf-8">
+<title>{title}</title>
+<style>
+body { font-family: sans-serif; background: #111; color: #ddd; }
+.entry { margin: 2px 0; }
+.time { color: #888; }
+.event { color: #888; font-style: italic; }
+.name { font-weight: bold; }
+</style>
+</head>
+<body>
+"#;
+
+/// Standalone HTML page with names in each user's color and messages styled
+/// after their flags.
+pub struct HtmlExporter<W: Write> {
+    writer: W,
+    title: String,
+    started: bool,
+}
+
+impl<W: Write> HtmlExporter<W> {
+    pub fn new(writer: W) -> Self {
+        HtmlExporter {
+            writer,
+            title: "Chat log".to_string(),
+            started: false,
+        }
+    }
+
+    pub fn with_title(mut self, title: &str) -> Self {
+        self.title = title.to_string();
+        self
+    }
+
+    pub fn into_inner(self) -> W {
+        self.writer
+    }
+
+    fn start(&mut self) -> io::Result<()> {
+        if !self.started {
+            self.started = true;
+            write!(
+                self.writer,
+                "{}",
+                HTML_HEAD.replace("{title}", &encode(&self.title))
+            )?;
+        }
+        Ok(())
+    }
+
+    fn name(entry: &Entry) -> String {
+        match entry.color.as_hex() {
+            Ok(hex) => format!(
+                r#"<span class="name" style="color: {}">{}</span>"#,
+                hex,
+                encode(&entry.username)
+            ),
+            Err(_) => format!(r#"<span class="name">{}</span>"#, encode(&entry.username)),
+        }
+    }
+}
+
+impl<W: Write> Exporter for HtmlExporter<W> {
+    fn write_entry(&mut self, entry: &Entry) -> io::Result<()> {
+        self.start()?;
+        let time = format!(r#"<span class="time">[{}]</span>"#, clock(entry.timestamp));
+        let name = Self::name(entry);
+        match &entry.kind {
+            EntryKind::Message {
+                text,
+                message_flags,
+                ..
+            } => {
+                let mut style = Vec::new();
+                if message_flags.bold {
+                    style.push("font-weight: bold");
+                }
+                if message_flags.cursive {
+                    style.push("font-style: italic");
+                }
+                if message_flags.underlined {
+                    style.push("text-decoration: underline");
+                }
+                writeln!(
+                    self.writer,
+                    r#"<div class="entry">{} {}{} <span style="{}">{}</span></div>"#,
+                    time,
+                    name,
+                    if message_flags.colon { ":" } else { "" },
+                    style.join("; "),
+                    encode(text)
+                )
+            }
+            EntryKind::Notice { message } => writeln!(
+                self.writer,
+                r#"<div class="entry event">{} {}</div>"#,
+                time,
+                encode(&message.summary())
+            ),
+            EntryKind::Join => writeln!(
+                self.writer,
+                r#"<div class="entry event">{} {} has joined</div>"#,
+                time, name
+            ),
+            EntryKind::Leave { reason } => writeln!(
+                self.writer,
+                r#"<div class="entry event">{} {} has left{}</div>"#,
+                time,
+                name,
+                reason.as_ref().map_or(String::new(), |reason| format!(
+                    " ({})",
+                    reason.to_sockstr()
+                ))
+            ),
+        }
+    }
+
+    fn finish(&mut self) -> io::Result<()> {
+        self.start()?;
+        writeln!(self.writer, "</body>\n</html>")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::clock::ManualClock;
+
+    fn packets() -> Vec<ServerPacket> {
+        [
+            "1\ty\t1\tkanii\t#f00\t0 0 0 0 0\tlounge\t2000",
+            "1\t45240\t2\tflash\t#00ff00\t0 0 0 0 0\ts1",
+            "2\t45300\t2\t&lt;hi&gt; there\ts2\t10010",
+            "2\t45360\t2\twaves\ts3\t00000",
+            "2\t45370\t2\tsecret\ts4\t00011",
+            "3\t2\tflash\tkick\t45420\ts5",
+        ]
+        .iter()
+        .map(|packet| packet.parse().unwrap())
+        .collect()
+    }
+
+    #[test]
+    fn exports_plain_text() {
+        let mut exporter = PlainTextExporter::new(Vec::new());
+        export(&packets(), &mut Transcriber::new(), &mut exporter).unwrap();
+        assert_eq!(
+            String::from_utf8(exporter.into_inner()).unwrap(),
+            "[12:34] *** flash has joined\n\
+             [12:35] <flash> <hi> there\n\
+             [12:36] * flash waves\n\
+             [12:37] *** flash has left (kick)\n"
+        );
+    }
+
+    #[test]
+    fn exports_bot_messages_as_notices() {
+        let mut packets = packets();
+        packets.push(
+            "2\t45400\t-1\t0\u{c}join\u{c}misaka\ts6\t10010"
+                .parse()
+                .unwrap(),
+        );
+        packets.push(
+            "2\t45410\t-1\tnot a bot message\ts7\t10010"
+                .parse()
+                .unwrap(),
+        );
+        let mut exporter = PlainTextExporter::new(Vec::new());
+        export(&packets, &mut Transcriber::new(), &mut exporter).unwrap();
+        let output = String::from_utf8(exporter.into_inner()).unwrap();
+        assert!(output.ends_with(
+            "[12:36] -!- join: misaka\n\
+             [12:36] <-1> not a bot message\n"
+        ));
+    }
+
+    #[test]
+    fn stamps_channel_switches_with_the_clock() {
+        let mut transcriber = Transcriber::new().with_clock(ManualClock::new(45_480_000));
+        let mut packets = packets();
+        packets.push("5\t0\t3\tmisaka\t#00f\t0 0 0 0 0\ts6".parse().unwrap());
+        let entries = packets
+            .iter()
+            .filter_map(|packet| transcriber.entry(packet))
+            .collect::<Vec<_>>();
+        let join = entries.last().unwrap();
+        assert_eq!(join.kind, EntryKind::Join);
+        assert_eq!(join.username, "misaka");
+        assert_eq!(join.timestamp, 45_480);
+    }
+
+    #[test]
+    fn exports_html() {
+        let mut exporter = HtmlExporter::new(Vec::new()).with_title("<lounge>");
+        export(&packets(), &mut Transcriber::new(), &mut exporter).unwrap();
+        let html = String::from_utf8(exporter.into_inner()).unwrap();
+        assert!(html.contains("<title>&lt;lounge&gt;</title>"));
+        assert!(html.contains(
+            r#"<span class="name" style="color: #00FF00">flash</span>: <span style="font-weight: bold">&lt;hi&gt; there</span>"#
+        ));
+        assert!(!html.contains("secret"));
+        assert!(html.ends_with("</html>\n"));
+    }
+
+    #[cfg(feature = "json")]
+    #[test]
+    fn exports_json_lines() {
+        let mut exporter = JsonLinesExporter::new(Vec::new());
+        export(
+            &packets(),
+            &mut Transcriber::new().include_private(),
+            &mut exporter,
+        )
+        .unwrap();
+        let output = String::from_utf8(exporter.into_inner()).unwrap();
+        let lines = output
+            .lines()
+            .map(|line| serde_json::from_str::<serde_json::Value>(line).unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(lines.len(), 5);
+        assert_eq!(lines[1]["type"], "message");
+        assert_eq!(lines[1]["text"], "<hi> there");
+        assert_eq!(lines[1]["channel"], "lounge");
+        assert_eq!(lines[3]["flags"]["private"], true);
+        assert_eq!(lines[4]["reason"], "kick");
+        assert!(lines[0].get("reason").is_none());
+    }
+}
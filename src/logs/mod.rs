@@ -0,0 +1,11 @@
+pub mod export;
+#[cfg(feature = "sqlite")]
+pub mod sqlite;
+
+#[cfg(feature = "json")]
+pub use export::JsonLinesExporter;
+pub use export::{
+    export, Entry, EntryKind, Exporter, HtmlExporter, PlainTextExporter, Transcriber,
+};
+#[cfg(feature = "sqlite")]
+pub use sqlite::{EventKind, LogQuery, SqliteStore, StoredEvent, StoredMessage};
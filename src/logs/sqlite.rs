@@ -0,0 +1,540 @@
+use std::path::Path;
+
+use rusqlite::{params, params_from_iter, types::Value, Connection, Row};
+
+use crate::{
+    client::state::ChatState,
+    clock::{Clock, SystemClock},
+    packets::{
+        server::{
+            ChannelEventPacket, ChannelSwitchingPacket, ContextInformationPacket, JoinAuthPacket,
+            ServerPacket,
+        },
+        types::{MessageFlags, Sockchatable},
+    },
+};
+
+const SCHEMA: &str = "
+    CREATE TABLE IF NOT EXISTS messages (
+        id INTEGER PRIMARY KEY,
+        sequence_id TEXT NOT NULL UNIQUE,
+        channel TEXT,
+        timestamp INTEGER NOT NULL,
+        user_id TEXT NOT NULL,
+        username TEXT,
+        message TEXT NOT NULL,
+        flags TEXT NOT NULL,
+        deleted INTEGER NOT NULL DEFAULT 0
+    );
+    CREATE INDEX IF NOT EXISTS messages_channel ON messages (channel, timestamp);
+    CREATE INDEX IF NOT EXISTS messages_user ON messages (user_id, timestamp);
+    CREATE VIRTUAL TABLE IF NOT EXISTS messages_fts USING fts5 (
+        message, content = 'messages', content_rowid = 'id'
+    );
+    CREATE TRIGGER IF NOT EXISTS messages_fts_insert AFTER INSERT ON messages BEGIN
+        INSERT INTO messages_fts (rowid, message) VALUES (new.id, new.message);
+    END;
+    CREATE TABLE IF NOT EXISTS events (
+        id INTEGER PRIMARY KEY,
+        timestamp INTEGER NOT NULL,
+        channel TEXT,
+        kind TEXT NOT NULL,
+        user_id TEXT,
+        username TEXT,
+        detail TEXT
+    );
+    CREATE INDEX IF NOT EXISTS events_channel ON events (channel, timestamp);
+";
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct StoredMessage {
+    pub sequence_id: String,
+    pub channel: Option<String>,
+    pub timestamp: i64,
+    pub user_id: String,
+    pub username: Option<String>,
+    pub message: String,
+    pub message_flags: MessageFlags,
+    /// Set once a `MessageDeletionPacket` came in; the text is kept for review.
+    pub deleted: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum EventKind {
+    Join,
+    Leave,
+    UserUpdate,
+    ChannelCreation,
+    ChannelUpdate,
+    ChannelDeletion,
+}
+
+impl EventKind {
+    fn as_str(&self) -> &'static str {
+        match self {
+            Self::Join => "join",
+            Self::Leave => "leave",
+            Self::UserUpdate => "user_update",
+            Self::ChannelCreation => "channel_creation",
+            Self::ChannelUpdate => "channel_update",
+            Self::ChannelDeletion => "channel_deletion",
+        }
+    }
+
+    fn parse(s: &str) -> Option<Self> {
+        [
+            Self::Join,
+            Self::Leave,
+            Self::UserUpdate,
+            Self::ChannelCreation,
+            Self::ChannelUpdate,
+            Self::ChannelDeletion,
+        ]
+        .into_iter()
+        .find(|kind| kind.as_str() == s)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct StoredEvent {
+    pub timestamp: i64,
+    pub channel: Option<String>,
+    pub kind: EventKind,
+    pub user_id: Option<String>,
+    pub username: Option<String>,
+    /// Disconnect reason, new channel name and the like.
+    pub detail: Option<String>,
+}
+
+/// Filters for `SqliteStore::messages` and `SqliteStore::events`.
+///
+/// Time bounds are inclusive Unix timestamps. `text` is an FTS5 query and
+/// only applies to messages.
+#[derive(Debug, Clone, Default)]
+pub struct LogQuery {
+    pub channel: Option<String>,
+    pub user_id: Option<String>,
+    pub since: Option<i64>,
+    pub until: Option<i64>,
+    pub text: Option<String>,
+    pub include_deleted: bool,
+    pub limit: Option<usize>,
+}
+
+impl LogQuery {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn channel(mut self, channel: &str) -> Self {
+        self.channel = Some(channel.to_string());
+        self
+    }
+
+    pub fn user(mut self, user_id: &str) -> Self {
+        self.user_id = Some(user_id.to_string());
+        self
+    }
+
+    pub fn between(mut self, since: i64, until: i64) -> Self {
+        self.since = Some(since);
+        self.until = Some(until);
+        self
+    }
+
+    pub fn search(mut self, text: &str) -> Self {
+        self.text = Some(text.to_string());
+        self
+    }
+
+    pub fn include_deleted(mut self) -> Self {
+        self.include_deleted = true;
+        self
+    }
+
+    pub fn limit(mut self, limit: usize) -> Self {
+        self.limit = Some(limit);
+        self
+    }
+
+    /// `WHERE` clause and its parameters for a table aliased `t`.
+    fn filter(&self, messages: bool) -> (String, Vec<Value>) {
+        let mut conditions = vec!["1 = 1".to_string()];
+        let mut values = Vec::new();
+        if let Some(channel) = &self.channel {
+            conditions.push("t.channel = ?".to_string());
+            values.push(Value::Text(channel.clone()));
+        }
+        if let Some(user_id) = &self.user_id {
+            conditions.push("t.user_id = ?".to_string());
+            values.push(Value::Text(user_id.clone()));
+        }
+        if let Some(since) = self.since {
+            conditions.push("t.timestamp >= ?".to_string());
+            values.push(Value::Integer(since));
+        }
+        if let Some(until) = self.until {
+            conditions.push("t.timestamp <= ?".to_string());
+            values.push(Value::Integer(until));
+        }
+        if messages {
+            if let Some(text) = &self.text {
+                conditions.push(
+                    "t.id IN (SELECT rowid FROM messages_fts WHERE messages_fts MATCH ?)"
+                        .to_string(),
+                );
+                values.push(Value::Text(text.clone()));
+            }
+            if !self.include_deleted {
+                conditions.push("t.deleted = 0".to_string());
+            }
+        }
+        let mut clause = format!(
+            "WHERE {} ORDER BY t.timestamp, t.id",
+            conditions.join(" AND ")
+        );
+        if let Some(limit) = self.limit {
+            clause.push_str(&format!(" LIMIT {}", limit));
+        }
+        (clause, values)
+    }
+}
+
+/// Chat archive in an SQLite database, fed with the packets a client receives.
+///
+/// Backlog messages and live ones end up in the same table; a message seen
+/// twice, e.g. after reconnecting, is only stored once. Deleted messages are
+/// flagged rather than removed. Events whose packets carry no timestamp
+/// are stamped with the store's clock.
+pub struct SqliteStore {
+    connection: Connection,
+    state: ChatState,
+    clock: Box<dyn Clock>,
+}
+
+impl SqliteStore {
+    pub fn open<P: AsRef<Path>>(path: P) -> rusqlite::Result<Self> {
+        Self::with_connection(Connection::open(path)?)
+    }
+
+    pub fn in_memory() -> rusqlite::Result<Self> {
+        Self::with_connection(Connection::open_in_memory()?)
+    }
+
+    pub fn with_connection(connection: Connection) -> rusqlite::Result<Self> {
+        connection.execute_batch(SCHEMA)?;
+        Ok(SqliteStore {
+            connection,
+            state: ChatState::new(),
+            clock: Box::new(SystemClock),
+        })
+    }
+
+    /// Replaces the clock stamping events, e.g. with one following the
+    /// recording being imported.
+    pub fn with_clock<C: Clock + 'static>(mut self, clock: C) -> Self {
+        self.clock = Box::new(clock);
+        self
+    }
+
+    pub fn connection(&self) -> &Connection {
+        &self.connection
+    }
+
+    pub fn ingest(&mut self, packet: &ServerPacket) -> rusqlite::Result<()> {
+        let channel = self.state.channel_name().map(str::to_string);
+        match packet {
+            ServerPacket::ChatMessage(message) => {
+                let channel = self.state.message_channel(message).map(str::to_string);
+                let username = self
+                    .state
+                    .user(&message.user_id)
+                    .map(|user| user.username.clone());
+                self.insert_message(
+                    &message.sequence_id,
+                    channel,
+                    message.timestamp,
+                    &message.user_id,
+                    username,
+                    &message.message,
+                    &message.message_flags,
+                )?;
+            }
+
+            ServerPacket::ContextInformation(ContextInformationPacket::ExistingMessage {
+                timestamp,
+                user_id,
+                username,
+                message,
+                sequence_id,
+                message_flags,
+                ..
+            }) => self.insert_message(
+                sequence_id,
+                channel,
+                *timestamp,
+                user_id,
+                Some(username.clone()),
+                message,
+                message_flags,
+            )?,
+
+            ServerPacket::MessageDeletion(deletion) => {
+                self.connection.execute(
+                    "UPDATE messages SET deleted = 1 WHERE sequence_id = ?",
+                    [&deletion.sequence_id],
+                )?;
+            }
+
+            ServerPacket::JoinAuth(JoinAuthPacket::Join {
+                timestamp,
+                user_id,
+                username,
+                ..
+            }) => self.insert_event(
+                *timestamp,
+                channel,
+                EventKind::Join,
+                Some(user_id),
+                Some(username),
+                None,
+            )?,
+
+            ServerPacket::UserDisconnect(disconnect) => self.insert_event(
+                disconnect.timestamp,
+                channel,
+                EventKind::Leave,
+                Some(&disconnect.user_id),
+                Some(&disconnect.username),
+                Some(&disconnect.reason.to_sockstr()),
+            )?,
+
+            ServerPacket::ChannelSwitching(
+                switching @ ChannelSwitchingPacket::Join {
+                    user_id, username, ..
+                },
+            ) => self.insert_event(
+                self.clock.now(),
+                switching.channel_name().map(str::to_string).or(channel),
+                EventKind::Join,
+                Some(user_id),
+                Some(username),
+                None,
+            )?,
+
+            ServerPacket::ChannelSwitching(
+                switching @ ChannelSwitchingPacket::Departure { user_id, .. },
+            ) => {
+                let username = self.state.user(user_id).map(|user| user.username.clone());
+                self.insert_event(
+                    self.clock.now(),
+                    switching.channel_name().map(str::to_string).or(channel),
+                    EventKind::Leave,
+                    Some(user_id),
+                    username.as_deref(),
+                    None,
+                )?
+            }
+
+            ServerPacket::UserUpdate(update) => self.insert_event(
+                self.clock.now(),
+                None,
+                EventKind::UserUpdate,
+                Some(&update.user_id),
+                Some(&update.username),
+                None,
+            )?,
+
+            ServerPacket::ChannelEvent(event) => {
+                let (kind, name, detail) = match event {
+                    ChannelEventPacket::Creation { channel_name, .. } => {
+                        (EventKind::ChannelCreation, channel_name, None)
+                    }
+                    ChannelEventPacket::Update {
+                        channel_name,
+                        new_name,
+                        ..
+                    } => (
+                        EventKind::ChannelUpdate,
+                        channel_name,
+                        Some(new_name.as_str()),
+                    ),
+                    ChannelEventPacket::Deletion { channel_name } => {
+                        (EventKind::ChannelDeletion, channel_name, None)
+                    }
+                };
+                self.insert_event(
+                    self.clock.now(),
+                    Some(name.clone()),
+                    kind,
+                    None,
+                    None,
+                    detail,
+                )?;
+            }
+
+            _ => {}
+        }
+        self.state.handle(packet);
+        Ok(())
+    }
+
+    pub fn messages(&self, query: &LogQuery) -> rusqlite::Result<Vec<StoredMessage>> {
+        let (clause, values) = query.filter(true);
+        let mut statement = self.connection.prepare(&format!(
+            "SELECT t.sequence_id, t.channel, t.timestamp, t.user_id, t.username, t.message, \
+             t.flags, t.deleted FROM messages t {}",
+            clause
+        ))?;
+        let rows = statement.query_map(params_from_iter(values), Self::message_from_row)?;
+        rows.collect()
+    }
+
+    pub fn events(&self, query: &LogQuery) -> rusqlite::Result<Vec<StoredEvent>> {
+        let (clause, values) = query.filter(false);
+        let mut statement = self.connection.prepare(&format!(
+            "SELECT t.timestamp, t.channel, t.kind, t.user_id, t.username, t.detail \
+             FROM events t {}",
+            clause
+        ))?;
+        let rows = statement.query_map(params_from_iter(values), |row| {
+            let kind = row.get::<_, String>(2)?;
+            Ok(StoredEvent {
+                timestamp: row.get(0)?,
+                channel: row.get(1)?,
+                kind: EventKind::parse(&kind).ok_or_else(|| {
+                    rusqlite::Error::InvalidColumnType(2, kind, rusqlite::types::Type::Text)
+                })?,
+                user_id: row.get(3)?,
+                username: row.get(4)?,
+                detail: row.get(5)?,
+            })
+        })?;
+        rows.collect()
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    fn insert_message(
+        &self,
+        sequence_id: &str,
+        channel: Option<String>,
+        timestamp: i64,
+        user_id: &str,
+        username: Option<String>,
+        message: &str,
+        message_flags: &MessageFlags,
+    ) -> rusqlite::Result<()> {
+        self.connection.execute(
+            "INSERT OR IGNORE INTO messages \
+             (sequence_id, channel, timestamp, user_id, username, message, flags) \
+             VALUES (?, ?, ?, ?, ?, ?, ?)",
+            params![
+                sequence_id,
+                channel,
+                timestamp,
+                user_id,
+                username,
+                message,
+                message_flags.to_sockstr()
+            ],
+        )?;
+        Ok(())
+    }
+
+    fn insert_event(
+        &self,
+        timestamp: i64,
+        channel: Option<String>,
+        kind: EventKind,
+        user_id: Option<&str>,
+        username: Option<&str>,
+        detail: Option<&str>,
+    ) -> rusqlite::Result<()> {
+        self.connection.execute(
+            "INSERT INTO events (timestamp, channel, kind, user_id, username, detail) \
+             VALUES (?, ?, ?, ?, ?, ?)",
+            params![timestamp, channel, kind.as_str(), user_id, username, detail],
+        )?;
+        Ok(())
+    }
+
+    fn message_from_row(row: &Row) -> rusqlite::Result<StoredMessage> {
+        Ok(StoredMessage {
+            sequence_id: row.get(0)?,
+            channel: row.get(1)?,
+            timestamp: row.get(2)?,
+            user_id: row.get(3)?,
+            username: row.get(4)?,
+            message: row.get(5)?,
+            message_flags: row
+                .get::<_, String>(6)?
+                .parse::<MessageFlags>()
+                .unwrap_or_default(),
+            deleted: row.get(7)?,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::clock::ManualClock;
+
+    fn store(packets: &[&str]) -> SqliteStore {
+        let mut store = SqliteStore::in_memory()
+            .unwrap()
+            .with_clock(ManualClock::new(500_000));
+        for packet in packets {
+            store
+                .ingest(&packet.parse::<ServerPacket>().unwrap())
+                .unwrap();
+        }
+        store
+    }
+
+    #[test]
+    fn archives_messages_and_events() {
+        let store = store(&[
+            "1\ty\t1\tkanii\t#f00\t0 0 0 0 0\tlounge\t2000",
+            "7\t1\t100\t2\tflash\t#0f0\t0 0 0 0 0\told news\ts1\t0\t00000",
+            "1\t150\t2\tflash\t#0f0\t0 0 0 0 0\ts2",
+            "2\t200\t2\thello world\ts3\t00000",
+            "2\t200\t2\thello world\ts3\t00000",
+            "2\t300\t1\tgoodbye world\ts4\t00000",
+            "6\ts4",
+            "3\t2\tflash\tleave\t400\ts5",
+            "4\t0\tgames\t0\t0",
+        ]);
+
+        let all = store.messages(&LogQuery::new()).unwrap();
+        assert_eq!(all.len(), 2);
+        assert_eq!(all[1].username.as_deref(), Some("flash"));
+        assert_eq!(all[1].channel.as_deref(), Some("lounge"));
+
+        let deleted = store.messages(&LogQuery::new().include_deleted()).unwrap();
+        assert!(deleted[2].deleted);
+        assert_eq!(
+            store
+                .messages(&LogQuery::new().search("world").include_deleted())
+                .unwrap()
+                .len(),
+            2
+        );
+        assert_eq!(
+            store
+                .messages(&LogQuery::new().user("2").between(150, 250))
+                .unwrap()
+                .len(),
+            1
+        );
+
+        let events = store.events(&LogQuery::new().channel("lounge")).unwrap();
+        let kinds = events.iter().map(|event| event.kind).collect::<Vec<_>>();
+        assert_eq!(kinds, vec![EventKind::Join, EventKind::Leave]);
+        assert_eq!(events[0].timestamp, 150);
+        assert_eq!(events[1].detail.as_deref(), Some("leave"));
+        let creation = &store.events(&LogQuery::new().channel("games")).unwrap()[0];
+        assert_eq!(creation.kind, EventKind::ChannelCreation);
+        assert_eq!(creation.timestamp, 500);
+    }
+}
@@ -0,0 +1,75 @@
+use std::str::FromStr;
+
+use super::types::{FieldError, FieldPolicy, ParsePacketError, ParseSockBool, Sockchatable};
+
+/// User id the server uses for its own informational messages.
+pub const BOT_USER_ID: &str = "-1";
+
+/// Bot message id the server uses to echo an outgoing whisper back to its
+/// sender, with the recipient's username and the text as arguments.
+pub const WHISPER_ECHO: &str = "whisper";
+
+/// Body of a chat message sent by the server itself.
+///
+/// Bot messages carry a language id and its arguments instead of text,
+/// separated by form feeds, e.g. `0\fjoin\fkanii` or `1\fnochan\flounge`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct BotMessage {
+    pub error: bool,
+    pub id: String,
+    pub args: Vec<String>,
+}
+
+impl BotMessage {
+    pub fn info(id: &str, args: &[&str]) -> Self {
+        BotMessage {
+            error: false,
+            id: id.to_string(),
+            args: args.iter().map(|arg| arg.to_string()).collect(),
+        }
+    }
+
+    pub fn error(id: &str, args: &[&str]) -> Self {
+        BotMessage {
+            error: true,
+            ..Self::info(id, args)
+        }
+    }
+
+    /// The id followed by its arguments, e.g. `join: kanii`, for showing
+    /// bot messages without a language file.
+    pub fn summary(&self) -> String {
+        match self.args.is_empty() {
+            true => self.id.clone(),
+            false => format!("{}: {}", self.id, self.args.join(", ")),
+        }
+    }
+}
+
+impl FromStr for BotMessage {
+    type Err = ParsePacketError;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut iter = s.split('\u{c}').map(str::to_string);
+        let error = iter
+            .next()
+            .ok_or(ParsePacketError::Empty)?
+            .parse_sockbool()
+            .map_err(|_| ParsePacketError::WrongFormat)?;
+        let id = iter.next().ok_or(ParsePacketError::WrongFormat)?;
+        let args = iter.collect();
+        Ok(BotMessage { error, id, args })
+    }
+}
+
+impl Sockchatable for BotMessage {
+    fn to_sockstr_with(&self, policy: FieldPolicy) -> Result<String, FieldError> {
+        let mut fields = vec![
+            self.error.to_sockstr(),
+            policy.field_with(&self.id, &['\u{c}'])?.into_owned(),
+        ];
+        for arg in &self.args {
+            fields.push(policy.field_with(arg, &['\u{c}'])?.into_owned());
+        }
+        Ok(fields.join("\u{c}"))
+    }
+}
@@ -1,9 +1,45 @@
-use crate::packets::types::{FromParts, ParsePacketError, Sockchatable};
+use crate::packets::types::{FieldError, FieldPolicy, FromParts, ParsePacketError, Sockchatable};
 
-#[derive(Debug)]
+/// Capability asking the server to let the client sit in several channels at once.
+pub const MCHAN: &str = "MCHAN";
+
+#[derive(Clone, PartialEq)]
 pub struct AuthenticationPacket {
     pub method: String,
     pub authkey: String,
+    /// Optional features the client supports, sent space-separated after the authkey.
+    pub capabilities: Vec<String>,
+}
+
+impl AuthenticationPacket {
+    pub fn new(method: &str, authkey: &str) -> Self {
+        AuthenticationPacket {
+            method: method.to_string(),
+            authkey: authkey.to_string(),
+            capabilities: Vec::new(),
+        }
+    }
+
+    pub fn with_capabilities(mut self, capabilities: &[&str]) -> Self {
+        self.capabilities = capabilities.iter().map(|c| c.to_string()).collect();
+        self
+    }
+
+    pub fn supports(&self, capability: &str) -> bool {
+        self.capabilities
+            .iter()
+            .any(|c| c.eq_ignore_ascii_case(capability))
+    }
+}
+
+impl std::fmt::Debug for AuthenticationPacket {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("AuthenticationPacket")
+            .field("method", &self.method)
+            .field("authkey", &"<redacted>")
+            .field("capabilities", &self.capabilities)
+            .finish()
+    }
 }
 
 impl FromParts for AuthenticationPacket {
@@ -11,12 +47,34 @@ impl FromParts for AuthenticationPacket {
         let mut iter = parts.into_iter();
         let method = iter.next().unwrap_or("default_method".to_string());
         let authkey = iter.next().unwrap_or("default_authkey".to_string());
-        Ok(AuthenticationPacket { method, authkey })
+        let capabilities = iter
+            .next()
+            .map(|capabilities| {
+                capabilities
+                    .split_whitespace()
+                    .map(str::to_string)
+                    .collect()
+            })
+            .unwrap_or_default();
+        Ok(AuthenticationPacket {
+            method,
+            authkey,
+            capabilities,
+        })
     }
 }
 
 impl Sockchatable for AuthenticationPacket {
-    fn to_sockstr(&self) -> String {
-        vec![self.method.as_str(), self.authkey.as_str()].join("\t")
+    fn to_sockstr_with(&self, policy: FieldPolicy) -> Result<String, FieldError> {
+        let mut parts = vec![policy.field(&self.method)?, policy.field(&self.authkey)?];
+        if !self.capabilities.is_empty() {
+            let capabilities = self
+                .capabilities
+                .iter()
+                .map(|capability| policy.field_with(capability, &[' ']))
+                .collect::<Result<Vec<_>, _>>()?;
+            parts.push(capabilities.join(" ").into());
+        }
+        Ok(parts.join("\t"))
     }
 }
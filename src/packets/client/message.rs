@@ -1,11 +1,29 @@
-use crate::packets::types::{FromParts, ParsePacketError, Sockchatable};
+use crate::packets::types::{FieldError, FieldPolicy, FromParts, ParsePacketError, Sockchatable};
 
-#[derive(Debug)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct MessagePacket {
     pub user_id: String,
     pub message: String,
 }
 
+impl MessagePacket {
+    /// Builds one packet per chunk `limits` turns `text` into.
+    pub fn checked(
+        user_id: &str,
+        text: &str,
+        limits: &MessageLimits,
+    ) -> Result<Vec<Self>, MessageError> {
+        Ok(limits
+            .prepare(text)?
+            .into_iter()
+            .map(|message| MessagePacket {
+                user_id: user_id.to_string(),
+                message,
+            })
+            .collect())
+    }
+}
+
 impl FromParts for MessagePacket {
     fn from_parts(parts: Vec<String>) -> Result<Self, ParsePacketError> {
         let mut iter = parts.into_iter();
@@ -15,7 +33,307 @@ impl FromParts for MessagePacket {
     }
 }
 impl Sockchatable for MessagePacket {
-    fn to_sockstr(&self) -> String {
-        vec![self.user_id.as_str(), self.message.as_str()].join("\t")
+    fn to_sockstr_with(&self, policy: FieldPolicy) -> Result<String, FieldError> {
+        Ok([
+            &*policy.field(&self.user_id)?,
+            &*policy.field(&self.message)?,
+        ]
+        .join("\t"))
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum MessageError {
+    Empty,
+    TooLong { length: usize, max_length: usize },
+    ContainsTab,
+    ContainsNewline,
+}
+
+/// How the server counts message length.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum LengthUnit {
+    #[default]
+    Chars,
+    Bytes,
+    /// UTF-16 code units, as counted by servers written in C# or JavaScript.
+    Utf16,
+}
+
+impl LengthUnit {
+    pub fn measure(&self, text: &str) -> usize {
+        match self {
+            Self::Chars => text.chars().count(),
+            Self::Bytes => text.len(),
+            Self::Utf16 => text.encode_utf16().count(),
+        }
+    }
+}
+
+/// Checks outgoing message text before it is put in a `MessagePacket`.
+///
+/// Tabs would split the message into extra fields on the wire, so they are
+/// replaced by spaces unless `reject_tabs` asks for an error instead.
+/// Newlines would end the packet early, so every line is sent as a message
+/// of its own unless `reject_newlines` asks for an error instead.
+///
+/// Only plain text, `/me` and `/msg` can be split; the latter two repeat
+/// their prefix on every chunk, counted against the maximum length.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct MessageLimits {
+    /// Usually `max_msg_length` from `JoinAuthPacket::GoodAuth`.
+    pub max_length: Option<usize>,
+    pub unit: LengthUnit,
+    pub reject_tabs: bool,
+    pub reject_newlines: bool,
+    /// Split text that is too long into several messages instead of failing.
+    pub split: bool,
+}
+
+impl MessageLimits {
+    pub fn new(max_length: usize) -> Self {
+        MessageLimits {
+            max_length: Some(max_length),
+            ..Default::default()
+        }
+    }
+
+    pub fn measured_in(mut self, unit: LengthUnit) -> Self {
+        self.unit = unit;
+        self
+    }
+
+    pub fn reject_tabs(mut self) -> Self {
+        self.reject_tabs = true;
+        self
+    }
+
+    pub fn reject_newlines(mut self) -> Self {
+        self.reject_newlines = true;
+        self
+    }
+
+    pub fn split_long(mut self) -> Self {
+        self.split = true;
+        self
+    }
+
+    /// Validates `text`, returning the messages to send in its place.
+    pub fn prepare(&self, text: &str) -> Result<Vec<String>, MessageError> {
+        if text.trim().is_empty() {
+            return Err(MessageError::Empty);
+        }
+        if self.reject_tabs && text.contains('\t') {
+            return Err(MessageError::ContainsTab);
+        }
+        let text = text.replace('\t', " ");
+        if !text.contains(['\r', '\n']) {
+            return self.prepare_line(&text);
+        }
+        if self.reject_newlines {
+            return Err(MessageError::ContainsNewline);
+        }
+
+        // Later lines repeat the prefix of the first, and may not turn into
+        // commands of their own.
+        let mut lines = text
+            .split(['\r', '\n'])
+            .filter(|line| !line.trim().is_empty());
+        let first = lines.next().ok_or(MessageError::Empty)?;
+        let prefix = repeatable_prefix(first).ok_or(MessageError::ContainsNewline)?;
+        let mut messages = self.prepare_line(first)?;
+        for line in lines {
+            if prefix.is_empty() && line.trim_start().starts_with('/') {
+                return Err(MessageError::ContainsNewline);
+            }
+            messages.extend(self.prepare_line(&format!("{}{}", prefix, line))?);
+        }
+        Ok(messages)
+    }
+
+    fn prepare_line(&self, text: &str) -> Result<Vec<String>, MessageError> {
+        let Some(max_length) = self.max_length.filter(|_| !self.fits(text)) else {
+            return Ok(vec![text.to_string()]);
+        };
+        let too_long = MessageError::TooLong {
+            length: self.unit.measure(text),
+            max_length,
+        };
+        let Some(prefix) = repeatable_prefix(text).filter(|_| self.split) else {
+            return Err(too_long);
+        };
+        let room = max_length.saturating_sub(self.unit.measure(prefix));
+        if room == 0 {
+            return Err(too_long);
+        }
+        Ok(self
+            .split_words(&text[prefix.len()..], room)
+            .into_iter()
+            .map(|chunk| format!("{}{}", prefix, chunk))
+            .collect())
+    }
+
+    fn fits(&self, text: &str) -> bool {
+        self.max_length
+            .is_none_or(|max_length| self.unit.measure(text) <= max_length)
+    }
+
+    /// Greedily packs whole words into chunks, cutting words only when a
+    /// single one does not fit on its own.
+    fn split_words(&self, text: &str, max_length: usize) -> Vec<String> {
+        let mut chunks = Vec::new();
+        let mut current = String::new();
+        for word in text.split_inclusive(char::is_whitespace) {
+            if self
+                .unit
+                .measure(&format!("{}{}", current, word.trim_end()))
+                <= max_length
+            {
+                current.push_str(word);
+                continue;
+            }
+            push_chunk(&mut chunks, &mut current);
+            for c in word.chars() {
+                if !current.is_empty()
+                    && self.unit.measure(&current) + self.unit.measure(&c.to_string()) > max_length
+                {
+                    push_chunk(&mut chunks, &mut current);
+                }
+                current.push(c);
+            }
+        }
+        push_chunk(&mut chunks, &mut current);
+        chunks
+    }
+}
+
+/// Moves `chunk` into `chunks` without surrounding whitespace, dropping it if
+/// nothing else is left.
+fn push_chunk(chunks: &mut Vec<String>, chunk: &mut String) {
+    let chunk = std::mem::take(chunk);
+    if !chunk.trim().is_empty() {
+        chunks.push(chunk.trim().to_string());
+    }
+}
+
+/// Part of `text` to repeat on every chunk it is split into, or `None` for
+/// commands that cannot be split.
+fn repeatable_prefix(text: &str) -> Option<&str> {
+    if !text.starts_with('/') {
+        return Some("");
+    }
+    let mut words = text.split_inclusive(' ');
+    let command = words.next()?;
+    let length = match command.trim_end().to_ascii_lowercase().as_str() {
+        "/me" => command.len(),
+        "/msg" => {
+            let username = words
+                .next()
+                .filter(|username| !username.trim().is_empty())?;
+            command.len() + username.len()
+        }
+        _ => return None,
+    };
+    (text.len() > length).then(|| &text[..length])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn validates_and_splits() {
+        let limits = MessageLimits::new(10);
+        assert_eq!(limits.prepare("a\tb").unwrap(), vec!["a b"]);
+        assert_eq!(
+            limits.clone().reject_tabs().prepare("a\tb"),
+            Err(MessageError::ContainsTab)
+        );
+        assert_eq!(limits.prepare(" \n"), Err(MessageError::Empty));
+        assert_eq!(
+            limits.prepare("héllo wörld"),
+            Err(MessageError::TooLong {
+                length: 11,
+                max_length: 10
+            })
+        );
+        assert!(limits
+            .clone()
+            .measured_in(LengthUnit::Bytes)
+            .prepare("ééééé")
+            .is_ok());
+        assert!(limits
+            .clone()
+            .measured_in(LengthUnit::Bytes)
+            .prepare("éééééé")
+            .is_err());
+
+        let packets = MessagePacket::checked(
+            "1",
+            "the quick brown fox jumps over thelazydoggo",
+            &limits.split_long(),
+        )
+        .unwrap();
+        let messages = packets
+            .iter()
+            .map(|packet| packet.message.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            messages,
+            vec!["the quick", "brown fox", "jumps over", "thelazydog", "go"]
+        );
+    }
+
+    #[test]
+    fn repeats_command_prefixes() {
+        let limits = MessageLimits::new(20).split_long();
+        assert_eq!(
+            limits.prepare("/msg flash the quick brown fox").unwrap(),
+            vec!["/msg flash the quick", "/msg flash brown fox"]
+        );
+        assert_eq!(
+            limits.prepare("/ME waves at everyone here").unwrap(),
+            vec!["/ME waves at", "/ME everyone here"]
+        );
+        assert!(matches!(
+            limits.prepare("/join a-very-long-channel-name"),
+            Err(MessageError::TooLong { .. })
+        ));
+        assert!(matches!(
+            limits.prepare("/msg someone-with-a-long-name hi"),
+            Err(MessageError::TooLong { .. })
+        ));
+        assert_eq!(
+            MessageLimits::new(4)
+                .split_long()
+                .prepare("abcdefgh x")
+                .unwrap(),
+            vec!["abcd", "efgh", "x"]
+        );
+    }
+
+    #[test]
+    fn sends_lines_separately() {
+        let limits = MessageLimits::new(20);
+        assert_eq!(
+            limits.prepare("hello\r\n\nworld").unwrap(),
+            vec!["hello", "world"]
+        );
+        assert_eq!(
+            limits.prepare("/msg flash hi\nbye").unwrap(),
+            vec!["/msg flash hi", "/msg flash bye"]
+        );
+        assert_eq!(
+            limits.prepare("hello\n/kick flash"),
+            Err(MessageError::ContainsNewline)
+        );
+        assert_eq!(
+            limits.prepare("/join lounge\nhi"),
+            Err(MessageError::ContainsNewline)
+        );
+        assert_eq!(
+            limits.reject_newlines().prepare("hello\nworld"),
+            Err(MessageError::ContainsNewline)
+        );
     }
 }
@@ -4,13 +4,13 @@ pub mod ping;
 
 use std::str::FromStr;
 
-use authentication::AuthenticationPacket;
-use message::MessagePacket;
-use ping::PingPacket;
+pub use authentication::{AuthenticationPacket, MCHAN};
+pub use message::{LengthUnit, MessageError, MessageLimits, MessagePacket};
+pub use ping::PingPacket;
 
 use super::types::*;
 
-#[derive(Debug)]
+#[derive(Debug, Clone, PartialEq)]
 pub enum ClientPacket {
     Ping(PingPacket),
     Authentication(AuthenticationPacket),
@@ -28,24 +28,21 @@ impl FromStr for ClientPacket {
 
         let first_part = parts.remove(0);
         match first_part.as_str() {
-            "0" => Ok(ClientPacket::Ping(PingPacket::from_parts(parts).unwrap())),
-            "1" => Ok(ClientPacket::Authentication(
-                AuthenticationPacket::from_parts(parts).unwrap(),
-            )),
-            "2" => Ok(ClientPacket::Message(
-                MessagePacket::from_parts(parts).unwrap(),
-            )),
+            "0" => PingPacket::from_parts(parts).map(ClientPacket::Ping),
+            "1" => AuthenticationPacket::from_parts(parts).map(ClientPacket::Authentication),
+            "2" => MessagePacket::from_parts(parts).map(ClientPacket::Message),
             _ => Err(ParsePacketError::WrongFormat),
         }
     }
 }
 
 impl Sockchatable for ClientPacket {
-    fn to_sockstr(&self) -> String {
-        match self {
-            Self::Ping(packet) => vec!["0", packet.to_sockstr().as_str()].join("\t"),
-            Self::Authentication(packet) => vec!["1", packet.to_sockstr().as_str()].join("\t"),
-            Self::Message(packet) => vec!["2", packet.to_sockstr().as_str()].join("\t"),
-        }
+    fn to_sockstr_with(&self, policy: FieldPolicy) -> Result<String, FieldError> {
+        let (id, packet) = match self {
+            Self::Ping(packet) => ("0", packet.to_sockstr_with(policy)?),
+            Self::Authentication(packet) => ("1", packet.to_sockstr_with(policy)?),
+            Self::Message(packet) => ("2", packet.to_sockstr_with(policy)?),
+        };
+        Ok([id, packet.as_str()].join("\t"))
     }
 }
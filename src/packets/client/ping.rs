@@ -1,6 +1,6 @@
-use crate::packets::types::{FromParts, ParsePacketError, Sockchatable};
+use crate::packets::types::{FieldError, FieldPolicy, FromParts, ParsePacketError, Sockchatable};
 
-#[derive(Debug)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct PingPacket {
     pub user_id: String,
 }
@@ -14,7 +14,7 @@ impl FromParts for PingPacket {
 }
 
 impl Sockchatable for PingPacket {
-    fn to_sockstr(&self) -> String {
-        self.user_id.clone()
+    fn to_sockstr_with(&self, policy: FieldPolicy) -> Result<String, FieldError> {
+        Ok(policy.field(&self.user_id)?.into_owned())
     }
 }
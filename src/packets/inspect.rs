@@ -0,0 +1,496 @@
+use crate::recording::Direction;
+
+use super::{
+    client::ClientPacket,
+    server::ServerPacket,
+    types::{self, ParseSockBool, FIELD_SEPARATOR},
+    Packet,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum FieldKind {
+    Text,
+    /// Text that should not end up in logs, such as an authkey.
+    Secret,
+    Integer,
+    Bool,
+    Color,
+    Permissions,
+    MessageFlags,
+    DisconnectReason,
+    BadAuthReason,
+    ClearingMode,
+}
+
+impl FieldKind {
+    fn accepts(&self, value: &str) -> bool {
+        let is_bool = |s: &str| s.to_string().parse_sockbool().is_ok();
+        match self {
+            Self::Text | Self::Secret => true,
+            Self::Integer => value.parse::<i64>().is_ok(),
+            Self::Bool => is_bool(value),
+            Self::Color => {
+                value == "inherit"
+                    || types::Color {
+                        value: value.to_string(),
+                    }
+                    .as_hex()
+                    .is_ok()
+            }
+            Self::Permissions => {
+                let parts = value.split(['\u{c}', ' ']).collect::<Vec<_>>();
+                parts.len() == 5
+                    && parts[0].parse::<u8>().is_ok()
+                    && parts[1..4].iter().all(|part| is_bool(part))
+                    && parts[4].parse::<u8>().is_ok()
+            }
+            Self::MessageFlags => value.len() == 5 && value.chars().all(|c| c == '0' || c == '1'),
+            Self::DisconnectReason => value.parse::<types::DisconnectReason>().is_ok(),
+            Self::BadAuthReason => value.parse::<types::BadAuthReason>().is_ok(),
+            Self::ClearingMode => ["0", "1", "2", "3", "4"].contains(&value),
+        }
+    }
+
+    fn describe(&self) -> &'static str {
+        match self {
+            Self::Text | Self::Secret => "text",
+            Self::Integer => "an integer",
+            Self::Bool => "0 or 1",
+            Self::Color => "a color",
+            Self::Permissions => "user permissions",
+            Self::MessageFlags => "five message flags",
+            Self::DisconnectReason => "a disconnect reason",
+            Self::BadAuthReason => "an auth failure reason",
+            Self::ClearingMode => "a clearing mode from 0 to 4",
+        }
+    }
+}
+
+type Field = (&'static str, FieldKind);
+
+/// Field names and kinds of one packet type, after its id and sub-id.
+struct Layout {
+    name: &'static str,
+    fields: &'static [Field],
+    /// Repeated as many times as the last of `fields`, a count, says.
+    repeated: &'static [Field],
+    /// Trailing fields that may be left out.
+    optional: &'static [Field],
+}
+
+impl Layout {
+    const fn new(name: &'static str, fields: &'static [Field]) -> Self {
+        Layout {
+            name,
+            fields,
+            repeated: &[],
+            optional: &[],
+        }
+    }
+
+    const fn repeating(mut self, repeated: &'static [Field]) -> Self {
+        self.repeated = repeated;
+        self
+    }
+
+    const fn optional(mut self, optional: &'static [Field]) -> Self {
+        self.optional = optional;
+        self
+    }
+}
+
+use FieldKind::*;
+
+const USER: [Field; 4] = [
+    ("user_id", Text),
+    ("username", Text),
+    ("color", Color),
+    ("user_permissions", Permissions),
+];
+
+/// Whether packets with this id tell their kind apart with a sub-id.
+fn has_sub_id(direction: Direction, id: &str) -> bool {
+    direction == Direction::Server && ["4", "5", "7"].contains(&id)
+}
+
+/// Which layout applies, and whether the field after the id is a sub-id.
+fn layout(direction: Direction, id: &str, first: Option<&str>) -> Option<(Layout, bool)> {
+    let plain = |layout: Layout| Some((layout, false));
+    let sub = |layout: Layout| Some((layout, true));
+    match (direction, id, first) {
+        (Direction::Client, "0", _) => plain(Layout::new("Ping", &[("user_id", Text)])),
+        (Direction::Client, "1", _) => plain(
+            Layout::new("Authentication", &[("method", Text), ("authkey", Secret)])
+                .optional(&[("capabilities", Text)]),
+        ),
+        (Direction::Client, "2", _) => plain(Layout::new(
+            "Message",
+            &[("user_id", Text), ("message", Text)],
+        )),
+
+        (Direction::Server, "0", _) => plain(Layout::new("Pong", &[("text", Text)])),
+        (Direction::Server, "1", Some("y")) => sub(Layout::new(
+            "JoinAuth::GoodAuth",
+            &[
+                USER[0],
+                USER[1],
+                USER[2],
+                USER[3],
+                ("channel_name", Text),
+                ("max_msg_length", Integer),
+            ],
+        )
+        .optional(&[("capabilities", Text)])),
+        (Direction::Server, "1", Some("n")) => sub(Layout::new(
+            "JoinAuth::BadAuth",
+            &[("reason", BadAuthReason), ("timestamp", Integer)],
+        )),
+        (Direction::Server, "1", _) => plain(Layout::new(
+            "JoinAuth::Join",
+            &[
+                ("timestamp", Integer),
+                USER[0],
+                USER[1],
+                USER[2],
+                USER[3],
+                ("sequence_id", Text),
+            ],
+        )),
+        (Direction::Server, "2", _) => plain(
+            Layout::new(
+                "ChatMessage",
+                &[
+                    ("timestamp", Integer),
+                    ("user_id", Text),
+                    ("message", Text),
+                    ("sequence_id", Text),
+                    ("message_flags", MessageFlags),
+                ],
+            )
+            .optional(&[("channel_name", Text)]),
+        ),
+        (Direction::Server, "3", _) => plain(Layout::new(
+            "UserDisconnect",
+            &[
+                ("user_id", Text),
+                ("username", Text),
+                ("reason", DisconnectReason),
+                ("timestamp", Integer),
+                ("sequence_id", Text),
+            ],
+        )),
+        (Direction::Server, "4", Some("0")) => sub(Layout::new(
+            "ChannelEvent::Creation",
+            &[
+                ("channel_name", Text),
+                ("is_protected", Bool),
+                ("is_temporary", Bool),
+            ],
+        )),
+        (Direction::Server, "4", Some("1")) => sub(Layout::new(
+            "ChannelEvent::Update",
+            &[
+                ("channel_name", Text),
+                ("new_name", Text),
+                ("is_protected", Bool),
+                ("is_temporary", Bool),
+            ],
+        )),
+        (Direction::Server, "4", Some("2")) => sub(Layout::new(
+            "ChannelEvent::Deletion",
+            &[("channel_name", Text)],
+        )),
+        (Direction::Server, "5", Some("0")) => sub(Layout::new(
+            "ChannelSwitching::Join",
+            &[USER[0], USER[1], USER[2], USER[3], ("sequence_id", Text)],
+        )
+        .optional(&[("channel_name", Text)])),
+        (Direction::Server, "5", Some("1")) => sub(Layout::new(
+            "ChannelSwitching::Departure",
+            &[("user_id", Text), ("sequence_id", Text)],
+        )
+        .optional(&[("channel_name", Text)])),
+        (Direction::Server, "5", Some("2")) => sub(Layout::new(
+            "ChannelSwitching::ForcedSwitch",
+            &[("channel_name", Text)],
+        )),
+        (Direction::Server, "6", _) => {
+            plain(Layout::new("MessageDeletion", &[("sequence_id", Text)]))
+        }
+        (Direction::Server, "7", Some("0")) => sub(Layout::new(
+            "ContextInformation::ExistingUsers",
+            &[("count", Integer)],
+        )
+        .repeating(&[USER[0], USER[1], USER[2], USER[3], ("visible", Bool)])),
+        (Direction::Server, "7", Some("1")) => sub(Layout::new(
+            "ContextInformation::ExistingMessage",
+            &[
+                ("timestamp", Integer),
+                USER[0],
+                USER[1],
+                USER[2],
+                USER[3],
+                ("message", Text),
+                ("sequence_id", Text),
+                ("notify", Bool),
+                ("message_flags", MessageFlags),
+            ],
+        )),
+        (Direction::Server, "7", Some("2")) => {
+            sub(
+                Layout::new("ContextInformation::Channels", &[("count", Integer)]).repeating(&[
+                    ("channel_name", Text),
+                    ("password_protected", Bool),
+                    ("temporary", Bool),
+                ]),
+            )
+        }
+        (Direction::Server, "8", _) => {
+            plain(Layout::new("ContextClearing", &[("mode", ClearingMode)]))
+        }
+        (Direction::Server, "9", _) => plain(Layout::new(
+            "ForcedDisconnect",
+            &[("ban", Bool), ("timestamp", Integer)],
+        )),
+        (Direction::Server, "10", _) => plain(Layout::new(
+            "UserUpdate",
+            &[USER[0], USER[1], USER[2], USER[3]],
+        )),
+        _ => None,
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct InspectedField {
+    pub name: String,
+    pub kind: FieldKind,
+    pub value: String,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum FieldIssue {
+    /// No known packet has this id.
+    Unknown,
+    /// The id is known, but not the sub-id after it.
+    UnknownSubId {
+        sub_id: String,
+    },
+    Missing {
+        name: String,
+    },
+    Invalid {
+        index: usize,
+        name: String,
+        expected: &'static str,
+    },
+    /// More fields than the packet has, starting at `index`.
+    Unexpected {
+        index: usize,
+    },
+}
+
+/// A raw line taken apart field by field.
+///
+/// Field indices count from the packet id, which is field 0. The packet is
+/// only decoded when every field checks out, since the decoders fill in
+/// defaults for anything they cannot read.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Inspection {
+    pub direction: Direction,
+    pub id: String,
+    pub sub_id: Option<String>,
+    /// e.g. `ChatMessage` or `ChannelEvent::Creation`.
+    pub name: Option<&'static str>,
+    pub fields: Vec<InspectedField>,
+    pub issue: Option<FieldIssue>,
+    pub packet: Option<Packet>,
+}
+
+pub fn inspect(direction: Direction, line: &str) -> Inspection {
+    let mut parts = line.split(FIELD_SEPARATOR);
+    let id = parts.next().unwrap_or_default().to_string();
+    let rest = parts.collect::<Vec<_>>();
+    let mut inspection = Inspection {
+        direction,
+        id,
+        sub_id: None,
+        name: None,
+        fields: Vec::new(),
+        issue: None,
+        packet: None,
+    };
+
+    let Some((layout, has_sub_id)) = layout(direction, &inspection.id, rest.first().copied())
+    else {
+        inspection.issue = Some(match rest.first() {
+            _ if !has_sub_id(direction, &inspection.id) => FieldIssue::Unknown,
+            Some(sub_id) => {
+                inspection.sub_id = Some(sub_id.to_string());
+                FieldIssue::UnknownSubId {
+                    sub_id: sub_id.to_string(),
+                }
+            }
+            None => FieldIssue::Missing {
+                name: "sub_id".to_string(),
+            },
+        });
+        return inspection;
+    };
+    inspection.name = Some(layout.name);
+    let values = if has_sub_id {
+        inspection.sub_id = Some(rest[0].to_string());
+        &rest[1..]
+    } else {
+        &rest[..]
+    };
+    let offset = 1 + has_sub_id as usize;
+
+    let mut expected = layout.fields.to_vec();
+    if !layout.repeated.is_empty() {
+        // At most what the values left fill, plus one to flag the shortfall.
+        let left = values.len().saturating_sub(layout.fields.len());
+        let count = values
+            .get(layout.fields.len() - 1)
+            .and_then(|count| count.parse::<usize>().ok())
+            .unwrap_or(0)
+            .min(left / layout.repeated.len() + 1);
+        for _ in 0..count {
+            expected.extend_from_slice(layout.repeated);
+        }
+    }
+    let required = expected.len();
+    expected.extend_from_slice(layout.optional);
+
+    for (index, value) in values.iter().enumerate() {
+        let Some((name, kind)) = expected.get(index) else {
+            inspection.issue.get_or_insert(FieldIssue::Unexpected {
+                index: index + offset,
+            });
+            inspection.fields.push(InspectedField {
+                name: format!("extra_{}", index - expected.len()),
+                kind: Text,
+                value: value.to_string(),
+            });
+            continue;
+        };
+        if !kind.accepts(value) {
+            inspection.issue.get_or_insert(FieldIssue::Invalid {
+                index: index + offset,
+                name: name.to_string(),
+                expected: kind.describe(),
+            });
+        }
+        inspection.fields.push(InspectedField {
+            name: name.to_string(),
+            kind: *kind,
+            value: value.to_string(),
+        });
+    }
+    if let Some((name, _)) = expected
+        .get(values.len())
+        .filter(|_| values.len() < required)
+    {
+        inspection.issue.get_or_insert(FieldIssue::Missing {
+            name: name.to_string(),
+        });
+    }
+
+    if inspection.issue.is_none() {
+        inspection.packet = match direction {
+            Direction::Client => line.parse::<ClientPacket>().map(Packet::Client).ok(),
+            Direction::Server => line.parse::<ServerPacket>().map(Packet::Server).ok(),
+        };
+    }
+    inspection
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn flags_the_first_bad_field() {
+        let good = inspect(Direction::Server, "2\t1700000000\t2\thi\ts1\t10010");
+        assert_eq!(good.name, Some("ChatMessage"));
+        assert_eq!(good.fields[4].name, "message_flags");
+        assert!(good.issue.is_none() && good.packet.is_some());
+
+        let bad = inspect(Direction::Server, "2\tsoon\t2\thi\ts1\t100");
+        assert_eq!(
+            bad.issue,
+            Some(FieldIssue::Invalid {
+                index: 1,
+                name: "timestamp".to_string(),
+                expected: "an integer",
+            })
+        );
+        assert!(bad.packet.is_none());
+
+        let users = inspect(
+            Direction::Server,
+            "7\t0\t2\t1\tkanii\t#f00\t0 0 0 0 0\t1\t2\tflash",
+        );
+        assert_eq!(users.sub_id.as_deref(), Some("0"));
+        assert_eq!(
+            users.issue,
+            Some(FieldIssue::Missing {
+                name: "color".to_string()
+            })
+        );
+
+        assert_eq!(
+            inspect(Direction::Server, "4\t7").issue,
+            Some(FieldIssue::UnknownSubId {
+                sub_id: "7".to_string()
+            })
+        );
+        assert_eq!(
+            inspect(Direction::Server, "5").issue,
+            Some(FieldIssue::Missing {
+                name: "sub_id".to_string()
+            })
+        );
+        assert_eq!(
+            inspect(Direction::Server, "12\t0").issue,
+            Some(FieldIssue::Unknown)
+        );
+        assert_eq!(
+            inspect(Direction::Client, "0\t1\tmore").issue,
+            Some(FieldIssue::Unexpected { index: 2 })
+        );
+        assert_eq!(
+            inspect(
+                Direction::Server,
+                "1\t1700000000\t2\tflash\tinherit\t0 0 0 0 0\ts1"
+            )
+            .name,
+            Some("JoinAuth::Join")
+        );
+    }
+
+    #[test]
+    fn bounds_repeats_by_the_values_left() {
+        let users = inspect(Direction::Server, "7\t0\t100000000000\t1\tkanii");
+        assert_eq!(users.fields.len(), 3);
+        assert_eq!(
+            users.issue,
+            Some(FieldIssue::Missing {
+                name: "color".to_string()
+            })
+        );
+        assert_eq!(
+            inspect(Direction::Server, "7\t0\t100000000000").issue,
+            Some(FieldIssue::Missing {
+                name: "user_id".to_string()
+            })
+        );
+
+        let channels = inspect(Direction::Server, "7\t2\t2\tlounge\t0\t0");
+        assert_eq!(
+            channels.issue,
+            Some(FieldIssue::Missing {
+                name: "channel_name".to_string()
+            })
+        );
+    }
+}
@@ -1,20 +1,154 @@
-use types::Sockchatable;
+use types::{FieldError, FieldPolicy, Sockchatable};
 
+pub mod bot_message;
 pub mod client;
+pub mod inspect;
 pub mod server;
 pub mod types;
 
-#[derive(Debug)]
+#[derive(Debug, Clone, PartialEq)]
 pub enum Packet {
     Client(client::ClientPacket),
     Server(server::ServerPacket),
 }
 
 impl Sockchatable for Packet {
-    fn to_sockstr(&self) -> String {
+    fn to_sockstr_with(&self, policy: FieldPolicy) -> Result<String, FieldError> {
         match self {
-            Self::Client(packet) => packet.to_sockstr(),
-            Self::Server(packet) => packet.to_sockstr(),
+            Self::Client(packet) => packet.to_sockstr_with(policy),
+            Self::Server(packet) => packet.to_sockstr_with(policy),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        client::{AuthenticationPacket, ClientPacket, MessagePacket},
+        server::*,
+        types::*,
+        Packet,
+    };
+
+    /// Small xorshift generator so the fuzz cases are reproducible without
+    /// pulling in a fuzzing crate.
+    struct Rng(u64);
+
+    impl Rng {
+        fn next(&mut self) -> u64 {
+            self.0 ^= self.0 << 13;
+            self.0 ^= self.0 >> 7;
+            self.0 ^= self.0 << 17;
+            self.0
+        }
+
+        fn string(&mut self) -> String {
+            const ALPHABET: [char; 8] = ['a', 'Z', '0', ' ', '\t', '\u{c}', '\n', 'é'];
+            let length = self.next() % 8;
+            (0..length)
+                .map(|_| ALPHABET[(self.next() % ALPHABET.len() as u64) as usize])
+                .collect()
+        }
+    }
+
+    fn user(rng: &mut Rng) -> UserContext {
+        UserContext {
+            user_id: rng.string(),
+            username: rng.string(),
+            color: Color {
+                value: rng.string(),
+            },
+            ..Default::default()
+        }
+    }
+
+    fn channel(rng: &mut Rng) -> ChannelContext {
+        ChannelContext {
+            channel_name: rng.string(),
+            ..Default::default()
+        }
+    }
+
+    fn packets(rng: &mut Rng) -> Vec<Packet> {
+        let server = [
+            ServerPacket::pong(&rng.string()),
+            JoinAuthPacket::good_auth(&user(rng), &rng.string(), 2000).into(),
+            ChatMessagePacket::new(
+                1,
+                &rng.string(),
+                &rng.string(),
+                &rng.string(),
+                MessageFlags::default(),
+            )
+            .in_channel(&rng.string())
+            .into(),
+            UserDisconnectPacket::new(&user(rng), DisconnectReason::Kick, 1, &rng.string()).into(),
+            ChannelEventPacket::update(&rng.string(), &channel(rng)).into(),
+            ChannelSwitchingPacket::join(&user(rng), &rng.string())
+                .in_channel(&rng.string())
+                .into(),
+            ContextInformationPacket::users([user(rng), user(rng)]).into(),
+            ContextInformationPacket::message(
+                1,
+                &user(rng),
+                &rng.string(),
+                &rng.string(),
+                true,
+                MessageFlags::default(),
+            )
+            .into(),
+            ContextInformationPacket::channels([channel(rng), channel(rng)]).into(),
+            UserUpdatePacket::new(&user(rng)).into(),
+        ];
+        let client = [
+            ClientPacket::Authentication(AuthenticationPacket::new(&rng.string(), &rng.string())),
+            ClientPacket::Message(MessagePacket {
+                user_id: rng.string(),
+                message: rng.string(),
+            }),
+        ];
+        server
+            .into_iter()
+            .map(Packet::Server)
+            .chain(client.into_iter().map(Packet::Client))
+            .collect()
+    }
+
+    fn field_count(sockstr: &str) -> usize {
+        sockstr.split(FIELD_SEPARATOR).count()
+    }
+
+    #[test]
+    fn serialized_packets_keep_their_field_count() {
+        let expected = packets(&mut Rng(1))
+            .iter()
+            .map(|packet| field_count(&packet.to_sockstr()))
+            .collect::<Vec<_>>();
+        let mut rng = Rng(0x5eed);
+        for _ in 0..500 {
+            for (packet, expected) in packets(&mut rng).iter().zip(&expected) {
+                let sockstr = packet.to_sockstr();
+                assert_eq!(field_count(&sockstr), *expected, "{:?}", sockstr);
+
+                let reparsed = match packet {
+                    Packet::Server(_) => Packet::Server(sockstr.parse::<ServerPacket>().unwrap()),
+                    Packet::Client(_) => Packet::Client(sockstr.parse::<ClientPacket>().unwrap()),
+                };
+                assert_eq!(
+                    field_count(&reparsed.to_sockstr()),
+                    *expected,
+                    "{:?}",
+                    sockstr
+                );
+
+                match packet.to_sockstr_with(FieldPolicy::Reject) {
+                    Ok(strict) => {
+                        assert_eq!(strict, sockstr);
+                        assert!(!format!("{:?}", packet).contains("\\t"));
+                    }
+                    Err(FieldError::ContainsSeparator(value)) => assert!(value.contains('\t')),
+                }
+            }
         }
     }
 }
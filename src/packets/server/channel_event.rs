@@ -1,6 +1,6 @@
 use crate::packets::types::*;
 
-#[derive(Debug)]
+#[derive(Debug, Clone, PartialEq)]
 pub enum ChannelEventPacket {
     Creation {
         channel_name: String,
@@ -18,10 +18,36 @@ pub enum ChannelEventPacket {
     },
 }
 
+impl ChannelEventPacket {
+    pub fn creation(channel: &ChannelContext) -> Self {
+        Self::Creation {
+            channel_name: channel.channel_name.clone(),
+            is_protected: channel.password_protected,
+            is_temporary: channel.temporary,
+        }
+    }
+
+    /// Announces that the channel formerly known as `channel_name` now looks like `channel`.
+    pub fn update(channel_name: &str, channel: &ChannelContext) -> Self {
+        Self::Update {
+            channel_name: channel_name.to_string(),
+            new_name: channel.channel_name.clone(),
+            is_protected: channel.password_protected,
+            is_temporary: channel.temporary,
+        }
+    }
+
+    pub fn deletion(channel_name: &str) -> Self {
+        Self::Deletion {
+            channel_name: channel_name.to_string(),
+        }
+    }
+}
+
 impl FromParts for ChannelEventPacket {
     fn from_parts(parts: Vec<String>) -> Result<Self, ParsePacketError> {
         let mut iter = parts.into_iter();
-        match iter.next().unwrap().as_str() {
+        match iter.next().ok_or(ParsePacketError::WrongFormat)?.as_str() {
             "0" => {
                 let channel_name = iter.next().unwrap_or("default_channel_name".to_string());
                 let is_protected = iter.next().unwrap_or("default_is_protected".to_string()).parse_sockbool().unwrap_or(false);
@@ -57,14 +83,15 @@ impl FromParts for ChannelEventPacket {
 }
 
 impl Sockchatable for ChannelEventPacket {
-    fn to_sockstr(&self) -> String {
-        match self {
+    fn to_sockstr_with(&self, policy: FieldPolicy) -> Result<String, FieldError> {
+        Ok(match self {
             Self::Creation {
                 channel_name,
                 is_protected,
                 is_temporary,
-            } => vec![
-                channel_name.as_str(),
+            } => [
+                "0",
+                &*policy.field(channel_name)?,
                 is_protected.to_sockstr().as_str(),
                 is_temporary.to_sockstr().as_str(),
             ]
@@ -75,15 +102,42 @@ impl Sockchatable for ChannelEventPacket {
                 new_name,
                 is_protected,
                 is_temporary,
-            } => vec![
-                channel_name.as_str(),
-                new_name.as_str(),
+            } => [
+                "1",
+                &*policy.field(channel_name)?,
+                &*policy.field(new_name)?,
                 is_protected.to_sockstr().as_str(),
                 is_temporary.to_sockstr().as_str(),
             ]
             .join("\t"),
 
-            Self::Deletion { channel_name } => channel_name.clone(),
+            Self::Deletion { channel_name } => ["2", &*policy.field(channel_name)?].join("\t"),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn serializes_the_event_type() {
+        let channel = ChannelContext {
+            channel_name: "games".to_string(),
+            password_protected: true,
+            temporary: false,
+        };
+        for (packet, sockstr) in [
+            (ChannelEventPacket::creation(&channel), "0\tgames\t1\t0"),
+            (
+                ChannelEventPacket::update("lounge", &channel),
+                "1\tlounge\tgames\t1\t0",
+            ),
+            (ChannelEventPacket::deletion("games"), "2\tgames"),
+        ] {
+            assert_eq!(packet.to_sockstr(), sockstr);
+            let parts = sockstr.split('\t').map(str::to_string).collect();
+            assert_eq!(ChannelEventPacket::from_parts(parts).unwrap(), packet);
         }
     }
 }
@@ -1,6 +1,6 @@
 use crate::packets::types::*;
 
-#[derive(Debug)]
+#[derive(Debug, Clone, PartialEq)]
 pub enum ChannelSwitchingPacket {
     Join {
         user_id: String,
@@ -8,41 +8,94 @@ pub enum ChannelSwitchingPacket {
         color: Color,
         user_permissions: UserPermissions,
         sequence_id: String,
+        /// Channel joined, only sent by multi-channel servers.
+        channel_name: Option<String>,
     },
     Departure {
         user_id: String,
         sequence_id: String,
+        /// Channel left, only sent by multi-channel servers.
+        channel_name: Option<String>,
     },
     ForcedSwitch {
         channel_name: String,
     },
 }
 
+impl ChannelSwitchingPacket {
+    pub fn join(user: &UserContext, sequence_id: &str) -> Self {
+        Self::Join {
+            user_id: user.user_id.clone(),
+            username: user.username.clone(),
+            color: user.color.clone(),
+            user_permissions: user.user_permissions.clone(),
+            sequence_id: sequence_id.to_string(),
+            channel_name: None,
+        }
+    }
+
+    pub fn departure(user_id: &str, sequence_id: &str) -> Self {
+        Self::Departure {
+            user_id: user_id.to_string(),
+            sequence_id: sequence_id.to_string(),
+            channel_name: None,
+        }
+    }
+
+    /// Attributes a join or departure to `channel`; forced switches are left as is.
+    pub fn in_channel(mut self, channel: &str) -> Self {
+        if let Self::Join { channel_name, .. } | Self::Departure { channel_name, .. } = &mut self {
+            *channel_name = Some(channel.to_string());
+        }
+        self
+    }
+
+    /// Channel a join or departure happened in, when the server says so.
+    pub fn channel_name(&self) -> Option<&str> {
+        match self {
+            Self::Join { channel_name, .. } | Self::Departure { channel_name, .. } => {
+                channel_name.as_deref()
+            }
+            Self::ForcedSwitch { channel_name } => Some(channel_name),
+        }
+    }
+
+    pub fn forced_switch(channel_name: &str) -> Self {
+        Self::ForcedSwitch {
+            channel_name: channel_name.to_string(),
+        }
+    }
+}
+
 impl FromParts for ChannelSwitchingPacket {
     fn from_parts(parts: Vec<String>) -> Result<Self, ParsePacketError> {
         let mut iter = parts.into_iter();
-        match iter.next().unwrap().as_str() {
+        match iter.next().ok_or(ParsePacketError::WrongFormat)?.as_str() {
             "0" => {
                 let user_id = iter.next().unwrap_or("default_user_id".to_string());
                 let username = iter.next().unwrap_or("default_username".to_string());
                 let color = iter.next().unwrap_or("default_color".to_string()).parse::<Color>().unwrap_or_default();
                 let user_permissions = iter.next().unwrap_or("default_user_permissions".to_string()).parse::<UserPermissions>().unwrap_or_default();
                 let sequence_id = iter.next().unwrap_or("default_sequence_id".to_string());
+                let channel_name = iter.next();
                 Ok(ChannelSwitchingPacket::Join {
                     user_id,
                     username,
                     color,
                     user_permissions,
                     sequence_id,
+                    channel_name,
                 })
             }
 
             "1" => {
                 let user_id = iter.next().unwrap_or("default_user_id".to_string());
                 let sequence_id = iter.next().unwrap_or("default_sequence_id".to_string());
+                let channel_name = iter.next();
                 Ok(ChannelSwitchingPacket::Departure {
                     user_id,
                     sequence_id,
+                    channel_name,
                 })
             }
 
@@ -57,29 +110,78 @@ impl FromParts for ChannelSwitchingPacket {
 }
 
 impl Sockchatable for ChannelSwitchingPacket {
-    fn to_sockstr(&self) -> String {
-        match self {
+    fn to_sockstr_with(&self, policy: FieldPolicy) -> Result<String, FieldError> {
+        Ok(match self {
             Self::Join {
                 user_id,
                 username,
                 color,
                 user_permissions,
                 sequence_id,
-            } => vec![
-                user_id.as_str(),
-                username.as_str(),
-                color.to_sockstr().as_str(),
-                user_permissions.to_sockstr().as_str(),
-                sequence_id.as_str(),
-            ]
-            .join("\t"),
+                channel_name,
+            } => {
+                let mut parts = vec![
+                    "0".to_string(),
+                    policy.field(user_id)?.into_owned(),
+                    policy.field(username)?.into_owned(),
+                    color.to_sockstr_with(policy)?,
+                    user_permissions.to_sockstr(),
+                    policy.field(sequence_id)?.into_owned(),
+                ];
+                if let Some(channel_name) = channel_name {
+                    parts.push(policy.field(channel_name)?.into_owned());
+                }
+                parts.join("\t")
+            }
 
             Self::Departure {
                 user_id,
                 sequence_id,
-            } => vec![user_id.as_str(), sequence_id.as_str()].join("\t"),
+                channel_name,
+            } => {
+                let mut parts = vec![
+                    "1".to_string(),
+                    policy.field(user_id)?.into_owned(),
+                    policy.field(sequence_id)?.into_owned(),
+                ];
+                if let Some(channel_name) = channel_name {
+                    parts.push(policy.field(channel_name)?.into_owned());
+                }
+                parts.join("\t")
+            }
+
+            Self::ForcedSwitch { channel_name } => ["2", &*policy.field(channel_name)?].join("\t"),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-            Self::ForcedSwitch { channel_name } => channel_name.clone(),
+    #[test]
+    fn serializes_the_switch_type() {
+        let user = UserContext {
+            user_id: "2".to_string(),
+            username: "flash".to_string(),
+            color: "#0f0".parse().unwrap(),
+            ..Default::default()
+        };
+        for (packet, sockstr) in [
+            (
+                ChannelSwitchingPacket::join(&user, "s1"),
+                "0\t2\tflash\t#0f0\t0 0 0 0 0\ts1",
+            ),
+            (
+                ChannelSwitchingPacket::join(&user, "s1").in_channel("games"),
+                "0\t2\tflash\t#0f0\t0 0 0 0 0\ts1\tgames",
+            ),
+            (ChannelSwitchingPacket::departure("2", "s2"), "1\t2\ts2"),
+            (ChannelSwitchingPacket::forced_switch("games"), "2\tgames"),
+        ] {
+            assert_eq!(packet.to_sockstr(), sockstr);
+            let parts = sockstr.split('\t').map(str::to_string).collect();
+            assert_eq!(ChannelSwitchingPacket::from_parts(parts).unwrap(), packet);
         }
     }
 }
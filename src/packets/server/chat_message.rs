@@ -1,12 +1,38 @@
 use crate::packets::types::*;
 
-#[derive(Debug)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct ChatMessagePacket {
     pub timestamp: i64,
     pub user_id: String,
     pub message: String,
     pub sequence_id: String,
     pub message_flags: MessageFlags,
+    /// Channel the message was posted in, only sent by multi-channel servers.
+    pub channel_name: Option<String>,
+}
+
+impl ChatMessagePacket {
+    pub fn new(
+        timestamp: i64,
+        user_id: &str,
+        message: &str,
+        sequence_id: &str,
+        message_flags: MessageFlags,
+    ) -> Self {
+        ChatMessagePacket {
+            timestamp,
+            user_id: user_id.to_string(),
+            message: message.to_string(),
+            sequence_id: sequence_id.to_string(),
+            message_flags,
+            channel_name: None,
+        }
+    }
+
+    pub fn in_channel(mut self, channel_name: &str) -> Self {
+        self.channel_name = Some(channel_name.to_string());
+        self
+    }
 }
 
 impl FromParts for ChatMessagePacket {
@@ -25,25 +51,30 @@ impl FromParts for ChatMessagePacket {
             .unwrap_or("default_message_flags".to_string())
             .parse::<MessageFlags>()
             .unwrap_or_default();
+        let channel_name = iter.next();
         Ok(ChatMessagePacket {
             timestamp,
             user_id,
             message,
             sequence_id,
             message_flags,
+            channel_name,
         })
     }
 }
 
 impl Sockchatable for ChatMessagePacket {
-    fn to_sockstr(&self) -> String {
-        vec![
-            self.timestamp.to_string().as_str(),
-            self.user_id.as_str(),
-            self.message.as_str(),
-            self.sequence_id.as_str(),
-            self.message_flags.to_sockstr().as_str(),
-        ]
-        .join("\t")
+    fn to_sockstr_with(&self, policy: FieldPolicy) -> Result<String, FieldError> {
+        let mut parts = vec![
+            self.timestamp.to_string(),
+            policy.field(&self.user_id)?.into_owned(),
+            policy.field(&self.message)?.into_owned(),
+            policy.field(&self.sequence_id)?.into_owned(),
+            self.message_flags.to_sockstr(),
+        ];
+        if let Some(channel_name) = &self.channel_name {
+            parts.push(policy.field(channel_name)?.into_owned());
+        }
+        Ok(parts.join("\t"))
     }
 }
@@ -1,17 +1,47 @@
-use super::{FromParts, ParsePacketError, Sockchatable};
+use super::{FieldError, FieldPolicy, FromParts, ParsePacketError, Sockchatable};
 
-#[derive(Debug)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct ContextClearingPacket {
     pub message_history: bool,
     pub user_list: bool,
     pub channel_list: bool,
 }
 
+impl ContextClearingPacket {
+    pub fn messages() -> Self {
+        Self::new(true, false, false)
+    }
+
+    pub fn users() -> Self {
+        Self::new(false, true, false)
+    }
+
+    pub fn channels() -> Self {
+        Self::new(false, false, true)
+    }
+
+    pub fn messages_and_users() -> Self {
+        Self::new(true, true, false)
+    }
+
+    pub fn all() -> Self {
+        Self::new(true, true, true)
+    }
+
+    fn new(message_history: bool, user_list: bool, channel_list: bool) -> Self {
+        ContextClearingPacket {
+            message_history,
+            user_list,
+            channel_list,
+        }
+    }
+}
+
 impl FromParts for ContextClearingPacket {
     fn from_parts(parts: Vec<String>) -> Result<Self, ParsePacketError> {
-        let num = parts[0].as_str();
+        let num = parts.first().map_or("", String::as_str);
         let message_history = ["0", "3", "4"].contains(&num);
-        let user_list = ["1", "3"].contains(&num);
+        let user_list = ["1", "3", "4"].contains(&num);
         let channel_list = ["2", "4"].contains(&num);
         if message_history || user_list || channel_list {
             Ok(ContextClearingPacket {
@@ -26,14 +56,40 @@ impl FromParts for ContextClearingPacket {
 }
 
 impl Sockchatable for ContextClearingPacket {
-    fn to_sockstr(&self) -> String {
-        match (self.message_history, self.user_list, self.channel_list) {
-            (true, false, false) => String::from("0"),
-            (false, true, false) => String::from("1"),
-            (false, false, true) => String::from("2"),
-            (true, true, false) => String::from("3"),
-            (true, true, true) => String::from("4"),
-            _ => String::from("i havent read the docs"),
+    fn to_sockstr_with(&self, _policy: FieldPolicy) -> Result<String, FieldError> {
+        let mode = match (self.message_history, self.user_list, self.channel_list) {
+            (true, false, false) => "0",
+            (false, true, false) => "1",
+            (false, false, true) => "2",
+            (true, true, false) => "3",
+            (true, true, true) => "4",
+            _ => "i havent read the docs",
+        };
+        Ok(mode.to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(num: &str) -> ContextClearingPacket {
+        ContextClearingPacket::from_parts(vec![num.to_string()]).unwrap()
+    }
+
+    #[test]
+    fn clearing_everything_includes_users() {
+        assert_eq!(parse("4"), ContextClearingPacket::all());
+        assert_eq!(parse("3"), ContextClearingPacket::messages_and_users());
+        for packet in [
+            ContextClearingPacket::messages(),
+            ContextClearingPacket::users(),
+            ContextClearingPacket::channels(),
+            ContextClearingPacket::messages_and_users(),
+            ContextClearingPacket::all(),
+        ] {
+            assert_eq!(parse(&packet.to_sockstr()), packet);
         }
+        assert!(ContextClearingPacket::from_parts(vec!["5".to_string()]).is_err());
     }
 }
@@ -1,9 +1,8 @@
 use crate::packets::types::*;
 
-#[derive(Debug)]
+#[derive(Debug, Clone, PartialEq)]
 pub enum ContextInformationPacket {
     ExistingUsers {
-        count: i64,
         contexts: Vec<UserContext>,
     },
     ExistingMessage {
@@ -18,21 +17,62 @@ pub enum ContextInformationPacket {
         message_flags: MessageFlags,
     },
     Channels {
-        count: i64,
         contexts: Vec<ChannelContext>,
     },
 }
 
+impl ContextInformationPacket {
+    pub fn users<I>(users: I) -> Self
+    where
+        I: IntoIterator<Item = UserContext>,
+    {
+        Self::ExistingUsers {
+            contexts: users.into_iter().collect(),
+        }
+    }
+
+    pub fn message(
+        timestamp: i64,
+        user: &UserContext,
+        message: &str,
+        sequence_id: &str,
+        notify: bool,
+        message_flags: MessageFlags,
+    ) -> Self {
+        Self::ExistingMessage {
+            timestamp,
+            user_id: user.user_id.clone(),
+            username: user.username.clone(),
+            color: user.color.clone(),
+            user_permissions: user.user_permissions.clone(),
+            message: message.to_string(),
+            sequence_id: sequence_id.to_string(),
+            notify,
+            message_flags,
+        }
+    }
+
+    pub fn channels<I>(channels: I) -> Self
+    where
+        I: IntoIterator<Item = ChannelContext>,
+    {
+        Self::Channels {
+            contexts: channels.into_iter().collect(),
+        }
+    }
+}
+
 impl FromParts for ContextInformationPacket {
     fn from_parts(parts: Vec<String>) -> Result<Self, ParsePacketError> {
         let mut iter = parts.into_iter();
-        match iter.next().unwrap().as_str() {
+        match iter.next().ok_or(ParsePacketError::WrongFormat)?.as_str() {
             "0" => {
                 let count = iter
                     .next()
                     .unwrap_or("default_count".to_string())
                     .parse::<i64>()
-                    .unwrap_or(0);
+                    .unwrap_or(0)
+                    .min(iter.len().div_ceil(5) as i64);
                 let mut contexts: Vec<UserContext> = Vec::new();
                 for _ in 0..count {
                     let user_id = iter.next().unwrap_or("default_user_id".to_string());
@@ -60,7 +100,7 @@ impl FromParts for ContextInformationPacket {
                         visible,
                     });
                 }
-                Ok(Self::ExistingUsers { count, contexts })
+                Ok(Self::ExistingUsers { contexts })
             }
 
             "1" => {
@@ -112,7 +152,8 @@ impl FromParts for ContextInformationPacket {
                     .next()
                     .unwrap_or("default_count".to_string())
                     .parse::<i64>()
-                    .unwrap_or(0);
+                    .unwrap_or(0)
+                    .min(iter.len().div_ceil(3) as i64);
                 let mut contexts: Vec<ChannelContext> = Vec::new();
                 for _ in 0..count {
                     let channel_name = iter.next().unwrap_or("default_channel_name".to_string());
@@ -132,7 +173,7 @@ impl FromParts for ContextInformationPacket {
                         temporary,
                     });
                 }
-                Ok(Self::Channels { count, contexts })
+                Ok(Self::Channels { contexts })
             }
 
             _ => Err(ParsePacketError::WrongFormat),
@@ -141,15 +182,15 @@ impl FromParts for ContextInformationPacket {
 }
 
 impl Sockchatable for ContextInformationPacket {
-    fn to_sockstr(&self) -> String {
-        match self {
-            Self::ExistingUsers { count, contexts } => {
+    fn to_sockstr_with(&self, policy: FieldPolicy) -> Result<String, FieldError> {
+        Ok(match self {
+            Self::ExistingUsers { contexts } => {
                 let mut output = String::new();
                 output.push_str("0\t");
-                output.push_str(count.to_string().as_str());
+                output.push_str(contexts.len().to_string().as_str());
                 for context in contexts {
-                    output.push_str("\t");
-                    output.push_str(context.to_sockstr().as_str());
+                    output.push('\t');
+                    output.push_str(context.to_sockstr_with(policy)?.as_str());
                 }
                 output
             }
@@ -164,30 +205,77 @@ impl Sockchatable for ContextInformationPacket {
                 sequence_id,
                 notify,
                 message_flags,
-            } => vec![
+            } => [
                 "1",
                 timestamp.to_string().as_str(),
-                user_id.as_str(),
-                username.as_str(),
-                color.to_sockstr().as_str(),
+                &*policy.field(user_id)?,
+                &*policy.field(username)?,
+                color.to_sockstr_with(policy)?.as_str(),
                 user_permissions.to_sockstr().as_str(),
-                message.as_str(),
-                sequence_id.as_str(),
+                &*policy.field(message)?,
+                &*policy.field(sequence_id)?,
                 notify.to_sockstr().as_str(),
                 message_flags.to_sockstr().as_str(),
             ]
             .join("\t"),
 
-            Self::Channels { count, contexts } => {
+            Self::Channels { contexts } => {
                 let mut output = String::new();
                 output.push_str("2\t");
-                output.push_str(count.to_string().as_str());
+                output.push_str(contexts.len().to_string().as_str());
                 for context in contexts {
-                    output.push_str("\t");
-                    output.push_str(context.to_sockstr().as_str());
+                    output.push('\t');
+                    output.push_str(context.to_sockstr_with(policy)?.as_str());
                 }
                 output
             }
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn derives_count_from_contexts() {
+        let packet = ContextInformationPacket::users(vec![
+            UserContext::default(),
+            UserContext {
+                user_id: "2".to_string(),
+                username: "kanii".to_string(),
+                ..Default::default()
+            },
+        ]);
+        let sockstr = packet.to_sockstr();
+        assert!(sockstr.starts_with("0\t2\tdefault_user_id\t"));
+
+        let parts = sockstr.split('\t').map(str::to_string).collect();
+        match ContextInformationPacket::from_parts(parts).unwrap() {
+            ContextInformationPacket::ExistingUsers { contexts } => {
+                assert_eq!(contexts.len(), 2);
+                assert_eq!(contexts[1].username, "kanii");
+            }
+            packet => panic!("unexpected packet {:?}", packet),
+        }
+
+        assert_eq!(
+            ContextInformationPacket::channels(Vec::new()).to_sockstr(),
+            "2\t0"
+        );
+    }
+
+    #[test]
+    fn caps_counts_by_the_fields_left() {
+        let parts = ["0", "100000000000", "1", "kanii"]
+            .map(str::to_string)
+            .to_vec();
+        match ContextInformationPacket::from_parts(parts).unwrap() {
+            ContextInformationPacket::ExistingUsers { contexts } => {
+                assert_eq!(contexts.len(), 1);
+                assert_eq!(contexts[0].username, "kanii");
+            }
+            packet => panic!("unexpected packet {:?}", packet),
         }
     }
 }
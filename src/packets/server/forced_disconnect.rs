@@ -1,12 +1,29 @@
 use super::FromParts;
 use crate::packets::types::*;
 
-#[derive(Debug)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct ForcedDisconnectPacket {
     pub ban: bool,
     pub timestamp: i64,
 }
 
+impl ForcedDisconnectPacket {
+    pub fn kick() -> Self {
+        ForcedDisconnectPacket {
+            ban: false,
+            timestamp: 0,
+        }
+    }
+
+    /// Bans until `expires`, a Unix timestamp.
+    pub fn ban(expires: i64) -> Self {
+        ForcedDisconnectPacket {
+            ban: true,
+            timestamp: expires,
+        }
+    }
+}
+
 impl FromParts for ForcedDisconnectPacket {
     fn from_parts(parts: Vec<String>) -> Result<Self, ParsePacketError> {
         let mut iter = parts.into_iter();
@@ -17,11 +34,11 @@ impl FromParts for ForcedDisconnectPacket {
 }
 
 impl Sockchatable for ForcedDisconnectPacket {
-    fn to_sockstr(&self) -> String {
-        vec![
+    fn to_sockstr_with(&self, _policy: FieldPolicy) -> Result<String, FieldError> {
+        Ok([
             self.ban.to_sockstr().as_str(),
             self.timestamp.to_string().as_str(),
         ]
-        .join("\t")
+        .join("\t"))
     }
 }
@@ -1,6 +1,6 @@
 use crate::packets::types::*;
 
-#[derive(Debug)]
+#[derive(Debug, Clone, PartialEq)]
 pub enum JoinAuthPacket {
     GoodAuth {
         user_id: String,
@@ -9,6 +9,9 @@ pub enum JoinAuthPacket {
         user_permissions: UserPermissions,
         channel_name: String,
         max_msg_length: i64,
+        /// Capabilities the server agreed to out of those the client
+        /// announced, sent space-separated after `max_msg_length`.
+        capabilities: Vec<String>,
     },
     BadAuth {
         reason: BadAuthReason,
@@ -24,10 +27,57 @@ pub enum JoinAuthPacket {
     },
 }
 
+impl JoinAuthPacket {
+    pub fn good_auth(user: &UserContext, channel_name: &str, max_msg_length: i64) -> Self {
+        Self::GoodAuth {
+            user_id: user.user_id.clone(),
+            username: user.username.clone(),
+            color: user.color.clone(),
+            user_permissions: user.user_permissions.clone(),
+            channel_name: channel_name.to_string(),
+            max_msg_length,
+            capabilities: Vec::new(),
+        }
+    }
+
+    /// Confirms `capabilities` in a `GoodAuth`; other packets are left as is.
+    pub fn with_capabilities(mut self, confirmed: &[&str]) -> Self {
+        if let Self::GoodAuth { capabilities, .. } = &mut self {
+            *capabilities = confirmed.iter().map(|c| c.to_string()).collect();
+        }
+        self
+    }
+
+    /// Whether the server confirmed `capability`.
+    pub fn supports(&self, capability: &str) -> bool {
+        match self {
+            Self::GoodAuth { capabilities, .. } => capabilities
+                .iter()
+                .any(|c| c.eq_ignore_ascii_case(capability)),
+            _ => false,
+        }
+    }
+
+    pub fn bad_auth(reason: BadAuthReason, timestamp: i64) -> Self {
+        Self::BadAuth { reason, timestamp }
+    }
+
+    pub fn join(timestamp: i64, user: &UserContext, sequence_id: &str) -> Self {
+        Self::Join {
+            timestamp,
+            user_id: user.user_id.clone(),
+            username: user.username.clone(),
+            color: user.color.clone(),
+            user_permissions: user.user_permissions.clone(),
+            sequence_id: sequence_id.to_string(),
+        }
+    }
+}
+
 impl FromParts for JoinAuthPacket {
     fn from_parts(parts: Vec<String>) -> Result<Self, ParsePacketError> {
         let mut iter = parts.into_iter();
-        match iter.next().unwrap().as_str() {
+        match iter.next().ok_or(ParsePacketError::WrongFormat)?.as_str() {
             "y" => {
                 let user_id = iter.next().unwrap_or("default_user_id".to_string());
                 let username = iter.next().unwrap_or("default_username".to_string());
@@ -35,6 +85,15 @@ impl FromParts for JoinAuthPacket {
                 let user_permissions = iter.next().unwrap_or("default_user_permissions".to_string()).parse::<UserPermissions>().unwrap_or_default();
                 let channel_name = iter.next().unwrap_or("default_channel_name".to_string());
                 let max_msg_length = iter.next().unwrap_or("default_max_msg_length".to_string()).parse::<i64>().unwrap_or(444);
+                let capabilities = iter
+                    .next()
+                    .map(|capabilities| {
+                        capabilities
+                            .split_whitespace()
+                            .map(str::to_string)
+                            .collect()
+                    })
+                    .unwrap_or_default();
                 Ok(Self::GoodAuth {
                     user_id,
                     username,
@@ -42,6 +101,7 @@ impl FromParts for JoinAuthPacket {
                     user_permissions,
                     channel_name,
                     max_msg_length,
+                    capabilities,
                 })
             }
 
@@ -51,8 +111,8 @@ impl FromParts for JoinAuthPacket {
                 Ok(Self::BadAuth { reason, timestamp })
             }
 
-            _ => {
-                let timestamp = iter.next().unwrap_or("default_timestamp".to_string()).parse::<i64>().unwrap_or(444);
+            timestamp => {
+                let timestamp = timestamp.parse::<i64>().unwrap_or(444);
                 let user_id = iter.next().unwrap_or("default_user_id".to_string());
                 let username = iter.next().unwrap_or("default_username".to_string());
                 let color = iter.next().unwrap_or("default_color".to_string()).parse::<Color>().unwrap_or_default();
@@ -72,8 +132,8 @@ impl FromParts for JoinAuthPacket {
 }
 
 impl Sockchatable for JoinAuthPacket {
-    fn to_sockstr(&self) -> String {
-        match self {
+    fn to_sockstr_with(&self, policy: FieldPolicy) -> Result<String, FieldError> {
+        Ok(match self {
             Self::GoodAuth {
                 user_id,
                 username,
@@ -81,18 +141,30 @@ impl Sockchatable for JoinAuthPacket {
                 user_permissions,
                 channel_name,
                 max_msg_length,
-            } => vec![
-                "y",
-                user_id.as_str(),
-                username.as_str(),
-                color.to_sockstr().as_str(),
-                user_permissions.to_sockstr().as_str(),
-                channel_name.as_str(),
-                max_msg_length.to_string().as_str(),
-            ]
-            .join("\t"),
+                capabilities,
+            } => {
+                let mut line = [
+                    "y",
+                    &*policy.field(user_id)?,
+                    &*policy.field(username)?,
+                    color.to_sockstr_with(policy)?.as_str(),
+                    user_permissions.to_sockstr().as_str(),
+                    &*policy.field(channel_name)?,
+                    max_msg_length.to_string().as_str(),
+                ]
+                .join("\t");
+                if !capabilities.is_empty() {
+                    let capabilities = capabilities
+                        .iter()
+                        .map(|capability| policy.field_with(capability, &[' ']))
+                        .collect::<Result<Vec<_>, _>>()?;
+                    line.push('\t');
+                    line.push_str(&capabilities.join(" "));
+                }
+                line
+            }
 
-            Self::BadAuth { reason, timestamp } => vec![
+            Self::BadAuth { reason, timestamp } => [
                 "n",
                 reason.to_sockstr().as_str(),
                 timestamp.to_string().as_str(),
@@ -106,15 +178,40 @@ impl Sockchatable for JoinAuthPacket {
                 color,
                 user_permissions,
                 sequence_id,
-            } => vec![
+            } => [
                 timestamp.to_string().as_str(),
-                user_id.as_str(),
-                username.as_str(),
-                color.to_sockstr().as_str(),
+                &*policy.field(user_id)?,
+                &*policy.field(username)?,
+                color.to_sockstr_with(policy)?.as_str(),
                 user_permissions.to_sockstr().as_str(),
-                sequence_id.as_str(),
+                &*policy.field(sequence_id)?,
             ]
             .join("\t"),
-        }
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_joins_from_their_timestamp() {
+        let user = UserContext {
+            user_id: "2".to_string(),
+            username: "flash".to_string(),
+            color: "#0f0".parse().unwrap(),
+            ..Default::default()
+        };
+        let packet = JoinAuthPacket::join(1700000000, &user, "s1");
+        let sockstr = packet.to_sockstr();
+        assert_eq!(sockstr, "1700000000\t2\tflash\t#0f0\t0 0 0 0 0\ts1");
+        let parts = sockstr.split('\t').map(str::to_string).collect();
+        assert_eq!(JoinAuthPacket::from_parts(parts).unwrap(), packet);
+
+        let good_auth = JoinAuthPacket::good_auth(&user, "lounge", 2000);
+        assert!(good_auth.to_sockstr().starts_with("y\t2\t"));
+        let bad_auth = JoinAuthPacket::bad_auth(BadAuthReason::AuthFail, 1700000000);
+        assert!(bad_auth.to_sockstr().starts_with("n\t"));
     }
 }
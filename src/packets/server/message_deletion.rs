@@ -1,10 +1,18 @@
-use super::{FromParts, Sockchatable};
+use super::{FieldError, FieldPolicy, FromParts, Sockchatable};
 
-#[derive(Debug)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct MessageDeletionPacket {
     pub sequence_id: String,
 }
 
+impl MessageDeletionPacket {
+    pub fn new(sequence_id: &str) -> Self {
+        MessageDeletionPacket {
+            sequence_id: sequence_id.to_string(),
+        }
+    }
+}
+
 impl FromParts for MessageDeletionPacket {
     fn from_parts(parts: Vec<String>) -> Result<Self, super::ParsePacketError> {
         let mut iter = parts.into_iter();
@@ -14,7 +22,7 @@ impl FromParts for MessageDeletionPacket {
 }
 
 impl Sockchatable for MessageDeletionPacket {
-    fn to_sockstr(&self) -> String {
-        self.sequence_id.clone()
+    fn to_sockstr_with(&self, policy: FieldPolicy) -> Result<String, FieldError> {
+        Ok(policy.field(&self.sequence_id)?.into_owned())
     }
 }
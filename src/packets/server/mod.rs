@@ -10,7 +10,10 @@ pub mod pong;
 pub mod user_disconnect;
 pub mod user_update;
 
-use crate::packets::types::*;
+use crate::packets::{
+    bot_message::{BotMessage, BOT_USER_ID},
+    types::*,
+};
 
 pub use channel_event::ChannelEventPacket;
 pub use channel_switching::ChannelSwitchingPacket;
@@ -26,7 +29,7 @@ pub use user_update::UserUpdatePacket;
 
 use std::str::FromStr;
 
-#[derive(Debug)]
+#[derive(Debug, Clone, PartialEq)]
 pub enum ServerPacket {
     Pong(PongPacket),
     JoinAuth(JoinAuthPacket),
@@ -41,6 +44,72 @@ pub enum ServerPacket {
     UserUpdate(UserUpdatePacket),
 }
 
+impl ServerPacket {
+    pub fn pong(text: &str) -> Self {
+        Self::Pong(PongPacket::new(text))
+    }
+
+    /// Builds a chat message stamped with the current time.
+    pub fn chat_message(
+        user_id: &str,
+        message: &str,
+        sequence_id: &str,
+        message_flags: MessageFlags,
+    ) -> Self {
+        Self::ChatMessage(ChatMessagePacket::new(
+            unix_timestamp(),
+            user_id,
+            message,
+            sequence_id,
+            message_flags,
+        ))
+    }
+
+    /// Wraps a bot message in a chat message from the server itself.
+    pub fn bot_message(bot_message: &BotMessage, sequence_id: &str) -> Self {
+        Self::chat_message(
+            BOT_USER_ID,
+            &bot_message.to_sockstr(),
+            sequence_id,
+            MessageFlags::default(),
+        )
+    }
+
+    pub fn message_deletion(sequence_id: &str) -> Self {
+        Self::MessageDeletion(MessageDeletionPacket::new(sequence_id))
+    }
+
+    pub fn user_update(user: &UserContext) -> Self {
+        Self::UserUpdate(UserUpdatePacket::new(user))
+    }
+}
+
+macro_rules! impl_from_packet {
+    ($($variant:ident($packet:ty)),* $(,)?) => {
+        $(
+            impl From<$packet> for ServerPacket {
+                fn from(packet: $packet) -> Self {
+                    Self::$variant(packet)
+                }
+            }
+        )*
+    };
+}
+
+impl_from_packet!(
+    Pong(PongPacket),
+    JoinAuth(JoinAuthPacket),
+    ChatMessage(ChatMessagePacket),
+    UserDisconnect(UserDisconnectPacket),
+    ChannelEvent(ChannelEventPacket),
+    ChannelSwitching(ChannelSwitchingPacket),
+    MessageDeletion(MessageDeletionPacket),
+    ContextInformation(ContextInformationPacket),
+    ContextClearing(ContextClearingPacket),
+    ForcedDisconnect(ForcedDisconnectPacket),
+    UserUpdate(UserUpdatePacket),
+);
+
 impl FromStr for ServerPacket {
     type Err = ParsePacketError;
 
@@ -52,56 +121,39 @@ impl FromStr for ServerPacket {
 
         let first_part = parts.remove(0);
         match first_part.as_str() {
-            "0" => Ok(ServerPacket::Pong(PongPacket::from_parts(parts).unwrap())),
-            "1" => Ok(ServerPacket::JoinAuth(
-                JoinAuthPacket::from_parts(parts).unwrap(),
-            )),
-            "2" => Ok(ServerPacket::ChatMessage(
-                ChatMessagePacket::from_parts(parts).unwrap(),
-            )),
-            "3" => Ok(ServerPacket::UserDisconnect(
-                UserDisconnectPacket::from_parts(parts).unwrap(),
-            )),
-            "4" => Ok(ServerPacket::ChannelEvent(
-                ChannelEventPacket::from_parts(parts).unwrap(),
-            )),
-            "5" => Ok(ServerPacket::ChannelSwitching(
-                ChannelSwitchingPacket::from_parts(parts).unwrap(),
-            )),
-            "6" => Ok(ServerPacket::MessageDeletion(
-                MessageDeletionPacket::from_parts(parts).unwrap(),
-            )),
-            "7" => Ok(ServerPacket::ContextInformation(
-                ContextInformationPacket::from_parts(parts).unwrap(),
-            )),
-            "8" => Ok(ServerPacket::ContextClearing(
-                ContextClearingPacket::from_parts(parts).unwrap(),
-            )),
-            "9" => Ok(ServerPacket::ForcedDisconnect(
-                ForcedDisconnectPacket::from_parts(parts).unwrap(),
-            )),
-            "10" => Ok(ServerPacket::UserUpdate(
-                UserUpdatePacket::from_parts(parts).unwrap(),
-            )),
+            "0" => PongPacket::from_parts(parts).map(ServerPacket::Pong),
+            "1" => JoinAuthPacket::from_parts(parts).map(ServerPacket::JoinAuth),
+            "2" => ChatMessagePacket::from_parts(parts).map(ServerPacket::ChatMessage),
+            "3" => UserDisconnectPacket::from_parts(parts).map(ServerPacket::UserDisconnect),
+            "4" => ChannelEventPacket::from_parts(parts).map(ServerPacket::ChannelEvent),
+            "5" => ChannelSwitchingPacket::from_parts(parts).map(ServerPacket::ChannelSwitching),
+            "6" => MessageDeletionPacket::from_parts(parts).map(ServerPacket::MessageDeletion),
+            "7" => {
+                ContextInformationPacket::from_parts(parts).map(ServerPacket::ContextInformation)
+            }
+            "8" => ContextClearingPacket::from_parts(parts).map(ServerPacket::ContextClearing),
+            "9" => ForcedDisconnectPacket::from_parts(parts).map(ServerPacket::ForcedDisconnect),
+            "10" => UserUpdatePacket::from_parts(parts).map(ServerPacket::UserUpdate),
             _ => Err(ParsePacketError::WrongFormat),
         }
     }
 }
 
 impl Sockchatable for ServerPacket {
-    fn to_sockstr(&self) -> String {
-        match self {
-            Self::Pong(packet) => vec!["0", packet.to_sockstr().as_str()].join("\t"),
-            Self::JoinAuth(packet) => vec!["1", packet.to_sockstr().as_str()].join("\t"),
-            Self::ChatMessage(packet) => vec!["2", packet.to_sockstr().as_str()].join("\t"),
-            Self::UserDisconnect(packet) => vec!["3", packet.to_sockstr().as_str()].join("\t"),
-            Self::ChannelEvent(packet) => vec!["4", packet.to_sockstr().as_str()].join("\t"),
-            Self::ChannelSwitching(packet) => vec!["5", packet.to_sockstr().as_str()].join("\t"),
-            Self::MessageDeletion(packet) => vec!["6", packet.to_sockstr().as_str()].join("\t"),
-            Self::ContextInformation(packet) => vec!["7", packet.to_sockstr().as_str()].join("\t"),
-            Self::ContextClearing(packet) => vec!["8", packet.to_sockstr().as_str()].join("\t"),
-            Self::ForcedDisconnect(packet) => vec!["9", packet.to_sockstr().as_str()].join("\t"),
-            Self::UserUpdate(packet) => vec!["10", packet.to_sockstr().as_str()].join("\t"),
-        }
+    fn to_sockstr_with(&self, policy: FieldPolicy) -> Result<String, FieldError> {
+        let (id, packet) = match self {
+            Self::Pong(packet) => ("0", packet.to_sockstr_with(policy)?),
+            Self::JoinAuth(packet) => ("1", packet.to_sockstr_with(policy)?),
+            Self::ChatMessage(packet) => ("2", packet.to_sockstr_with(policy)?),
+            Self::UserDisconnect(packet) => ("3", packet.to_sockstr_with(policy)?),
+            Self::ChannelEvent(packet) => ("4", packet.to_sockstr_with(policy)?),
+            Self::ChannelSwitching(packet) => ("5", packet.to_sockstr_with(policy)?),
+            Self::MessageDeletion(packet) => ("6", packet.to_sockstr_with(policy)?),
+            Self::ContextInformation(packet) => ("7", packet.to_sockstr_with(policy)?),
+            Self::ContextClearing(packet) => ("8", packet.to_sockstr_with(policy)?),
+            Self::ForcedDisconnect(packet) => ("9", packet.to_sockstr_with(policy)?),
+            Self::UserUpdate(packet) => ("10", packet.to_sockstr_with(policy)?),
+        };
+        Ok([id, packet.as_str()].join("\t"))
     }
 }
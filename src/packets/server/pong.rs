@@ -1,10 +1,18 @@
-use super::{FromParts, Sockchatable};
+use super::{FieldError, FieldPolicy, FromParts, Sockchatable};
 
-#[derive(Debug)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct PongPacket {
     pub text: String,
 }
 
+impl PongPacket {
+    pub fn new(text: &str) -> Self {
+        PongPacket {
+            text: text.to_string(),
+        }
+    }
+}
+
 impl FromParts for PongPacket {
     fn from_parts(parts: Vec<String>) -> Result<Self, super::ParsePacketError> {
         let mut iter = parts.into_iter();
@@ -14,7 +22,7 @@ impl FromParts for PongPacket {
 }
 
 impl Sockchatable for PongPacket {
-    fn to_sockstr(&self) -> String {
-        self.text.clone()
+    fn to_sockstr_with(&self, policy: FieldPolicy) -> Result<String, FieldError> {
+        Ok(policy.field(&self.text)?.into_owned())
     }
 }
@@ -1,6 +1,6 @@
 use crate::packets::types::*;
 
-#[derive(Debug)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct UserDisconnectPacket {
     pub user_id: String,
     pub username: String,
@@ -9,6 +9,23 @@ pub struct UserDisconnectPacket {
     pub sequence_id: String,
 }
 
+impl UserDisconnectPacket {
+    pub fn new(
+        user: &UserContext,
+        reason: DisconnectReason,
+        timestamp: i64,
+        sequence_id: &str,
+    ) -> Self {
+        UserDisconnectPacket {
+            user_id: user.user_id.clone(),
+            username: user.username.clone(),
+            reason,
+            timestamp,
+            sequence_id: sequence_id.to_string(),
+        }
+    }
+}
+
 impl FromParts for UserDisconnectPacket {
     fn from_parts(parts: Vec<String>) -> Result<Self, ParsePacketError> {
         let mut iter = parts.into_iter();
@@ -28,14 +45,14 @@ impl FromParts for UserDisconnectPacket {
 }
 
 impl Sockchatable for UserDisconnectPacket {
-    fn to_sockstr(&self) -> String {
-        vec![
-            self.user_id.as_str(),
-            self.username.as_str(),
+    fn to_sockstr_with(&self, policy: FieldPolicy) -> Result<String, FieldError> {
+        Ok([
+            &*policy.field(&self.user_id)?,
+            &*policy.field(&self.username)?,
             self.reason.to_sockstr().as_str(),
             self.timestamp.to_string().as_str(),
-            self.sequence_id.as_str(),
+            &*policy.field(&self.sequence_id)?,
         ]
-        .join("\t")
+        .join("\t"))
     }
 }
@@ -1,6 +1,6 @@
 use crate::packets::types::*;
 
-#[derive(Debug)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct UserUpdatePacket {
     pub user_id: String,
     pub username: String,
@@ -8,6 +8,17 @@ pub struct UserUpdatePacket {
     pub user_permissions: UserPermissions,
 }
 
+impl UserUpdatePacket {
+    pub fn new(user: &UserContext) -> Self {
+        UserUpdatePacket {
+            user_id: user.user_id.clone(),
+            username: user.username.clone(),
+            color: user.color.clone(),
+            user_permissions: user.user_permissions.clone(),
+        }
+    }
+}
+
 impl FromParts for UserUpdatePacket {
     fn from_parts(parts: Vec<String>) -> Result<Self, ParsePacketError> {
         let mut iter = parts.into_iter();
@@ -25,13 +36,13 @@ impl FromParts for UserUpdatePacket {
 }
 
 impl Sockchatable for UserUpdatePacket {
-    fn to_sockstr(&self) -> String {
-        vec![
-            self.user_id.as_str(),
-            self.username.as_str(),
-            self.color.to_sockstr().as_str(),
+    fn to_sockstr_with(&self, policy: FieldPolicy) -> Result<String, FieldError> {
+        Ok([
+            &*policy.field(&self.user_id)?,
+            &*policy.field(&self.username)?,
+            self.color.to_sockstr_with(policy)?.as_str(),
             self.user_permissions.to_sockstr().as_str(),
         ]
-        .join("\t")
+        .join("\t"))
     }
 }
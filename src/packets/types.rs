@@ -1,14 +1,17 @@
 use csscolorparser::{Color as CssColor, ParseColorError};
-use std::str::FromStr;
+use std::{borrow::Cow, str::FromStr};
 
-#[derive(Debug)]
+/// Character separating the fields of a packet.
+pub const FIELD_SEPARATOR: char = '\t';
+
+#[derive(Debug, Clone, PartialEq)]
 pub enum ParsePacketError {
     WrongFormat,
     Empty,
     FieldParsingFail,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, PartialEq)]
 pub enum BadAuthReason {
     AuthFail,
     UserFail,
@@ -49,23 +52,24 @@ impl FromStr for BadAuthReason {
 }
 
 impl Sockchatable for BadAuthReason {
-    fn to_sockstr(&self) -> String {
-        match self {
+    fn to_sockstr_with(&self, _policy: FieldPolicy) -> Result<String, FieldError> {
+        Ok(match self {
             Self::AuthFail => "authfail".to_string(),
             Self::JoinFail => "joinfail".to_string(),
             Self::SockFail => "sockfail".to_string(),
             Self::UserFail => "userfail".to_string(),
-        }
+        })
     }
 }
 
+#[allow(clippy::derivable_impls)]
 impl Default for BadAuthReason {
     fn default() -> Self {
         BadAuthReason::SockFail
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct UserPermissions {
     pub rank: u8,
     pub can_moderate: bool,
@@ -74,6 +78,7 @@ pub struct UserPermissions {
     pub channel_permissions: u8,
 }
 
+#[allow(clippy::manual_pattern_char_comparison)]
 impl FromStr for UserPermissions {
     type Err = ParsePacketError;
     fn from_str(s: &str) -> Result<Self, Self::Err> {
@@ -107,18 +112,19 @@ impl FromStr for UserPermissions {
 }
 
 impl Sockchatable for UserPermissions {
-    fn to_sockstr(&self) -> String {
-        vec![
+    fn to_sockstr_with(&self, _policy: FieldPolicy) -> Result<String, FieldError> {
+        Ok([
             self.rank.to_string().as_str(),
             self.can_moderate.to_sockstr().as_str(),
             self.can_logs.to_sockstr().as_str(),
             self.can_nickname.to_sockstr().as_str(),
             self.channel_permissions.to_string().as_str(),
         ]
-        .join(" ")
+        .join(" "))
     }
 }
 
+#[allow(clippy::derivable_impls)]
 impl Default for UserPermissions {
     fn default() -> Self {
         UserPermissions {
@@ -131,7 +137,7 @@ impl Default for UserPermissions {
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct MessageFlags {
     pub bold: bool,
     pub cursive: bool,
@@ -143,6 +149,9 @@ pub struct MessageFlags {
 impl FromStr for MessageFlags {
     type Err = ParsePacketError;
     fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s.chars().count() < 5 {
+            return Err(ParsePacketError::FieldParsingFail);
+        }
         let mut input = s.to_string();
         Ok(MessageFlags {
             bold: input
@@ -175,7 +184,7 @@ impl FromStr for MessageFlags {
 }
 
 impl Sockchatable for MessageFlags {
-    fn to_sockstr(&self) -> String {
+    fn to_sockstr_with(&self, _policy: FieldPolicy) -> Result<String, FieldError> {
         let mut output = String::new();
         for flag in [
             self.bold,
@@ -184,16 +193,13 @@ impl Sockchatable for MessageFlags {
             self.colon,
             self.private,
         ] {
-            if flag {
-                output.push_str("1");
-            } else {
-                output.push_str("0");
-            }
+            output.push(if flag { '1' } else { '0' });
         }
-        output
+        Ok(output)
     }
 }
 
+#[allow(clippy::derivable_impls)]
 impl Default for MessageFlags {
     fn default() -> Self {
         MessageFlags {
@@ -206,7 +212,7 @@ impl Default for MessageFlags {
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, PartialEq)]
 pub enum DisconnectReason {
     Leave,
     Timeout,
@@ -228,23 +234,24 @@ impl FromStr for DisconnectReason {
 }
 
 impl Sockchatable for DisconnectReason {
-    fn to_sockstr(&self) -> String {
-        match self {
+    fn to_sockstr_with(&self, _policy: FieldPolicy) -> Result<String, FieldError> {
+        Ok(match self {
             Self::Leave => "leave".to_string(),
             Self::Kick => "kick".to_string(),
             Self::Flood => "flood".to_string(),
             Self::Timeout => "timeout".to_string(),
-        }
+        })
     }
 }
 
+#[allow(clippy::derivable_impls)]
 impl Default for DisconnectReason {
     fn default() -> Self {
         DisconnectReason::Leave
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct UserContext {
     pub user_id: String,
     pub username: String,
@@ -254,15 +261,15 @@ pub struct UserContext {
 }
 
 impl Sockchatable for UserContext {
-    fn to_sockstr(&self) -> String {
-        vec![
-            self.user_id.as_str(),
-            self.username.as_str(),
-            self.color.to_sockstr().as_str(),
+    fn to_sockstr_with(&self, policy: FieldPolicy) -> Result<String, FieldError> {
+        Ok([
+            &*policy.field(&self.user_id)?,
+            &*policy.field(&self.username)?,
+            self.color.to_sockstr_with(policy)?.as_str(),
             self.user_permissions.to_sockstr().as_str(),
             self.visible.to_sockstr().as_str(),
         ]
-        .join("\t")
+        .join("\t"))
     }
 }
 
@@ -278,7 +285,7 @@ impl Default for UserContext {
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct Color {
     pub value: String,
 }
@@ -408,8 +415,8 @@ impl FromStr for Color {
 }
 
 impl Sockchatable for Color {
-    fn to_sockstr(&self) -> String {
-        self.value.clone()
+    fn to_sockstr_with(&self, policy: FieldPolicy) -> Result<String, FieldError> {
+        Ok(policy.field(&self.value)?.into_owned())
     }
 }
 
@@ -421,7 +428,7 @@ impl Default for Color {
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct ChannelContext {
     pub channel_name: String,
     pub password_protected: bool,
@@ -429,13 +436,13 @@ pub struct ChannelContext {
 }
 
 impl Sockchatable for ChannelContext {
-    fn to_sockstr(&self) -> String {
-        vec![
-            self.channel_name.as_str(),
+    fn to_sockstr_with(&self, policy: FieldPolicy) -> Result<String, FieldError> {
+        Ok([
+            &*policy.field(&self.channel_name)?,
             self.password_protected.to_sockstr().as_str(),
             self.temporary.to_sockstr().as_str(),
         ]
-        .join("\t")
+        .join("\t"))
     }
 }
 
@@ -464,11 +471,11 @@ impl ParseSockBool for String {
 }
 
 impl Sockchatable for bool {
-    fn to_sockstr(&self) -> String {
-        match self {
+    fn to_sockstr_with(&self, _policy: FieldPolicy) -> Result<String, FieldError> {
+        Ok(match self {
             false => "0".to_string(),
             true => "1".to_string(),
-        }
+        })
     }
 }
 
@@ -478,6 +485,71 @@ pub trait FromParts {
         Self: Sized;
 }
 
+/// What to do about separators found inside field values while serializing.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum FieldPolicy {
+    /// Replace them with spaces.
+    #[default]
+    Sanitize,
+    /// Refuse to serialize the packet.
+    Reject,
+}
+
+impl FieldPolicy {
+    /// Prepares `value` to be serialized as a single field.
+    pub fn field(self, value: &str) -> Result<Cow<'_, str>, FieldError> {
+        self.field_with(value, &[])
+    }
+
+    /// Like `field`, for values that also must not contain the given inner
+    /// separators.
+    pub fn field_with<'a>(
+        self,
+        value: &'a str,
+        separators: &[char],
+    ) -> Result<Cow<'a, str>, FieldError> {
+        match self {
+            Self::Reject if value.contains(FIELD_SEPARATOR) || value.contains(separators) => {
+                Err(FieldError::ContainsSeparator(value.to_string()))
+            }
+            Self::Reject => Ok(Cow::Borrowed(value)),
+            Self::Sanitize => Ok(replace_separators(value, separators)),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum FieldError {
+    /// A field value contained a separator; holds the offending value.
+    ContainsSeparator(String),
+}
+
+fn replace_separators<'a>(value: &'a str, separators: &[char]) -> Cow<'a, str> {
+    if !value.contains(FIELD_SEPARATOR) && !value.contains(separators) {
+        return Cow::Borrowed(value);
+    }
+    Cow::Owned(value.replace(FIELD_SEPARATOR, " ").replace(separators, " "))
+}
+
+/// Serialization to the Sockchat wire format.
+///
+/// Types without free-form values implement `to_sockstr`; the others
+/// implement `to_sockstr_with` and run every such value through
+/// `FieldPolicy::field`.
 pub trait Sockchatable {
-    fn to_sockstr(&self) -> String;
+    fn to_sockstr(&self) -> String {
+        self.to_sockstr_with(FieldPolicy::Sanitize)
+            .expect("sanitizing never fails")
+    }
+
+    /// Serializes according to `policy`, failing under `FieldPolicy::Reject`
+    /// if a field value contains a separator.
+    fn to_sockstr_with(&self, policy: FieldPolicy) -> Result<String, FieldError>;
+}
+
+/// Current time in seconds since the Unix epoch, as carried by packet timestamps.
+pub fn unix_timestamp() -> i64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map_or(0, |duration| duration.as_secs() as i64)
 }
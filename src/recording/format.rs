@@ -0,0 +1,280 @@
+use std::{
+    io::{self, BufRead, Write},
+    str::FromStr,
+};
+
+use crate::{
+    clock::{Clock, SystemClock},
+    packets::{client::ClientPacket, server::ServerPacket, types::ParsePacketError, Packet},
+};
+
+/// First line of every recording.
+pub const HEADER: &str = "# sockchat recording v1";
+
+/// Written instead of the authkey of recorded authentication packets.
+pub const REDACTED: &str = "<redacted>";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Direction {
+    /// Sent by the client to the server.
+    Client,
+    /// Sent by the server to the client.
+    Server,
+}
+
+impl Direction {
+    fn as_str(&self) -> &'static str {
+        match self {
+            Self::Client => "c",
+            Self::Server => "s",
+        }
+    }
+}
+
+impl FromStr for Direction {
+    type Err = ParsePacketError;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "c" => Ok(Self::Client),
+            "s" => Ok(Self::Server),
+            _ => Err(ParsePacketError::WrongFormat),
+        }
+    }
+}
+
+/// One raw websocket frame as it went over the wire.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Frame {
+    /// Milliseconds since the Unix epoch.
+    pub timestamp: i64,
+    pub direction: Direction,
+    pub raw: String,
+}
+
+impl Frame {
+    pub fn new(timestamp: i64, direction: Direction, raw: &str) -> Self {
+        Frame {
+            timestamp,
+            direction,
+            raw: raw.to_string(),
+        }
+    }
+
+    /// Parses the frame as whatever its direction says it is.
+    pub fn packet(&self) -> Result<Packet, ParsePacketError> {
+        match self.direction {
+            Direction::Client => self.raw.parse::<ClientPacket>().map(Packet::Client),
+            Direction::Server => self.raw.parse::<ServerPacket>().map(Packet::Server),
+        }
+    }
+
+    /// The frame with the authkey of an authentication packet replaced by
+    /// `REDACTED`; any other frame is returned as is.
+    pub fn redacted(&self) -> Self {
+        let authentication = matches!(
+            self.packet(),
+            Ok(Packet::Client(ClientPacket::Authentication(_)))
+        );
+        let mut fields = self.raw.splitn(4, '\t').collect::<Vec<_>>();
+        if authentication && fields.len() > 2 {
+            fields[2] = REDACTED;
+        }
+        Frame {
+            raw: fields.join("\t"),
+            ..self.clone()
+        }
+    }
+
+    /// The frame as a single recording line, without the line break.
+    ///
+    /// Backslashes and line breaks in the raw frame are escaped; tabs are
+    /// kept since only the first two separate the line's own fields.
+    pub fn to_line(&self) -> String {
+        let mut raw = String::with_capacity(self.raw.len());
+        for c in self.raw.chars() {
+            match c {
+                '\\' => raw.push_str("\\\\"),
+                '\n' => raw.push_str("\\n"),
+                '\r' => raw.push_str("\\r"),
+                c => raw.push(c),
+            }
+        }
+        format!("{}\t{}\t{}", self.timestamp, self.direction.as_str(), raw)
+    }
+
+    pub fn from_line(line: &str) -> Option<Self> {
+        let mut parts = line.splitn(3, '\t');
+        let timestamp = parts.next()?.parse::<i64>().ok()?;
+        let direction = parts.next()?.parse::<Direction>().ok()?;
+        let mut raw = String::new();
+        let mut chars = parts.next()?.chars();
+        while let Some(c) = chars.next() {
+            if c != '\\' {
+                raw.push(c);
+                continue;
+            }
+            match chars.next()? {
+                '\\' => raw.push('\\'),
+                'n' => raw.push('\n'),
+                'r' => raw.push('\r'),
+                _ => return None,
+            }
+        }
+        Some(Frame {
+            timestamp,
+            direction,
+            raw,
+        })
+    }
+}
+
+#[derive(Debug)]
+pub enum RecordingError {
+    Io(io::Error),
+    /// The 1-based line number that could not be read as a frame.
+    Malformed(usize),
+}
+
+impl From<io::Error> for RecordingError {
+    fn from(error: io::Error) -> Self {
+        RecordingError::Io(error)
+    }
+}
+
+/// Appends frames to a recording, one per line.
+pub struct RecordingWriter<W: Write> {
+    writer: W,
+    clock: Box<dyn Clock>,
+}
+
+impl<W: Write> RecordingWriter<W> {
+    pub fn new(mut writer: W) -> io::Result<Self> {
+        writeln!(writer, "{}", HEADER)?;
+        Ok(RecordingWriter {
+            writer,
+            clock: Box::new(SystemClock),
+        })
+    }
+
+    pub fn with_clock<C: Clock + 'static>(mut self, clock: C) -> Self {
+        self.clock = Box::new(clock);
+        self
+    }
+
+    /// Records `raw` as sent or received right now, with authkeys redacted
+    /// so that recordings can be attached to bug reports.
+    pub fn record(&mut self, direction: Direction, raw: &str) -> io::Result<()> {
+        let frame = Frame::new(self.clock.now_millis(), direction, raw);
+        self.write_frame(&frame.redacted())
+    }
+
+    pub fn write_frame(&mut self, frame: &Frame) -> io::Result<()> {
+        writeln!(self.writer, "{}", frame.to_line())?;
+        self.writer.flush()
+    }
+
+    pub fn into_inner(self) -> W {
+        self.writer
+    }
+}
+
+/// Reads the frames of a recording back.
+///
+/// Blank lines and lines starting with `#` are skipped, so recordings can be
+/// annotated by hand when attached to bug reports.
+pub struct RecordingReader<R: BufRead> {
+    lines: io::Lines<R>,
+    line: usize,
+}
+
+impl<R: BufRead> RecordingReader<R> {
+    pub fn new(reader: R) -> Self {
+        RecordingReader {
+            lines: reader.lines(),
+            line: 0,
+        }
+    }
+}
+
+impl<R: BufRead> Iterator for RecordingReader<R> {
+    type Item = Result<Frame, RecordingError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let line = match self.lines.next()? {
+                Ok(line) => line,
+                Err(error) => return Some(Err(error.into())),
+            };
+            self.line += 1;
+            if line.trim().is_empty() || line.starts_with('#') {
+                continue;
+            }
+            return Some(Frame::from_line(&line).ok_or(RecordingError::Malformed(self.line)));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::clock::ManualClock;
+
+    #[test]
+    fn round_trips_frames() {
+        let frames = vec![
+            Frame::new(1_000, Direction::Client, "1\tmisuzu\tkey"),
+            Frame::new(
+                1_250,
+                Direction::Server,
+                "2\t1\t2\tback\\slash\nline\ts1\t10010",
+            ),
+        ];
+        let mut writer = RecordingWriter::new(Vec::new()).unwrap();
+        for frame in &frames {
+            writer.write_frame(frame).unwrap();
+        }
+        let mut recording = writer.into_inner();
+        recording.extend_from_slice(b"\n# annotated\n");
+
+        let read = RecordingReader::new(recording.as_slice())
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        assert_eq!(read, frames);
+        assert!(matches!(read[0].packet(), Ok(Packet::Client(_))));
+
+        let broken = format!("{}\n1000\tx\t0\n", HEADER);
+        assert!(matches!(
+            RecordingReader::new(broken.as_bytes()).next(),
+            Some(Err(RecordingError::Malformed(2)))
+        ));
+    }
+    #[test]
+    fn redacts_authkeys() {
+        let clock = ManualClock::new(1_000);
+        let mut writer = RecordingWriter::new(Vec::new())
+            .unwrap()
+            .with_clock(clock.clone());
+        writer
+            .record(Direction::Client, "1\tMisuzu\tsecret\tMCHAN")
+            .unwrap();
+        clock.advance(250);
+        writer.record(Direction::Client, "2\t1\tsecret").unwrap();
+        writer.record(Direction::Server, "0\t1\tsecret").unwrap();
+
+        let recording = writer.into_inner();
+        let read = RecordingReader::new(recording.as_slice())
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        assert_eq!(
+            read,
+            vec![
+                Frame::new(1_000, Direction::Client, "1\tMisuzu\t<redacted>\tMCHAN"),
+                Frame::new(1_250, Direction::Client, "2\t1\tsecret"),
+                Frame::new(1_250, Direction::Server, "0\t1\tsecret"),
+            ]
+        );
+        assert!(!String::from_utf8(recording)
+            .unwrap()
+            .contains("\tsecret\tMCHAN"));
+    }
+}
@@ -0,0 +1,5 @@
+pub mod format;
+pub mod replay;
+
+pub use format::{Direction, Frame, RecordingError, RecordingReader, RecordingWriter};
+pub use replay::{ReplayConsumer, Replayer};
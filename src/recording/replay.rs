@@ -0,0 +1,146 @@
+use std::{io::BufRead, thread, time::Duration};
+
+use crate::{
+    client::state::ChatState,
+    packets::{client::ClientPacket, server::ServerPacket, types::ParsePacketError, Packet},
+};
+
+use super::format::{Frame, RecordingError, RecordingReader};
+
+/// Receives the packets of a replayed recording.
+pub trait ReplayConsumer {
+    fn server(&mut self, packet: &ServerPacket);
+
+    fn client(&mut self, _packet: &ClientPacket) {}
+
+    /// Frames that no longer parse, ignored unless overridden.
+    fn malformed(&mut self, _frame: &Frame, _error: &ParsePacketError) {}
+}
+
+impl ReplayConsumer for ChatState {
+    fn server(&mut self, packet: &ServerPacket) {
+        self.handle(packet);
+    }
+}
+
+/// Plays recorded frames back, in order, into a `ReplayConsumer`.
+///
+/// Gaps between frames are slept through, divided by the speed factor;
+/// `instant` drops them altogether, which is what tests want.
+#[derive(Debug, Clone)]
+pub struct Replayer {
+    frames: Vec<Frame>,
+    speed: Option<f64>,
+}
+
+impl Replayer {
+    pub fn new(frames: Vec<Frame>) -> Self {
+        Replayer {
+            frames,
+            speed: Some(1.0),
+        }
+    }
+
+    pub fn from_reader<R: BufRead>(reader: R) -> Result<Self, RecordingError> {
+        Ok(Self::new(
+            RecordingReader::new(reader).collect::<Result<_, _>>()?,
+        ))
+    }
+
+    /// Plays `speed` times faster than recorded; non-positive speeds mean
+    /// `instant`.
+    pub fn at_speed(mut self, speed: f64) -> Self {
+        self.speed = (speed > 0.0).then_some(speed);
+        self
+    }
+
+    pub fn instant(mut self) -> Self {
+        self.speed = None;
+        self
+    }
+
+    pub fn frames(&self) -> &[Frame] {
+        &self.frames
+    }
+
+    /// Replays every frame, returning how many parsed.
+    pub fn run<C: ReplayConsumer>(&self, consumer: &mut C) -> usize {
+        let mut parsed = 0;
+        let mut previous: Option<i64> = None;
+        for frame in &self.frames {
+            if let (Some(speed), Some(previous)) = (self.speed, previous) {
+                let gap = (frame.timestamp - previous).max(0) as f64 / speed;
+                thread::sleep(Duration::from_secs_f64(gap / 1000.0));
+            }
+            previous = Some(frame.timestamp);
+            match frame.packet() {
+                Ok(Packet::Server(packet)) => consumer.server(&packet),
+                Ok(Packet::Client(packet)) => consumer.client(&packet),
+                Err(error) => {
+                    consumer.malformed(frame, &error);
+                    continue;
+                }
+            }
+            parsed += 1;
+        }
+        parsed
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Instant;
+
+    use super::*;
+    use crate::recording::format::HEADER;
+
+    #[derive(Default)]
+    struct Counter {
+        server: usize,
+        client: usize,
+        malformed: usize,
+    }
+
+    impl ReplayConsumer for Counter {
+        fn server(&mut self, _packet: &ServerPacket) {
+            self.server += 1;
+        }
+
+        fn client(&mut self, _packet: &ClientPacket) {
+            self.client += 1;
+        }
+
+        fn malformed(&mut self, _frame: &Frame, _error: &ParsePacketError) {
+            self.malformed += 1;
+        }
+    }
+
+    const RECORDING: &str = "\
+1000\tc\t1\tmisuzu\tkey
+1100\ts\t1\ty\t1\tkanii\t#f00\t0 0 0 0 0\tlounge\t2000
+1150\ts\t1\t1700000000\t2\tflash\t#0f0\t0 0 0 0 0\ts1
+1200\ts\tx
+1210\ts\t5
+1220\ts\t4\t9
+";
+
+    #[test]
+    fn replays_into_consumers() {
+        let replayer =
+            Replayer::from_reader(format!("{}\n{}", HEADER, RECORDING).as_bytes()).unwrap();
+
+        let mut counter = Counter::default();
+        assert_eq!(replayer.clone().instant().run(&mut counter), 3);
+        assert_eq!(
+            (counter.client, counter.server, counter.malformed),
+            (1, 2, 3)
+        );
+
+        let mut state = ChatState::new();
+        let started = Instant::now();
+        replayer.at_speed(10.0).run(&mut state);
+        assert!(started.elapsed() >= Duration::from_millis(20));
+        assert_eq!(state.channel_name(), Some("lounge"));
+        assert_eq!(state.user("2").unwrap().username, "flash");
+    }
+}
@@ -0,0 +1,294 @@
+use std::str::FromStr;
+
+use crate::{packets::inspect::FieldIssue, recording::Direction};
+
+/// One field of a scripted packet.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Field {
+    Exact(String),
+    /// `*`, matching any single field.
+    Any,
+    /// `...` at the end of a packet, matching whatever fields are left.
+    Rest,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum StepKind {
+    Packet {
+        direction: Direction,
+        fields: Vec<Field>,
+    },
+    /// A `!` line, handed to the subject as is.
+    Action(String),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Step {
+    /// 1-based line number in the script.
+    pub line: usize,
+    /// Name before the arrow, empty when left out.
+    pub connection: String,
+    pub kind: StepKind,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ScriptError {
+    Syntax {
+        line: usize,
+        message: &'static str,
+    },
+    /// A packet the script sends contains a wildcard.
+    Wildcard {
+        line: usize,
+    },
+    /// A packet the script sends does not decode.
+    InvalidPacket {
+        line: usize,
+        issue: FieldIssue,
+    },
+    /// The subject does not know what to do with an action.
+    UnsupportedAction {
+        line: usize,
+        action: String,
+    },
+    Mismatch(super::Mismatch),
+}
+
+/// An expected exchange of packets, one per line.
+///
+/// ```text
+/// # a comment
+/// > 1 Misuzu user1
+/// < 1 y 1 user1 * "0 0 0 0 0" lounge *
+/// bob> 2 2 "hello there"
+/// bob< 2 * 2 "hello there" * ...
+/// ! /w flash hi
+/// ```
+///
+/// `>` lines are client packets and `<` lines server packets, no matter
+/// which side is under test. Fields are separated by spaces; double quotes
+/// keep spaces in a field and understand `\t`, `\n`, `\f`, `\"` and `\\`.
+/// An optional name before the arrow picks the connection.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Script {
+    steps: Vec<Step>,
+}
+
+impl Script {
+    pub fn steps(&self) -> &[Step] {
+        &self.steps
+    }
+}
+
+impl FromStr for Script {
+    type Err = ScriptError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut steps = Vec::new();
+        for (index, text) in s.lines().enumerate() {
+            let line = index + 1;
+            let text = text.trim();
+            if text.is_empty() || text.starts_with('#') {
+                continue;
+            }
+            let (head, rest) = text.split_once(char::is_whitespace).unwrap_or((text, ""));
+            let Some(marker) = head.chars().last().filter(|c| ['>', '<', '!'].contains(c)) else {
+                return Err(ScriptError::Syntax {
+                    line,
+                    message: "lines start with >, < or !",
+                });
+            };
+            let connection = &head[..head.len() - 1];
+            if !connection
+                .chars()
+                .all(|c| c.is_alphanumeric() || c == '_' || c == '-')
+            {
+                return Err(ScriptError::Syntax {
+                    line,
+                    message: "connection names are alphanumeric",
+                });
+            }
+            let kind = match marker {
+                '!' => StepKind::Action(rest.trim().to_string()),
+                _ => {
+                    let fields =
+                        tokenize(rest).map_err(|message| ScriptError::Syntax { line, message })?;
+                    if fields.is_empty() {
+                        return Err(ScriptError::Syntax {
+                            line,
+                            message: "empty packet",
+                        });
+                    }
+                    StepKind::Packet {
+                        direction: match marker {
+                            '>' => Direction::Client,
+                            _ => Direction::Server,
+                        },
+                        fields,
+                    }
+                }
+            };
+            steps.push(Step {
+                line,
+                connection: connection.to_string(),
+                kind,
+            });
+        }
+        Ok(Script { steps })
+    }
+}
+
+fn tokenize(text: &str) -> Result<Vec<Field>, &'static str> {
+    let mut fields = Vec::new();
+    let mut chars = text.chars().peekable();
+    loop {
+        while chars.next_if(|c| c.is_whitespace()).is_some() {}
+        let Some(first) = chars.next() else {
+            break;
+        };
+        if first != '"' {
+            let mut word = first.to_string();
+            while let Some(c) = chars.next_if(|c| !c.is_whitespace()) {
+                word.push(c);
+            }
+            fields.push(match word.as_str() {
+                "*" => Field::Any,
+                "..." => Field::Rest,
+                _ => Field::Exact(word),
+            });
+            continue;
+        }
+
+        let mut value = String::new();
+        loop {
+            match chars.next() {
+                None => return Err("unterminated quote"),
+                Some('"') => break,
+                Some('\\') => value.push(match chars.next() {
+                    Some('t') => '\t',
+                    Some('n') => '\n',
+                    Some('f') => '\u{c}',
+                    Some(c @ ('"' | '\\')) => c,
+                    _ => return Err("unknown escape"),
+                }),
+                Some(c) => value.push(c),
+            }
+        }
+        if chars.peek().is_some_and(|c| !c.is_whitespace()) {
+            return Err("quoted fields end at a space");
+        }
+        fields.push(Field::Exact(value));
+    }
+    if fields[..fields.len().saturating_sub(1)].contains(&Field::Rest) {
+        return Err("... only goes last");
+    }
+    Ok(fields)
+}
+
+/// Writes one field the way a script would, quoting it when needed.
+fn render_field(value: &str) -> String {
+    let plain = !value.is_empty()
+        && value != "*"
+        && value != "..."
+        && !value
+            .chars()
+            .any(|c| c.is_whitespace() || c.is_control() || c == '"' || c == '\\');
+    if plain {
+        return value.to_string();
+    }
+    let mut quoted = String::from('"');
+    for c in value.chars() {
+        match c {
+            '\t' => quoted.push_str("\\t"),
+            '\n' => quoted.push_str("\\n"),
+            '\u{c}' => quoted.push_str("\\f"),
+            '"' | '\\' => {
+                quoted.push('\\');
+                quoted.push(c);
+            }
+            _ => quoted.push(c),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
+
+/// Turns a raw packet into script notation, e.g. `2 1 "hello there"`.
+pub fn render(raw: &str) -> String {
+    raw.split('\t')
+        .map(render_field)
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+pub(crate) fn render_pattern(fields: &[Field]) -> String {
+    fields
+        .iter()
+        .map(|field| match field {
+            Field::Exact(value) => render_field(value),
+            Field::Any => "*".to_string(),
+            Field::Rest => "...".to_string(),
+        })
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_steps() {
+        let script = "
+            # login
+            > 1 Misuzu \"key with spaces\"
+            bob< 2 * -1 \"0\\fjoin\\fmisaka\" ...
+            ! /w flash hi
+        "
+        .parse::<Script>()
+        .unwrap();
+        assert_eq!(script.steps().len(), 3);
+        assert_eq!(
+            script.steps()[0].kind,
+            StepKind::Packet {
+                direction: Direction::Client,
+                fields: vec![
+                    Field::Exact("1".to_string()),
+                    Field::Exact("Misuzu".to_string()),
+                    Field::Exact("key with spaces".to_string()),
+                ],
+            }
+        );
+        assert_eq!(script.steps()[1].line, 4);
+        assert_eq!(script.steps()[1].connection, "bob");
+        assert!(matches!(
+            &script.steps()[1].kind,
+            StepKind::Packet { fields, .. }
+                if fields[1] == Field::Any
+                    && fields[3] == Field::Exact("0\u{c}join\u{c}misaka".to_string())
+                    && fields[4] == Field::Rest
+        ));
+        assert_eq!(
+            script.steps()[2].kind,
+            StepKind::Action("/w flash hi".to_string())
+        );
+
+        assert!(matches!(
+            "> 1 \"open".parse::<Script>(),
+            Err(ScriptError::Syntax { line: 1, .. })
+        ));
+        assert!(matches!(
+            "1 Misuzu key".parse::<Script>(),
+            Err(ScriptError::Syntax { line: 1, .. })
+        ));
+    }
+
+    #[test]
+    fn renders_back_to_script() {
+        let raw = "2\t100\t-1\t0\u{c}say\u{c}hi there\t*\t";
+        assert_eq!(render(raw), "2 100 -1 \"0\\fsay\\fhi there\" \"*\" \"\"");
+        let fields = tokenize(&render(raw)).unwrap();
+        assert_eq!(fields.len(), 6);
+        assert_eq!(fields[4], Field::Exact("*".to_string()));
+    }
+}
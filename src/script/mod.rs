@@ -0,0 +1,5 @@
+pub mod format;
+pub mod runner;
+
+pub use format::{render, Field, Script, ScriptError, Step, StepKind};
+pub use runner::{assert_script, run, EngineSubject, Mismatch, SessionSubject, Subject};
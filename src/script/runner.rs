@@ -0,0 +1,464 @@
+use std::{collections::BTreeMap, fmt, time::Instant};
+
+use crate::{
+    client::{
+        view::{Action, ChatView},
+        Session,
+    },
+    packets::{
+        inspect::{inspect, FieldIssue},
+        server::ServerPacket,
+        types::{DisconnectReason, Sockchatable},
+        Packet,
+    },
+    recording::Direction,
+    server::{AuthProvider, ConnectionId, ServerEngine},
+};
+
+use super::format::{render, render_pattern, Field, Script, ScriptError, StepKind};
+
+/// Something a script can talk to, either side of the protocol.
+///
+/// Packets coming out of a subject are tagged with the name of the
+/// connection they go to, as written before the arrows in the script.
+pub trait Subject {
+    /// Direction of the packets the script feeds in; lines going the other
+    /// way are what the subject is expected to send.
+    fn input(&self) -> Direction;
+
+    /// Packets sent before the script starts.
+    fn start(&mut self) -> Vec<(String, Packet)> {
+        Vec::new()
+    }
+
+    fn feed(&mut self, connection: &str, packet: &Packet) -> Vec<(String, Packet)>;
+
+    /// Runs a `!` line, `None` if the subject does not understand it.
+    fn act(&mut self, _connection: &str, _action: &str) -> Option<Vec<(String, Packet)>> {
+        None
+    }
+}
+
+/// Drives a `ServerEngine`, opening a connection for every name the script
+/// uses. The only action is `disconnect`.
+pub struct EngineSubject<A> {
+    engine: ServerEngine<A>,
+    connections: BTreeMap<String, ConnectionId>,
+}
+
+impl<A: AuthProvider> EngineSubject<A> {
+    pub fn new(engine: ServerEngine<A>) -> Self {
+        EngineSubject {
+            engine,
+            connections: BTreeMap::new(),
+        }
+    }
+
+    pub fn engine(&self) -> &ServerEngine<A> {
+        &self.engine
+    }
+
+    pub fn engine_mut(&mut self) -> &mut ServerEngine<A> {
+        &mut self.engine
+    }
+
+    pub fn into_inner(self) -> ServerEngine<A> {
+        self.engine
+    }
+
+    fn connection(&mut self, name: &str) -> ConnectionId {
+        if let Some(connection) = self.connections.get(name) {
+            return *connection;
+        }
+        let connection = self.engine.connect();
+        self.connections.insert(name.to_string(), connection);
+        connection
+    }
+
+    fn name(&self, connection: ConnectionId) -> String {
+        self.connections
+            .iter()
+            .find(|(_, other)| **other == connection)
+            .map(|(name, _)| name.clone())
+            .unwrap_or_else(|| format!("#{}", connection))
+    }
+
+    fn tag(&self, out: Vec<(ConnectionId, ServerPacket)>) -> Vec<(String, Packet)> {
+        out.into_iter()
+            .map(|(connection, packet)| (self.name(connection), Packet::Server(packet)))
+            .collect()
+    }
+}
+
+impl<A: AuthProvider> Subject for EngineSubject<A> {
+    fn input(&self) -> Direction {
+        Direction::Client
+    }
+
+    fn feed(&mut self, connection: &str, packet: &Packet) -> Vec<(String, Packet)> {
+        let Packet::Client(packet) = packet else {
+            return Vec::new();
+        };
+        let connection = self.connection(connection);
+        let out = self.engine.handle(connection, packet);
+        self.tag(out)
+    }
+
+    fn act(&mut self, connection: &str, action: &str) -> Option<Vec<(String, Packet)>> {
+        match action {
+            "disconnect" => {
+                let id = self.connections.remove(connection)?;
+                let out = self.engine.disconnect(id, DisconnectReason::Leave);
+                Some(self.tag(out))
+            }
+            _ => None,
+        }
+    }
+}
+
+/// Drives a client `Session` along with a `ChatView`.
+///
+/// The session authenticates as the script starts. Actions are typed into
+/// the view's input line and submitted, so `! hello` sends a message and
+/// `! /reconnect` authenticates again. Connection names are ignored.
+#[derive(Debug)]
+pub struct SessionSubject {
+    session: Session,
+    view: ChatView,
+}
+
+impl SessionSubject {
+    pub fn new(session: Session) -> Self {
+        SessionSubject {
+            session,
+            view: ChatView::new(),
+        }
+    }
+
+    pub fn session(&self) -> &Session {
+        &self.session
+    }
+
+    pub fn view(&self) -> &ChatView {
+        &self.view
+    }
+}
+
+impl Subject for SessionSubject {
+    fn input(&self) -> Direction {
+        Direction::Server
+    }
+
+    fn start(&mut self) -> Vec<(String, Packet)> {
+        vec![(String::new(), Packet::Client(self.session.authenticate()))]
+    }
+
+    fn feed(&mut self, _connection: &str, packet: &Packet) -> Vec<(String, Packet)> {
+        if let Packet::Server(packet) = packet {
+            self.session.handle(packet, Instant::now());
+            self.view.handle(packet);
+        }
+        Vec::new()
+    }
+
+    fn act(&mut self, _connection: &str, action: &str) -> Option<Vec<(String, Packet)>> {
+        for c in action.chars() {
+            self.view.input.insert(c);
+        }
+        Some(match self.view.submit(&self.session) {
+            Action::Send(packets) => packets
+                .into_iter()
+                .map(|packet| (String::new(), Packet::Client(packet)))
+                .collect(),
+            Action::Reconnect => {
+                self.view.reset();
+                self.start()
+            }
+            Action::None | Action::Quit => Vec::new(),
+        })
+    }
+}
+
+/// A packet the subject sent that the script did not expect, or the other
+/// way around. Its `Display` output is a field by field diff.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Mismatch {
+    /// Script line of the expectation, or of the last step for packets
+    /// left over at the end.
+    pub line: usize,
+    pub connection: String,
+    pub direction: Direction,
+    pub expected: Option<Vec<Field>>,
+    pub actual: Option<String>,
+}
+
+impl Mismatch {
+    /// Names every field that differs, as `(index, name, expected, actual)`.
+    pub fn differences(&self) -> Vec<(usize, String, Option<String>, Option<String>)> {
+        let (Some(expected), Some(actual)) = (&self.expected, &self.actual) else {
+            return Vec::new();
+        };
+        let inspection = inspect(self.direction, actual);
+        let offset = 1 + inspection.sub_id.is_some() as usize;
+        let name = |index: usize| match index {
+            0 => "id".to_string(),
+            1 if offset == 2 => "sub_id".to_string(),
+            _ => inspection
+                .fields
+                .get(index - offset)
+                .map_or_else(|| format!("field_{}", index), |field| field.name.clone()),
+        };
+
+        let actual = actual.split('\t').collect::<Vec<_>>();
+        let mut differences = Vec::new();
+        for index in 0..expected.len().max(actual.len()) {
+            let value = actual.get(index).map(|value| value.to_string());
+            match expected.get(index) {
+                Some(Field::Rest) => break,
+                Some(Field::Any) if value.is_some() => {}
+                Some(Field::Exact(exact)) if value.as_ref() == Some(exact) => {}
+                Some(Field::Exact(exact)) => {
+                    differences.push((index, name(index), Some(exact.clone()), value))
+                }
+                Some(Field::Any) => {
+                    differences.push((index, name(index), Some("*".to_string()), None))
+                }
+                None => differences.push((index, name(index), None, value)),
+            }
+        }
+        differences
+    }
+}
+
+impl fmt::Display for Mismatch {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let to = match self.connection.is_empty() {
+            true => String::new(),
+            false => format!(" for {}", self.connection),
+        };
+        let arrow = match self.direction {
+            Direction::Client => ">",
+            Direction::Server => "<",
+        };
+        match (&self.expected, &self.actual) {
+            (Some(_), Some(_)) => writeln!(f, "line {}: packet{} does not match", self.line, to)?,
+            (Some(_), None) => writeln!(
+                f,
+                "line {}: expected a packet{}, got nothing",
+                self.line, to
+            )?,
+            _ => writeln!(f, "after line {}: unexpected packet{}", self.line, to)?,
+        }
+        if let Some(expected) = &self.expected {
+            writeln!(f, "  expected: {} {}", arrow, render_pattern(expected))?;
+        }
+        if let Some(actual) = &self.actual {
+            writeln!(f, "  actual:   {} {}", arrow, render(actual))?;
+        }
+        for (index, name, expected, actual) in self.differences() {
+            let show = |value: Option<String>| {
+                value.map_or("nothing".to_string(), |value| format!("{:?}", value))
+            };
+            writeln!(
+                f,
+                "  field {} ({}): expected {}, got {}",
+                index,
+                name,
+                show(expected),
+                show(actual)
+            )?;
+        }
+        Ok(())
+    }
+}
+
+fn matches(expected: &[Field], actual: &str) -> bool {
+    let actual = actual.split('\t').collect::<Vec<_>>();
+    for (index, field) in expected.iter().enumerate() {
+        match (field, actual.get(index)) {
+            (Field::Rest, _) => return true,
+            (Field::Any, Some(_)) => {}
+            (Field::Exact(exact), Some(value)) if exact == value => {}
+            _ => return false,
+        }
+    }
+    expected.len() == actual.len()
+}
+
+/// Plays `script` against `subject`.
+///
+/// Every expectation takes the oldest unchecked packet sent to its
+/// connection, and the run fails on the first one that does not match or
+/// on packets nobody expected once the script is over.
+pub fn run<S: Subject>(script: &Script, subject: &mut S) -> Result<(), ScriptError> {
+    let input = subject.input();
+    let output = match input {
+        Direction::Client => Direction::Server,
+        Direction::Server => Direction::Client,
+    };
+    let mut pending = subject.start();
+    let mut last_line = 0;
+    for step in script.steps() {
+        last_line = step.line;
+        match &step.kind {
+            StepKind::Action(action) => {
+                let out = subject.act(&step.connection, action).ok_or_else(|| {
+                    ScriptError::UnsupportedAction {
+                        line: step.line,
+                        action: action.clone(),
+                    }
+                })?;
+                pending.extend(out);
+            }
+            StepKind::Packet { direction, fields } if *direction == input => {
+                let mut raw = Vec::new();
+                for field in fields {
+                    match field {
+                        Field::Exact(value) => raw.push(value.as_str()),
+                        _ => return Err(ScriptError::Wildcard { line: step.line }),
+                    }
+                }
+                let inspection = inspect(input, &raw.join("\t"));
+                let Some(packet) = inspection.packet else {
+                    return Err(ScriptError::InvalidPacket {
+                        line: step.line,
+                        issue: inspection.issue.unwrap_or(FieldIssue::Unknown),
+                    });
+                };
+                pending.extend(subject.feed(&step.connection, &packet));
+            }
+            StepKind::Packet { fields, .. } => {
+                let actual = pending
+                    .iter()
+                    .position(|(connection, _)| *connection == step.connection)
+                    .map(|index| pending.remove(index).1.to_sockstr());
+                if !actual
+                    .as_ref()
+                    .is_some_and(|actual| matches(fields, actual))
+                {
+                    return Err(ScriptError::Mismatch(Mismatch {
+                        line: step.line,
+                        connection: step.connection.clone(),
+                        direction: output,
+                        expected: Some(fields.clone()),
+                        actual,
+                    }));
+                }
+            }
+        }
+    }
+    match pending.into_iter().next() {
+        Some((connection, packet)) => Err(ScriptError::Mismatch(Mismatch {
+            line: last_line,
+            connection,
+            direction: output,
+            expected: None,
+            actual: Some(packet.to_sockstr()),
+        })),
+        None => Ok(()),
+    }
+}
+
+/// Parses and runs `script`, panicking with a readable diff on failure.
+#[track_caller]
+pub fn assert_script<S: Subject>(subject: &mut S, script: &str) {
+    let result = script
+        .parse::<Script>()
+        .and_then(|script| run(&script, subject));
+    match result {
+        Ok(()) => {}
+        Err(ScriptError::Mismatch(mismatch)) => panic!("script failed, {}", mismatch),
+        Err(error) => panic!("bad script: {:?}", error),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::packets::types::BadAuthReason;
+    use crate::server::{auth::UserProfile, ServerConfig};
+
+    struct NameAuth;
+
+    impl AuthProvider for NameAuth {
+        fn authenticate(
+            &mut self,
+            _method: &str,
+            authkey: &str,
+        ) -> Result<UserProfile, BadAuthReason> {
+            match authkey {
+                "alice" => Ok(UserProfile::new("1", "alice")),
+                "bob" => Ok(UserProfile::new("2", "bob")),
+                _ => Err(BadAuthReason::AuthFail),
+            }
+        }
+    }
+
+    #[test]
+    fn drives_an_engine() {
+        let mut subject = EngineSubject::new(ServerEngine::new(NameAuth, ServerConfig::default()));
+        assert_script(
+            &mut subject,
+            "
+            alice> 1 Misuzu alice
+            alice< 1 y 1 alice ...
+            alice< 7 0 0
+            alice< 7 2 1 lounge 0 0
+            bob> 1 Misuzu bob
+            alice< 1 * 2 bob ...
+            bob< 1 y 2 bob ...
+            bob< 7 0 1 1 alice ...
+            bob< 7 2 1 lounge 0 0
+            bob> 2 2 \"hi there\"
+            alice< 2 * 2 \"hi there\" * *
+            bob< 2 * 2 \"hi there\" * *
+            alice> 0 1
+            alice< 0 pong
+            ",
+        );
+        assert_eq!(subject.engine().users().count(), 2);
+    }
+
+    #[test]
+    fn drives_a_session() {
+        let mut subject = SessionSubject::new(Session::new("Misuzu", "secret"));
+        assert_script(
+            &mut subject,
+            "
+            > 1 Misuzu secret
+            < 1 y 1 kanii #f00 \"0 0 0 0 0\" lounge 2000
+            ! hello
+            > 2 1 hello
+            ! /w flash hi
+            > 2 1 \"/msg flash hi\"
+            ",
+        );
+        assert!(subject.session().is_connected());
+    }
+
+    #[test]
+    fn reports_field_differences() {
+        let mut subject = EngineSubject::new(ServerEngine::new(NameAuth, ServerConfig::default()));
+        let script = "
+            > 1 Misuzu nobody
+            < 1 n authfail
+        "
+        .parse::<Script>()
+        .unwrap();
+        let Err(ScriptError::Mismatch(mismatch)) = run(&script, &mut subject) else {
+            panic!("expected a mismatch");
+        };
+        assert_eq!(mismatch.line, 3);
+        let differences = mismatch.differences();
+        assert_eq!(differences.len(), 1);
+        assert_eq!(differences[0].0, 3);
+        assert_eq!(differences[0].1, "timestamp");
+        assert!(mismatch.to_string().contains("expected nothing, got"));
+
+        let script = "> 1 Misuzu nobody".parse::<Script>().unwrap();
+        let Err(ScriptError::Mismatch(mismatch)) = run(&script, &mut subject) else {
+            panic!("expected a mismatch");
+        };
+        assert!(mismatch.expected.is_none() && mismatch.actual.is_some());
+    }
+}
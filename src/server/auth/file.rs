@@ -0,0 +1,204 @@
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+use serde::Deserialize;
+
+use crate::packets::types::{BadAuthReason, Color, UserPermissions};
+
+use super::{AuthProvider, StaticAuth, UserProfile};
+
+#[derive(Debug)]
+pub enum AuthFileError {
+    Io(io::Error),
+    Parse(String),
+    UnknownFormat,
+    /// The table was parsed from a string, so there is no file to reload.
+    NoFile,
+}
+
+#[derive(Deserialize)]
+struct AuthFile {
+    #[serde(default)]
+    users: Vec<AuthEntry>,
+}
+
+#[derive(Deserialize)]
+struct AuthEntry {
+    method: Option<String>,
+    authkey: String,
+    user_id: String,
+    username: String,
+    color: Option<String>,
+    #[serde(default)]
+    rank: u8,
+    #[serde(default)]
+    can_moderate: bool,
+    #[serde(default)]
+    can_logs: bool,
+    #[serde(default)]
+    can_nickname: bool,
+    #[serde(default)]
+    channel_permissions: u8,
+}
+
+impl From<AuthFile> for StaticAuth {
+    fn from(file: AuthFile) -> Self {
+        let mut auth = StaticAuth::new();
+        for entry in file.users {
+            let profile = UserProfile {
+                user_id: entry.user_id,
+                username: entry.username,
+                color: entry
+                    .color
+                    .map_or_else(Color::default, |value| Color { value }),
+                user_permissions: UserPermissions {
+                    rank: entry.rank,
+                    can_moderate: entry.can_moderate,
+                    can_logs: entry.can_logs,
+                    can_nickname: entry.can_nickname,
+                    channel_permissions: entry.channel_permissions,
+                },
+            };
+            auth.insert(entry.method.as_deref(), &entry.authkey, profile);
+        }
+        auth
+    }
+}
+
+/// Authkey table read from a TOML or JSON file.
+///
+/// The file lists `users`, each with an `authkey`, `user_id` and `username`,
+/// plus optional `method`, `color` and permission fields:
+///
+/// ```toml
+/// [[users]]
+/// authkey = "secret"
+/// user_id = "1"
+/// username = "flash"
+/// color = "#f00"
+/// rank = 5
+/// can_moderate = true
+/// ```
+#[derive(Debug)]
+pub struct FileAuth {
+    path: Option<PathBuf>,
+    table: StaticAuth,
+}
+
+impl FileAuth {
+    /// Loads `path`, picking the format from its `.toml` or `.json` extension.
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self, AuthFileError> {
+        let path = path.as_ref().to_path_buf();
+        let table = Self::read(&path)?;
+        Ok(FileAuth {
+            path: Some(path),
+            table,
+        })
+    }
+
+    /// Re-reads the file, keeping the previous table if that fails.
+    pub fn reload(&mut self) -> Result<(), AuthFileError> {
+        let path = self.path.as_ref().ok_or(AuthFileError::NoFile)?;
+        self.table = Self::read(path)?;
+        Ok(())
+    }
+
+    pub fn table(&self) -> &StaticAuth {
+        &self.table
+    }
+
+    #[cfg(feature = "toml")]
+    pub fn parse_toml(s: &str) -> Result<Self, AuthFileError> {
+        Self::toml_table(s).map(Self::unsaved)
+    }
+
+    #[cfg(feature = "json")]
+    pub fn parse_json(s: &str) -> Result<Self, AuthFileError> {
+        Self::json_table(s).map(Self::unsaved)
+    }
+
+    fn unsaved(table: StaticAuth) -> Self {
+        FileAuth { path: None, table }
+    }
+
+    #[cfg(feature = "toml")]
+    fn toml_table(s: &str) -> Result<StaticAuth, AuthFileError> {
+        toml::from_str::<AuthFile>(s)
+            .map(StaticAuth::from)
+            .map_err(|error| AuthFileError::Parse(error.to_string()))
+    }
+
+    #[cfg(feature = "json")]
+    fn json_table(s: &str) -> Result<StaticAuth, AuthFileError> {
+        serde_json::from_str::<AuthFile>(s)
+            .map(StaticAuth::from)
+            .map_err(|error| AuthFileError::Parse(error.to_string()))
+    }
+
+    fn read(path: &Path) -> Result<StaticAuth, AuthFileError> {
+        let contents = fs::read_to_string(path).map_err(AuthFileError::Io)?;
+        match path.extension().and_then(|extension| extension.to_str()) {
+            #[cfg(feature = "toml")]
+            Some("toml") => Self::toml_table(&contents),
+            #[cfg(feature = "json")]
+            Some("json") => Self::json_table(&contents),
+            _ => Err(AuthFileError::UnknownFormat),
+        }
+    }
+}
+
+impl AuthProvider for FileAuth {
+    fn authenticate(&mut self, method: &str, authkey: &str) -> Result<UserProfile, BadAuthReason> {
+        self.table.authenticate(method, authkey)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[cfg(feature = "toml")]
+    #[test]
+    fn parses_toml() {
+        let mut auth = FileAuth::parse_toml(
+            r##"
+            [[users]]
+            method = "Misuzu"
+            authkey = "secret"
+            user_id = "1"
+            username = "flash"
+            color = "#f00"
+            rank = 5
+            can_moderate = true
+            "##,
+        )
+        .unwrap();
+        let profile = auth.authenticate("Misuzu", "secret").unwrap();
+        assert_eq!(profile.color.value, "#f00");
+        assert_eq!(profile.user_permissions.rank, 5);
+        assert!(profile.user_permissions.can_moderate);
+        assert!(auth.authenticate("Other", "secret").is_err());
+        assert!(!format!("{:?}", auth).contains("secret"));
+        assert!(matches!(auth.reload(), Err(AuthFileError::NoFile)));
+    }
+
+    #[cfg(feature = "json")]
+    #[test]
+    fn loads_json_file() {
+        let path = std::env::temp_dir().join(format!("kanii-auth-{}.json", std::process::id()));
+        fs::write(
+            &path,
+            r#"{"users": [{"authkey": "key", "user_id": "2", "username": "kanii"}]}"#,
+        )
+        .unwrap();
+        let mut auth = FileAuth::load(&path).unwrap();
+        assert_eq!(auth.authenticate("", "key").unwrap().username, "kanii");
+
+        fs::write(&path, r#"{"users": []}"#).unwrap();
+        auth.reload().unwrap();
+        assert!(auth.table().is_empty());
+        fs::remove_file(&path).unwrap();
+    }
+}
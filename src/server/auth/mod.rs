@@ -0,0 +1,207 @@
+#[cfg(any(feature = "json", feature = "toml"))]
+pub mod file;
+
+use std::{collections::HashMap, fmt};
+
+use crate::packets::types::{BadAuthReason, Color, UserContext, UserPermissions};
+
+#[cfg(any(feature = "json", feature = "toml"))]
+pub use file::{AuthFileError, FileAuth};
+
+/// Who an authenticated connection turned out to be.
+#[derive(Debug, Clone, PartialEq)]
+pub struct UserProfile {
+    pub user_id: String,
+    pub username: String,
+    pub color: Color,
+    pub user_permissions: UserPermissions,
+}
+
+impl UserProfile {
+    pub fn new(user_id: &str, username: &str) -> Self {
+        UserProfile {
+            user_id: user_id.to_string(),
+            username: username.to_string(),
+            color: Color::default(),
+            user_permissions: UserPermissions::default(),
+        }
+    }
+
+    pub fn to_context(&self) -> UserContext {
+        UserContext {
+            user_id: self.user_id.clone(),
+            username: self.username.clone(),
+            color: self.color.clone(),
+            user_permissions: self.user_permissions.clone(),
+            visible: true,
+        }
+    }
+}
+
+/// Validates the `method` and `authkey` of an `AuthenticationPacket`.
+pub trait AuthProvider {
+    fn authenticate(&mut self, method: &str, authkey: &str) -> Result<UserProfile, BadAuthReason>;
+}
+
+#[derive(Debug, Clone)]
+struct StaticEntry {
+    method: Option<String>,
+    profile: UserProfile,
+}
+
+/// Fixed in-memory table of authkeys.
+#[derive(Clone, Default)]
+pub struct StaticAuth {
+    entries: HashMap<String, StaticEntry>,
+}
+
+impl fmt::Debug for StaticAuth {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut user_ids = self
+            .entries
+            .values()
+            .map(|entry| entry.profile.user_id.as_str())
+            .collect::<Vec<_>>();
+        user_ids.sort_unstable();
+        f.debug_struct("StaticAuth")
+            .field("user_ids", &user_ids)
+            .finish_non_exhaustive()
+    }
+}
+
+impl StaticAuth {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Accepts `authkey` with any method.
+    pub fn with_user(mut self, authkey: &str, profile: UserProfile) -> Self {
+        self.insert(None, authkey, profile);
+        self
+    }
+
+    /// Accepts `authkey` only when it comes with `method`.
+    pub fn insert(&mut self, method: Option<&str>, authkey: &str, profile: UserProfile) {
+        self.entries.insert(
+            authkey.to_string(),
+            StaticEntry {
+                method: method.map(str::to_string),
+                profile,
+            },
+        );
+    }
+
+    pub fn remove(&mut self, authkey: &str) -> Option<UserProfile> {
+        self.entries.remove(authkey).map(|entry| entry.profile)
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+}
+
+impl AuthProvider for StaticAuth {
+    fn authenticate(&mut self, method: &str, authkey: &str) -> Result<UserProfile, BadAuthReason> {
+        let entry = self.entries.get(authkey).ok_or(BadAuthReason::AuthFail)?;
+        match &entry.method {
+            Some(expected) if expected != method => Err(BadAuthReason::AuthFail),
+            _ => Ok(entry.profile.clone()),
+        }
+    }
+}
+
+/// Accepts every authkey, naming the user after it.
+///
+/// Meant for local test fixtures; each distinct authkey gets its own user id,
+/// handed out in order starting at 1.
+#[derive(Clone, Default)]
+pub struct MockAuth {
+    user_permissions: UserPermissions,
+    user_ids: HashMap<String, String>,
+}
+
+impl fmt::Debug for MockAuth {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("MockAuth")
+            .field("user_permissions", &self.user_permissions)
+            .field("users", &self.user_ids.len())
+            .finish_non_exhaustive()
+    }
+}
+
+impl MockAuth {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_permissions(mut self, user_permissions: UserPermissions) -> Self {
+        self.user_permissions = user_permissions;
+        self
+    }
+}
+
+impl AuthProvider for MockAuth {
+    fn authenticate(&mut self, _method: &str, authkey: &str) -> Result<UserProfile, BadAuthReason> {
+        let next_id = (self.user_ids.len() + 1).to_string();
+        let user_id = self.user_ids.entry(authkey.to_string()).or_insert(next_id);
+        Ok(UserProfile {
+            user_permissions: self.user_permissions.clone(),
+            ..UserProfile::new(user_id, authkey)
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::packets::{client::AuthenticationPacket, types::Sockchatable};
+
+    #[test]
+    fn static_table_checks_method_and_key() {
+        let mut auth = StaticAuth::new().with_user("secret", UserProfile::new("1", "flash"));
+        auth.insert(Some("Misuzu"), "token", UserProfile::new("2", "kanii"));
+
+        assert_eq!(
+            auth.authenticate("Any", "secret").unwrap().username,
+            "flash"
+        );
+        assert_eq!(auth.authenticate("Misuzu", "token").unwrap().user_id, "2");
+        assert_eq!(
+            auth.authenticate("Other", "token"),
+            Err(BadAuthReason::AuthFail)
+        );
+        assert_eq!(
+            auth.authenticate("Any", "wrong"),
+            Err(BadAuthReason::AuthFail)
+        );
+    }
+
+    #[test]
+    fn mock_accepts_everyone() {
+        let mut auth = MockAuth::new();
+        assert_eq!(auth.authenticate("", "alice").unwrap().user_id, "1");
+        assert_eq!(auth.authenticate("", "bob").unwrap().user_id, "2");
+        assert_eq!(auth.authenticate("", "alice").unwrap().user_id, "1");
+    }
+
+    #[test]
+    fn redacts_authkey_in_debug_output() {
+        let packet = AuthenticationPacket::new("Misuzu", "hunter2");
+        let debug = format!("{:?}", packet);
+        assert!(debug.contains("Misuzu"));
+        assert!(!debug.contains("hunter2"));
+        assert_eq!(packet.to_sockstr(), "Misuzu\thunter2");
+
+        let mut mock = MockAuth::new();
+        mock.authenticate("", "hunter2").unwrap();
+        let table = StaticAuth::new().with_user("hunter2", UserProfile::new("7", "flash"));
+        for debug in [format!("{:?}", mock), format!("{:?}", table)] {
+            assert!(!debug.contains("hunter2"), "{}", debug);
+        }
+        assert!(format!("{:?}", table).contains("\"7\""));
+    }
+}
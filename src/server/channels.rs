@@ -0,0 +1,357 @@
+use crate::packets::{
+    bot_message::BotMessage,
+    server::ChannelEventPacket,
+    types::{ChannelContext, UserContext},
+};
+
+/// Lowest `UserPermissions::channel_permissions` allowed to create temporary channels.
+pub const CREATE_TEMPORARY: u8 = 1;
+/// Lowest `UserPermissions::channel_permissions` allowed to create permanent channels.
+pub const CREATE_PERMANENT: u8 = 2;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ChannelError {
+    NotFound(String),
+    AlreadyExists(String),
+    InvalidName(String),
+    NotPermitted(String),
+    WrongPassword(String),
+    SameChannel(String),
+}
+
+impl ChannelError {
+    /// Error bot message to show the user who caused it.
+    pub fn bot_message(&self) -> BotMessage {
+        let (id, channel_name) = match self {
+            Self::NotFound(channel_name) => ("nochan", channel_name),
+            Self::AlreadyExists(channel_name) => ("nischan", channel_name),
+            Self::InvalidName(channel_name) => ("inchan", channel_name),
+            Self::NotPermitted(channel_name) => ("ipchan", channel_name),
+            Self::WrongPassword(channel_name) => ("ipwchan", channel_name),
+            Self::SameChannel(channel_name) => ("samechan", channel_name),
+        };
+        BotMessage::error(id, &[channel_name])
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Channel {
+    pub context: ChannelContext,
+    /// Creator of the channel, `None` for channels set up by the server.
+    pub owner: Option<String>,
+    password: Option<String>,
+}
+
+impl Channel {
+    pub fn name(&self) -> &str {
+        &self.context.channel_name
+    }
+
+    fn can_manage(&self, user: &UserContext) -> bool {
+        user.user_permissions.can_moderate || self.owner.as_ref() == Some(&user.user_id)
+    }
+}
+
+/// Channel list honouring passwords, temporary channels and creation rights.
+///
+/// Every successful change hands back the `ChannelEventPacket` to broadcast
+/// to all connected users.
+#[derive(Debug, Clone)]
+pub struct ChannelManager {
+    channels: Vec<Channel>,
+    default_channel: String,
+}
+
+impl ChannelManager {
+    /// Starts out with server-owned `channels`; `default_channel` can never be deleted.
+    pub fn new<I>(channels: I, default_channel: &str) -> Self
+    where
+        I: IntoIterator<Item = ChannelContext>,
+    {
+        ChannelManager {
+            channels: channels
+                .into_iter()
+                .map(|context| Channel {
+                    context,
+                    owner: None,
+                    password: None,
+                })
+                .collect(),
+            default_channel: default_channel.to_string(),
+        }
+    }
+
+    pub fn default_channel(&self) -> &str {
+        &self.default_channel
+    }
+
+    pub fn get(&self, channel_name: &str) -> Option<&Channel> {
+        self.channels
+            .iter()
+            .find(|channel| channel.name().eq_ignore_ascii_case(channel_name))
+    }
+
+    pub fn contexts(&self) -> impl Iterator<Item = &ChannelContext> {
+        self.channels.iter().map(|channel| &channel.context)
+    }
+
+    pub fn create(
+        &mut self,
+        owner: &UserContext,
+        channel_name: &str,
+        temporary: bool,
+        password: Option<&str>,
+    ) -> Result<ChannelEventPacket, ChannelError> {
+        let required = if temporary {
+            CREATE_TEMPORARY
+        } else {
+            CREATE_PERMANENT
+        };
+        if owner.user_permissions.channel_permissions < required {
+            return Err(ChannelError::NotPermitted(channel_name.to_string()));
+        }
+        self.check_name(channel_name)?;
+
+        let channel = Channel {
+            context: ChannelContext {
+                channel_name: channel_name.to_string(),
+                password_protected: password.is_some(),
+                temporary,
+            },
+            owner: Some(owner.user_id.clone()),
+            password: password.map(str::to_string),
+        };
+        let packet = ChannelEventPacket::creation(&channel.context);
+        self.channels.push(channel);
+        Ok(packet)
+    }
+
+    pub fn rename(
+        &mut self,
+        user: &UserContext,
+        channel_name: &str,
+        new_name: &str,
+    ) -> Result<ChannelEventPacket, ChannelError> {
+        self.check_name(new_name)?;
+        let is_default = self.is_default(channel_name);
+        let channel = self.managed_mut(user, channel_name)?;
+        let old_name = channel.name().to_string();
+        channel.context.channel_name = new_name.to_string();
+        let packet = ChannelEventPacket::update(&old_name, &channel.context);
+        if is_default {
+            self.default_channel = new_name.to_string();
+        }
+        Ok(packet)
+    }
+
+    /// Sets or, with `None`, removes the password of a channel.
+    pub fn set_password(
+        &mut self,
+        user: &UserContext,
+        channel_name: &str,
+        password: Option<&str>,
+    ) -> Result<ChannelEventPacket, ChannelError> {
+        let channel = self.managed_mut(user, channel_name)?;
+        channel.password = password.map(str::to_string);
+        channel.context.password_protected = password.is_some();
+        Ok(ChannelEventPacket::update(channel.name(), &channel.context))
+    }
+
+    pub fn delete(
+        &mut self,
+        user: &UserContext,
+        channel_name: &str,
+    ) -> Result<ChannelEventPacket, ChannelError> {
+        if self.is_default(channel_name) {
+            return Err(ChannelError::NotPermitted(channel_name.to_string()));
+        }
+        let name = self.managed_mut(user, channel_name)?.name().to_string();
+        self.channels.retain(|channel| channel.name() != name);
+        Ok(ChannelEventPacket::deletion(&name))
+    }
+
+    /// Checks whether `user` may enter a channel, returning its exact name.
+    ///
+    /// Owners and moderators skip the password check.
+    pub fn check_join(
+        &self,
+        user: &UserContext,
+        channel_name: &str,
+        password: Option<&str>,
+    ) -> Result<&str, ChannelError> {
+        let channel = self
+            .get(channel_name)
+            .ok_or_else(|| ChannelError::NotFound(channel_name.to_string()))?;
+        match &channel.password {
+            Some(expected) if !channel.can_manage(user) && Some(expected.as_str()) != password => {
+                Err(ChannelError::WrongPassword(channel.name().to_string()))
+            }
+            _ => Ok(channel.name()),
+        }
+    }
+
+    /// Deletes the temporary channels owned by a user who left.
+    pub fn owner_left(&mut self, user_id: &str) -> Vec<ChannelEventPacket> {
+        let mut packets = Vec::new();
+        self.channels.retain(|channel| {
+            let delete = channel.context.temporary && channel.owner.as_deref() == Some(user_id);
+            if delete {
+                packets.push(ChannelEventPacket::deletion(channel.name()));
+            }
+            !delete
+        });
+        packets
+    }
+
+    fn is_default(&self, channel_name: &str) -> bool {
+        self.default_channel.eq_ignore_ascii_case(channel_name)
+    }
+
+    fn check_name(&self, channel_name: &str) -> Result<(), ChannelError> {
+        if channel_name.is_empty()
+            || channel_name
+                .chars()
+                .any(|c| c.is_whitespace() || c.is_control())
+        {
+            return Err(ChannelError::InvalidName(channel_name.to_string()));
+        }
+        if self.get(channel_name).is_some() {
+            return Err(ChannelError::AlreadyExists(channel_name.to_string()));
+        }
+        Ok(())
+    }
+
+    fn managed_mut(
+        &mut self,
+        user: &UserContext,
+        channel_name: &str,
+    ) -> Result<&mut Channel, ChannelError> {
+        let channel = self
+            .channels
+            .iter_mut()
+            .find(|channel| channel.name().eq_ignore_ascii_case(channel_name))
+            .ok_or_else(|| ChannelError::NotFound(channel_name.to_string()))?;
+        if !channel.can_manage(user) {
+            return Err(ChannelError::NotPermitted(channel.name().to_string()));
+        }
+        Ok(channel)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::packets::types::UserPermissions;
+
+    fn user(user_id: &str, channel_permissions: u8, can_moderate: bool) -> UserContext {
+        UserContext {
+            user_id: user_id.to_string(),
+            user_permissions: UserPermissions {
+                channel_permissions,
+                can_moderate,
+                ..Default::default()
+            },
+            ..Default::default()
+        }
+    }
+
+    fn manager() -> ChannelManager {
+        ChannelManager::new(
+            [ChannelContext {
+                channel_name: "lounge".to_string(),
+                ..Default::default()
+            }],
+            "lounge",
+        )
+    }
+
+    #[test]
+    fn enforces_creation_rights() {
+        let mut channels = manager();
+        let (nobody, temporary, permanent) = (
+            user("1", 0, false),
+            user("2", 1, false),
+            user("3", 2, false),
+        );
+
+        assert_eq!(
+            channels.create(&nobody, "mine", true, None),
+            Err(ChannelError::NotPermitted("mine".to_string()))
+        );
+        assert!(channels.create(&temporary, "mine", false, None).is_err());
+        assert_eq!(
+            channels.create(&temporary, "mine", true, None),
+            Ok(ChannelEventPacket::Creation {
+                channel_name: "mine".to_string(),
+                is_protected: false,
+                is_temporary: true,
+            })
+        );
+        assert_eq!(
+            channels.create(&permanent, "MINE", false, None),
+            Err(ChannelError::AlreadyExists("MINE".to_string()))
+        );
+        assert_eq!(
+            channels.create(&permanent, "bad name", false, None),
+            Err(ChannelError::InvalidName("bad name".to_string()))
+        );
+        assert!(channels.create(&permanent, "ours", false, None).is_ok());
+        assert!(channels.create(&permanent, "brief", true, None).is_ok());
+    }
+
+    #[test]
+    fn checks_passwords() {
+        let mut channels = manager();
+        let (owner, guest, moderator) =
+            (user("1", 1, false), user("2", 0, false), user("3", 0, true));
+        channels
+            .create(&owner, "secret", true, Some("hunter2"))
+            .unwrap();
+
+        assert_eq!(
+            channels.check_join(&guest, "secret", None),
+            Err(ChannelError::WrongPassword("secret".to_string()))
+        );
+        assert_eq!(
+            channels.check_join(&guest, "SECRET", Some("hunter2")),
+            Ok("secret")
+        );
+        assert_eq!(channels.check_join(&owner, "secret", None), Ok("secret"));
+        assert_eq!(
+            channels.check_join(&moderator, "secret", None),
+            Ok("secret")
+        );
+
+        assert!(channels.set_password(&guest, "secret", None).is_err());
+        assert_eq!(
+            channels.set_password(&owner, "secret", None),
+            Ok(ChannelEventPacket::Update {
+                channel_name: "secret".to_string(),
+                new_name: "secret".to_string(),
+                is_protected: false,
+                is_temporary: true,
+            })
+        );
+        assert_eq!(channels.check_join(&guest, "secret", None), Ok("secret"));
+    }
+
+    #[test]
+    fn manages_lifetime() {
+        let mut channels = manager();
+        let (owner, moderator) = (user("1", 2, false), user("3", 0, true));
+        channels.create(&owner, "temp", true, None).unwrap();
+        channels.create(&owner, "kept", false, None).unwrap();
+
+        assert!(channels.rename(&moderator, "kept", "renamed").is_ok());
+        assert!(channels.delete(&moderator, "lounge").is_err());
+        assert_eq!(
+            channels.owner_left("1"),
+            vec![ChannelEventPacket::deletion("temp")]
+        );
+        assert_eq!(
+            channels.delete(&owner, "renamed"),
+            Ok(ChannelEventPacket::deletion("renamed"))
+        );
+        assert_eq!(channels.contexts().count(), 1);
+    }
+}
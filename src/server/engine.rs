@@ -0,0 +1,1549 @@
+use std::collections::{BTreeMap, HashMap, VecDeque};
+
+use crate::packets::{
+    bot_message::{BotMessage, BOT_USER_ID, WHISPER_ECHO},
+    client::{AuthenticationPacket, ClientPacket, MessagePacket, MCHAN},
+    server::{
+        ChannelEventPacket, ChannelSwitchingPacket, ChatMessagePacket, ContextClearingPacket,
+        ContextInformationPacket, ForcedDisconnectPacket, JoinAuthPacket, ServerPacket,
+        UserDisconnectPacket,
+    },
+    types::{
+        unix_timestamp, ChannelContext, DisconnectReason, MessageFlags, Sockchatable, UserContext,
+    },
+};
+
+use super::{
+    auth::AuthProvider,
+    channels::{ChannelError, ChannelManager},
+    flood::{FloodProtection, FloodVerdict},
+    moderation::{BanList, BanTarget},
+};
+
+/// Opaque handle the transport uses to tell connections apart.
+pub type ConnectionId = u64;
+
+#[derive(Debug, Clone)]
+pub struct ServerConfig {
+    pub default_channel: String,
+    pub channels: Vec<ChannelContext>,
+    /// Longest accepted message, in characters; longer ones are refused.
+    pub max_msg_length: i64,
+    pub backlog_size: usize,
+}
+
+impl Default for ServerConfig {
+    fn default() -> Self {
+        ServerConfig {
+            default_channel: "lounge".to_string(),
+            channels: vec![ChannelContext {
+                channel_name: "lounge".to_string(),
+                password_protected: false,
+                temporary: false,
+            }],
+            max_msg_length: 2000,
+            backlog_size: 30,
+        }
+    }
+}
+
+#[derive(Default)]
+struct Connection {
+    user_id: Option<String>,
+    address: Option<String>,
+    /// Whether `MCHAN` was confirmed, which attributes joins, departures and
+    /// messages to their channel.
+    multi_channel: bool,
+}
+
+struct Member {
+    user: UserContext,
+    /// Channel the user's messages go to.
+    channel: String,
+    /// Every channel the user is in, `channel` included; only users whose
+    /// first connection negotiated `MCHAN` can be in more than one.
+    channels: Vec<String>,
+    multi_channel: bool,
+    connections: Vec<ConnectionId>,
+}
+
+/// Transport-independent Sockchat server.
+///
+/// Every call returns the packets to deliver as `(connection, packet)` pairs,
+/// in order; the engine itself never performs any IO.
+pub struct ServerEngine<A> {
+    auth: A,
+    config: ServerConfig,
+    connections: BTreeMap<ConnectionId, Connection>,
+    members: BTreeMap<String, Member>,
+    channels: ChannelManager,
+    backlogs: HashMap<String, VecDeque<ContextInformationPacket>>,
+    flood: Option<FloodProtection>,
+    bans: Option<BanList>,
+    next_connection: ConnectionId,
+    sequence: u64,
+}
+
+impl<A: AuthProvider> ServerEngine<A> {
+    pub fn new(auth: A, config: ServerConfig) -> Self {
+        let channels = ChannelManager::new(config.channels.clone(), &config.default_channel);
+        ServerEngine {
+            auth,
+            config,
+            connections: BTreeMap::new(),
+            members: BTreeMap::new(),
+            channels,
+            backlogs: HashMap::new(),
+            flood: None,
+            bans: None,
+            next_connection: 1,
+            sequence: 0,
+        }
+    }
+
+    /// Runs every chat message through `flood` before broadcasting it.
+    pub fn with_flood_protection(mut self, flood: FloodProtection) -> Self {
+        self.flood = Some(flood);
+        self
+    }
+
+    /// Keeps banned users out and enables the moderation commands.
+    pub fn with_bans(mut self, bans: BanList) -> Self {
+        self.bans = Some(bans);
+        self
+    }
+
+    pub fn bans(&self) -> Option<&BanList> {
+        self.bans.as_ref()
+    }
+
+    pub fn auth(&self) -> &A {
+        &self.auth
+    }
+
+    pub fn auth_mut(&mut self) -> &mut A {
+        &mut self.auth
+    }
+
+    pub fn user(&self, user_id: &str) -> Option<&UserContext> {
+        self.members.get(user_id).map(|member| &member.user)
+    }
+
+    pub fn users(&self) -> impl Iterator<Item = &UserContext> {
+        self.members.values().map(|member| &member.user)
+    }
+
+    pub fn user_channel(&self, user_id: &str) -> Option<&str> {
+        self.members
+            .get(user_id)
+            .map(|member| member.channel.as_str())
+    }
+
+    /// Every channel a user is in, starting with the one they joined first.
+    pub fn user_channels(&self, user_id: &str) -> Option<&[String]> {
+        self.members
+            .get(user_id)
+            .map(|member| member.channels.as_slice())
+    }
+
+    pub fn channels(&self) -> impl Iterator<Item = &ChannelContext> {
+        self.channels.contexts()
+    }
+
+    /// Registers a freshly opened connection.
+    pub fn connect(&mut self) -> ConnectionId {
+        let connection = self.next_connection;
+        self.next_connection += 1;
+        self.connections.insert(connection, Connection::default());
+        connection
+    }
+
+    /// Registers a freshly opened connection from a remote `address`, which
+    /// address bans are matched against.
+    pub fn connect_from(&mut self, address: &str) -> ConnectionId {
+        let connection = self.connect();
+        self.connections.get_mut(&connection).unwrap().address = Some(address.to_string());
+        connection
+    }
+
+    pub fn handle(
+        &mut self,
+        connection: ConnectionId,
+        packet: &ClientPacket,
+    ) -> Vec<(ConnectionId, ServerPacket)> {
+        if !self.connections.contains_key(&connection) {
+            return Vec::new();
+        }
+        match packet {
+            ClientPacket::Ping(_) => vec![(connection, ServerPacket::pong("pong"))],
+            ClientPacket::Authentication(packet) => self.authenticate(connection, packet),
+            ClientPacket::Message(packet) => self.message(connection, packet),
+        }
+    }
+
+    /// Forgets a closed connection, announcing the departure of its user if
+    /// it was their last one.
+    pub fn disconnect(
+        &mut self,
+        connection: ConnectionId,
+        reason: DisconnectReason,
+    ) -> Vec<(ConnectionId, ServerPacket)> {
+        let mut out = Vec::new();
+        let Some(Connection {
+            user_id: Some(user_id),
+            ..
+        }) = self.connections.remove(&connection)
+        else {
+            return out;
+        };
+        let Some(member) = self.members.get_mut(&user_id) else {
+            return out;
+        };
+        member.connections.retain(|other| *other != connection);
+        if !member.connections.is_empty() {
+            return out;
+        }
+
+        let member = self.members.remove(&user_id).unwrap();
+        self.leave(&mut out, member, reason);
+        out
+    }
+
+    /// Forcibly disconnects every connection of a user, banning them until
+    /// `ban_expires` if given.
+    ///
+    /// The engine forgets the connections right away; the transport should
+    /// close each of them once the `ForcedDisconnectPacket` is delivered.
+    pub fn kick(
+        &mut self,
+        user_id: &str,
+        reason: DisconnectReason,
+        ban_expires: Option<i64>,
+    ) -> Vec<(ConnectionId, ServerPacket)> {
+        let mut out = Vec::new();
+        let Some(member) = self.members.remove(user_id) else {
+            return out;
+        };
+
+        let forced = match ban_expires {
+            Some(expires) => ForcedDisconnectPacket::ban(expires),
+            None => ForcedDisconnectPacket::kick(),
+        };
+        for connection in &member.connections {
+            self.connections.remove(connection);
+            out.push((*connection, forced.clone().into()));
+        }
+        self.leave(&mut out, member, reason);
+        out
+    }
+
+    /// Announces the departure of a user who has no connections left and
+    /// cleans up after them.
+    fn leave(
+        &mut self,
+        out: &mut Vec<(ConnectionId, ServerPacket)>,
+        member: Member,
+        reason: DisconnectReason,
+    ) {
+        if let Some(flood) = &mut self.flood {
+            flood.forget(&member.user.user_id);
+        }
+        let sequence_id = self.next_sequence();
+        let packet = ServerPacket::UserDisconnect(UserDisconnectPacket::new(
+            &member.user,
+            reason,
+            unix_timestamp(),
+            &sequence_id,
+        ));
+        for other in self.members.values() {
+            if !other
+                .channels
+                .iter()
+                .any(|channel| member.channels.contains(channel))
+            {
+                continue;
+            }
+            for connection in &other.connections {
+                out.push((*connection, packet.clone()));
+            }
+        }
+        for deletion in self.channels.owner_left(&member.user.user_id) {
+            self.channel_deleted(out, deletion);
+        }
+    }
+
+    fn authenticate(
+        &mut self,
+        connection: ConnectionId,
+        packet: &AuthenticationPacket,
+    ) -> Vec<(ConnectionId, ServerPacket)> {
+        let mut out = Vec::new();
+        if self.connection_user(connection).is_some() {
+            return out;
+        }
+
+        let profile = match self.auth.authenticate(&packet.method, &packet.authkey) {
+            Ok(profile) => profile,
+            Err(reason) => {
+                out.push((
+                    connection,
+                    JoinAuthPacket::bad_auth(reason, unix_timestamp()).into(),
+                ));
+                return out;
+            }
+        };
+
+        if let Some(bans) = &self.bans {
+            let address = self.connections[&connection].address.as_deref();
+            if let Err((reason, expires)) = bans.check(&profile.user_id, address) {
+                out.push((connection, JoinAuthPacket::bad_auth(reason, expires).into()));
+                return out;
+            }
+        }
+
+        let user = profile.to_context();
+        let wants_multi_channel = packet.supports(MCHAN);
+        if let Some(member) = self.members.get_mut(&user.user_id) {
+            member.connections.push(connection);
+        } else {
+            let channel = self.channels.default_channel().to_string();
+            let sequence_id = self.next_sequence();
+            let join = JoinAuthPacket::join(unix_timestamp(), &user, &sequence_id).into();
+            self.broadcast(&mut out, &channel, None, &join);
+            self.members.insert(
+                user.user_id.clone(),
+                Member {
+                    user: user.clone(),
+                    channel: channel.clone(),
+                    channels: vec![channel],
+                    multi_channel: wants_multi_channel,
+                    connections: vec![connection],
+                },
+            );
+        }
+
+        let member = &self.members[&user.user_id];
+        let (channel, channels) = (member.channel.clone(), member.channels.clone());
+        let multi_channel = wants_multi_channel && member.multi_channel;
+        let state = self.connections.get_mut(&connection).unwrap();
+        state.user_id = Some(user.user_id.clone());
+        state.multi_channel = multi_channel;
+
+        let mut good_auth = JoinAuthPacket::good_auth(&user, &channel, self.config.max_msg_length);
+        if multi_channel {
+            good_auth = good_auth.with_capabilities(&[MCHAN]);
+        }
+        out.push((connection, good_auth.into()));
+        for packet in self.channel_context(&channel, &user.user_id) {
+            out.push((connection, packet));
+        }
+        // Channels joined through other connections are walked through one
+        // forced switch at a time before returning to the current one.
+        if multi_channel && channels.len() > 1 {
+            for other in channels.iter().filter(|other| **other != channel) {
+                out.push((
+                    connection,
+                    ChannelSwitchingPacket::forced_switch(other).into(),
+                ));
+                for packet in self.channel_context(other, &user.user_id) {
+                    out.push((connection, packet));
+                }
+            }
+            out.push((
+                connection,
+                ChannelSwitchingPacket::forced_switch(&channel).into(),
+            ));
+        }
+        out.push((
+            connection,
+            ContextInformationPacket::channels(self.channels().cloned()).into(),
+        ));
+        out
+    }
+
+    fn message(
+        &mut self,
+        connection: ConnectionId,
+        packet: &MessagePacket,
+    ) -> Vec<(ConnectionId, ServerPacket)> {
+        let mut out = Vec::new();
+        let Some(user_id) = self.connection_user(connection).map(str::to_string) else {
+            return out;
+        };
+        if packet.user_id != user_id {
+            return out;
+        }
+
+        // `/me` and `/msg` are messages of another kind rather than commands.
+        let mut text = packet.message.as_str();
+        let mut flags = MessageFlags::default();
+        let mut recipient = None;
+        if let Some(command) = text.strip_prefix('/') {
+            let (name, rest) = command.split_once(' ').unwrap_or((command, ""));
+            let rest = rest.trim_start();
+            match (name.to_lowercase().as_str(), rest.split_once(' ')) {
+                ("me", _) if !rest.is_empty() => {
+                    text = rest;
+                    flags.cursive = true;
+                    flags.colon = false;
+                }
+                ("msg", Some((username, whisper))) if !whisper.trim().is_empty() => {
+                    text = whisper;
+                    flags.private = true;
+                    recipient = Some(username);
+                }
+                ("me" | "msg", _) => {
+                    self.reply(&mut out, connection, BotMessage::error("cmderr", &[]));
+                    return out;
+                }
+                _ => {
+                    self.command(&mut out, connection, &user_id, command);
+                    return out;
+                }
+            }
+        }
+
+        let max_msg_length = self.config.max_msg_length.max(0) as usize;
+        let verdict = match &mut self.flood {
+            Some(flood) => flood.check(&user_id, text, max_msg_length),
+            None if text.chars().count() > max_msg_length => FloodVerdict::TooLong,
+            None => FloodVerdict::Allow,
+        };
+        match verdict {
+            FloodVerdict::Allow => {}
+            FloodVerdict::Flood => {
+                let ban_seconds = self.flood.as_ref().unwrap().config().ban_seconds;
+                // Without a ban list to enforce it, the ban is only a kick.
+                let ban_expires = match &mut self.bans {
+                    Some(bans) if ban_seconds > 0 => {
+                        let target = BanTarget::User(user_id.clone());
+                        let username = self.members[&user_id].user.username.clone();
+                        bans.ban(target, &username, Some(ban_seconds))
+                            .ok()
+                            .map(|ban| ban.expires)
+                    }
+                    _ => None,
+                };
+                return self.kick(&user_id, DisconnectReason::Flood, ban_expires);
+            }
+            verdict => {
+                if let Some(bot_message) = verdict.bot_message() {
+                    self.reply(&mut out, connection, bot_message);
+                }
+                return out;
+            }
+        }
+
+        if let Some(username) = recipient {
+            self.whisper(&mut out, connection, &user_id, username, text, flags);
+            return out;
+        }
+
+        let member = &self.members[&user_id];
+        let (user, channel) = (member.user.clone(), member.channel.clone());
+        let sequence_id = self.next_sequence();
+        let timestamp = unix_timestamp();
+        let packet = ServerPacket::ChatMessage(ChatMessagePacket::new(
+            timestamp,
+            &user_id,
+            text,
+            &sequence_id,
+            flags.clone(),
+        ));
+        self.broadcast(&mut out, &channel, None, &packet);
+
+        let backlog = self.backlogs.entry(channel).or_default();
+        backlog.push_back(ContextInformationPacket::message(
+            timestamp,
+            &user,
+            text,
+            &sequence_id,
+            false,
+            flags,
+        ));
+        while backlog.len() > self.config.backlog_size {
+            backlog.pop_front();
+        }
+        out
+    }
+
+    /// Sends `text` to every connection of `username`, and echoes it back to
+    /// the sender's connections as a `WHISPER_ECHO` bot message.
+    fn whisper(
+        &mut self,
+        out: &mut Vec<(ConnectionId, ServerPacket)>,
+        connection: ConnectionId,
+        user_id: &str,
+        username: &str,
+        text: &str,
+        flags: MessageFlags,
+    ) {
+        let recipient = self
+            .members
+            .values()
+            .find(|member| member.user.username.eq_ignore_ascii_case(username))
+            .map(|member| (member.user.username.clone(), member.connections.clone()));
+        let Some((username, connections)) = recipient else {
+            self.reply(out, connection, BotMessage::error("usernf", &[username]));
+            return;
+        };
+        let timestamp = unix_timestamp();
+        let sequence_id = self.next_sequence();
+        let packet = ServerPacket::ChatMessage(ChatMessagePacket::new(
+            timestamp,
+            user_id,
+            text,
+            &sequence_id,
+            flags.clone(),
+        ));
+        out.extend(connections.iter().map(|to| (*to, packet.clone())));
+
+        let echo = BotMessage::info(WHISPER_ECHO, &[&username, text]);
+        let sequence_id = self.next_sequence();
+        let packet = ServerPacket::ChatMessage(ChatMessagePacket::new(
+            timestamp,
+            BOT_USER_ID,
+            &echo.to_sockstr(),
+            &sequence_id,
+            flags,
+        ));
+        let connections = &self.members[user_id].connections;
+        out.extend(connections.iter().map(|to| (*to, packet.clone())));
+    }
+
+    fn command(
+        &mut self,
+        out: &mut Vec<(ConnectionId, ServerPacket)>,
+        connection: ConnectionId,
+        user_id: &str,
+        command: &str,
+    ) {
+        let mut args = command.split_whitespace();
+        let name = args.next().unwrap_or_default().to_lowercase();
+        let args = args.collect::<Vec<_>>();
+        let can_moderate = self.members[user_id].user.user_permissions.can_moderate;
+        match (name.as_str(), args.as_slice()) {
+            ("join", [channel_name, password @ ..]) => self.join_channel(
+                out,
+                connection,
+                user_id,
+                channel_name,
+                password.first().copied(),
+            ),
+            ("create", ["-p", channel_name, password @ ..]) => self.create_channel(
+                out,
+                connection,
+                user_id,
+                channel_name,
+                false,
+                password.first().copied(),
+            ),
+            ("create", [channel_name, password @ ..]) => self.create_channel(
+                out,
+                connection,
+                user_id,
+                channel_name,
+                true,
+                password.first().copied(),
+            ),
+            ("leave", [channel_name, ..]) => {
+                self.leave_channel(out, connection, user_id, channel_name)
+            }
+            ("rename", [new_name, ..]) => self.rename_channel(out, connection, user_id, new_name),
+            ("delete", [channel_name, ..]) => {
+                self.delete_channel(out, connection, user_id, channel_name)
+            }
+            ("password" | "pwd", password) => {
+                self.set_password(out, connection, user_id, password.first().copied())
+            }
+            ("kick" | "ban" | "pardon" | "bans", _) if !can_moderate => self.reply(
+                out,
+                connection,
+                BotMessage::error("cmdna", &[&format!("/{}", name)]),
+            ),
+            ("ban" | "pardon" | "bans", _) if self.bans.is_none() => self.reply(
+                out,
+                connection,
+                BotMessage::error("cmdna", &[&format!("/{}", name)]),
+            ),
+            ("kick" | "ban", [target, rest @ ..]) => {
+                let seconds = rest.first().and_then(|seconds| seconds.parse::<i64>().ok());
+                self.moderate(out, connection, user_id, target, name == "ban", seconds)
+            }
+            ("pardon", [target, ..]) => {
+                let reply = match self.bans.as_mut().unwrap().pardon(target) {
+                    Ok(true) => BotMessage::info("unban", &[target]),
+                    Ok(false) => BotMessage::error("notban", &[target]),
+                    Err(error) => BotMessage::error("generr", &[&error.to_string()]),
+                };
+                self.reply(out, connection, reply)
+            }
+            ("bans", _) => {
+                let reply = self.bans.as_ref().unwrap().bot_message();
+                self.reply(out, connection, reply)
+            }
+            ("join" | "leave" | "create" | "rename" | "delete" | "kick" | "ban" | "pardon", _) => {
+                self.reply(out, connection, BotMessage::error("cmderr", &[]))
+            }
+            _ => self.reply(out, connection, BotMessage::error("nocmd", &[&name])),
+        }
+    }
+
+    /// Kicks the user called `username`, banning their id and addresses if
+    /// `ban` is set or a duration is given.
+    ///
+    /// Moderators can only act on users of a lower rank than their own.
+    fn moderate(
+        &mut self,
+        out: &mut Vec<(ConnectionId, ServerPacket)>,
+        connection: ConnectionId,
+        user_id: &str,
+        username: &str,
+        ban: bool,
+        seconds: Option<i64>,
+    ) {
+        let Some(member) = self
+            .members
+            .values()
+            .find(|member| member.user.username.eq_ignore_ascii_case(username))
+        else {
+            self.reply(out, connection, BotMessage::error("usernf", &[username]));
+            return;
+        };
+
+        let user = member.user.clone();
+        let rank = self.members[user_id].user.user_permissions.rank;
+        if user.user_id == user_id || user.user_permissions.rank >= rank {
+            self.reply(
+                out,
+                connection,
+                BotMessage::error("kickna", &[&user.username]),
+            );
+            return;
+        }
+        let addresses = member
+            .connections
+            .iter()
+            .filter_map(|connection| self.connections[connection].address.clone())
+            .collect::<Vec<_>>();
+        let mut ban_expires = None;
+        if ban || seconds.is_some() {
+            let Some(bans) = self.bans.as_mut() else {
+                self.reply(out, connection, BotMessage::error("cmdna", &["/kick"]));
+                return;
+            };
+            let mut targets = vec![BanTarget::User(user.user_id.clone())];
+            targets.extend(addresses.into_iter().map(BanTarget::Address));
+            for target in targets {
+                match bans.ban(target, &user.username, seconds) {
+                    Ok(ban) => ban_expires = Some(ban.expires),
+                    Err(error) => {
+                        let reply = BotMessage::error("generr", &[&error.to_string()]);
+                        self.reply(out, connection, reply);
+                        break;
+                    }
+                }
+            }
+            // Nothing to enforce if not even the user id could be banned.
+            if ban_expires.is_none() {
+                return;
+            }
+        }
+        out.extend(self.kick(&user.user_id, DisconnectReason::Kick, ban_expires));
+    }
+
+    fn join_channel(
+        &mut self,
+        out: &mut Vec<(ConnectionId, ServerPacket)>,
+        connection: ConnectionId,
+        user_id: &str,
+        channel_name: &str,
+        password: Option<&str>,
+    ) {
+        let member = &self.members[user_id];
+        let result = self
+            .channels
+            .check_join(&member.user, channel_name, password)
+            .and_then(|channel_name| match channel_name == member.channel {
+                true => Err(ChannelError::SameChannel(channel_name.to_string())),
+                false => Ok(channel_name.to_string()),
+            });
+        match result {
+            Ok(channel_name) if member.channels.contains(&channel_name) => {
+                self.switch_channel(out, user_id, &channel_name)
+            }
+            Ok(channel_name) => self.move_user(out, user_id, &channel_name),
+            Err(error) => self.reply(out, connection, error.bot_message()),
+        }
+    }
+
+    /// Leaves one of several channels joined with `MCHAN`.
+    fn leave_channel(
+        &mut self,
+        out: &mut Vec<(ConnectionId, ServerPacket)>,
+        connection: ConnectionId,
+        user_id: &str,
+        channel_name: &str,
+    ) {
+        let member = &self.members[user_id];
+        if !member.multi_channel {
+            self.reply(out, connection, BotMessage::error("cmdna", &["/leave"]));
+            return;
+        }
+        let Some(channel) = member
+            .channels
+            .iter()
+            .find(|channel| channel.eq_ignore_ascii_case(channel_name))
+            .cloned()
+        else {
+            let error = ChannelError::NotFound(channel_name.to_string());
+            self.reply(out, connection, error.bot_message());
+            return;
+        };
+        if member.channels.len() == 1 {
+            self.reply(
+                out,
+                connection,
+                ChannelError::NotPermitted(channel).bot_message(),
+            );
+            return;
+        }
+
+        let sequence_id = self.next_sequence();
+        let departure = ChannelSwitchingPacket::departure(user_id, &sequence_id);
+        self.broadcast(out, &channel, Some(user_id), &departure.clone().into());
+        // Only the user's own `MCHAN` connections learn which channel they left.
+        let departure = departure.in_channel(&channel);
+        let member = self.members.get_mut(user_id).unwrap();
+        member.channels.retain(|other| *other != channel);
+        for connection in &member.connections {
+            if self.connections[connection].multi_channel {
+                out.push((*connection, departure.clone().into()));
+            }
+        }
+        if member.channel == channel {
+            let current = member.channels.last().unwrap().clone();
+            self.switch_channel(out, user_id, &current);
+        }
+    }
+
+    /// Creates a channel and moves its creator in.
+    ///
+    /// `/create` makes a temporary channel, `/create -p` a permanent one.
+    fn create_channel(
+        &mut self,
+        out: &mut Vec<(ConnectionId, ServerPacket)>,
+        connection: ConnectionId,
+        user_id: &str,
+        channel_name: &str,
+        temporary: bool,
+        password: Option<&str>,
+    ) {
+        let user = self.members[user_id].user.clone();
+        match self
+            .channels
+            .create(&user, channel_name, temporary, password)
+        {
+            Ok(packet) => {
+                self.broadcast_all(out, &packet.into());
+                self.move_user(out, user_id, channel_name);
+            }
+            Err(error) => self.reply(out, connection, error.bot_message()),
+        }
+    }
+
+    /// Renames the channel the user is in, taking its members and backlog along.
+    fn rename_channel(
+        &mut self,
+        out: &mut Vec<(ConnectionId, ServerPacket)>,
+        connection: ConnectionId,
+        user_id: &str,
+        new_name: &str,
+    ) {
+        let member = &self.members[user_id];
+        let old_name = member.channel.clone();
+        match self.channels.rename(&member.user, &old_name, new_name) {
+            Ok(packet) => {
+                for member in self.members.values_mut() {
+                    for channel in member
+                        .channels
+                        .iter_mut()
+                        .chain([&mut member.channel])
+                        .filter(|channel| **channel == old_name)
+                    {
+                        *channel = new_name.to_string();
+                    }
+                }
+                if let Some(backlog) = self.backlogs.remove(&old_name) {
+                    self.backlogs.insert(new_name.to_string(), backlog);
+                }
+                self.broadcast_all(out, &packet.into());
+            }
+            Err(error) => self.reply(out, connection, error.bot_message()),
+        }
+    }
+
+    fn delete_channel(
+        &mut self,
+        out: &mut Vec<(ConnectionId, ServerPacket)>,
+        connection: ConnectionId,
+        user_id: &str,
+        channel_name: &str,
+    ) {
+        let user = self.members[user_id].user.clone();
+        match self.channels.delete(&user, channel_name) {
+            Ok(packet) => self.channel_deleted(out, packet),
+            Err(error) => self.reply(out, connection, error.bot_message()),
+        }
+    }
+
+    /// Sets or clears the password of the channel the user is in.
+    fn set_password(
+        &mut self,
+        out: &mut Vec<(ConnectionId, ServerPacket)>,
+        connection: ConnectionId,
+        user_id: &str,
+        password: Option<&str>,
+    ) {
+        let member = &self.members[user_id];
+        match self
+            .channels
+            .set_password(&member.user, &member.channel, password)
+        {
+            Ok(packet) => self.broadcast_all(out, &packet.into()),
+            Err(error) => self.reply(out, connection, error.bot_message()),
+        }
+    }
+
+    /// Announces a deletion and moves everyone left inside to the default channel.
+    fn channel_deleted(
+        &mut self,
+        out: &mut Vec<(ConnectionId, ServerPacket)>,
+        packet: ChannelEventPacket,
+    ) {
+        let ChannelEventPacket::Deletion { channel_name } = &packet else {
+            return;
+        };
+        let channel_name = channel_name.clone();
+        self.backlogs.remove(&channel_name);
+        self.broadcast_all(out, &packet.into());
+
+        let default_channel = self.channels.default_channel().to_string();
+        let mut stranded = Vec::new();
+        for member in self.members.values_mut() {
+            member.channels.retain(|channel| *channel != channel_name);
+            if member.channel == channel_name {
+                stranded.push((member.user.user_id.clone(), member.channels.last().cloned()));
+            }
+        }
+        for (user_id, remaining) in stranded {
+            match remaining {
+                Some(channel) => self.switch_channel(out, &user_id, &channel),
+                None => self.move_user(out, &user_id, &default_channel),
+            }
+        }
+    }
+
+    fn move_user(
+        &mut self,
+        out: &mut Vec<(ConnectionId, ServerPacket)>,
+        user_id: &str,
+        channel_name: &str,
+    ) {
+        let member = &self.members[user_id];
+        let (user, old_channel) = (member.user.clone(), member.channel.clone());
+        let keep = member.multi_channel;
+
+        let sequence_id = self.next_sequence();
+        if !keep {
+            let departure = ChannelSwitchingPacket::departure(user_id, &sequence_id).into();
+            self.broadcast(out, &old_channel, Some(user_id), &departure);
+        }
+        let join = ChannelSwitchingPacket::join(&user, &sequence_id).into();
+        self.broadcast(out, channel_name, None, &join);
+
+        let member = self.members.get_mut(user_id).unwrap();
+        if !keep {
+            member.channels.clear();
+        }
+        member.channels.push(channel_name.to_string());
+        self.switch_channel(out, user_id, channel_name);
+    }
+
+    /// Makes `channel_name`, which the user is already in, their current
+    /// channel and sends them its user list and backlog.
+    fn switch_channel(
+        &mut self,
+        out: &mut Vec<(ConnectionId, ServerPacket)>,
+        user_id: &str,
+        channel_name: &str,
+    ) {
+        let member = self.members.get_mut(user_id).unwrap();
+        member.channel = channel_name.to_string();
+        let connections = member.connections.clone();
+        let channel_name = channel_name.to_string();
+        for connection in connections {
+            out.push((
+                connection,
+                ChannelSwitchingPacket::forced_switch(&channel_name).into(),
+            ));
+            out.push((
+                connection,
+                ContextClearingPacket::messages_and_users().into(),
+            ));
+            for packet in self.channel_context(&channel_name, user_id) {
+                out.push((connection, packet));
+            }
+        }
+    }
+
+    /// User list and backlog of `channel`, as seen by `user_id`.
+    fn channel_context(&self, channel: &str, user_id: &str) -> Vec<ServerPacket> {
+        let users = self
+            .members
+            .values()
+            .filter(|member| {
+                member.channels.iter().any(|joined| joined == channel)
+                    && member.user.user_id != user_id
+            })
+            .map(|member| member.user.clone());
+        let mut packets = vec![ContextInformationPacket::users(users).into()];
+        if let Some(backlog) = self.backlogs.get(channel) {
+            packets.extend(backlog.iter().cloned().map(ServerPacket::from));
+        }
+        packets
+    }
+
+    fn broadcast(
+        &self,
+        out: &mut Vec<(ConnectionId, ServerPacket)>,
+        channel: &str,
+        except: Option<&str>,
+        packet: &ServerPacket,
+    ) {
+        for member in self.members.values() {
+            if !member.channels.iter().any(|joined| joined == channel)
+                || Some(member.user.user_id.as_str()) == except
+            {
+                continue;
+            }
+            for connection in &member.connections {
+                let multi_channel = self
+                    .connections
+                    .get(connection)
+                    .is_some_and(|connection| connection.multi_channel);
+                let packet = match packet {
+                    ServerPacket::ChatMessage(message) if multi_channel => {
+                        ServerPacket::ChatMessage(message.clone().in_channel(channel))
+                    }
+                    ServerPacket::ChannelSwitching(switching) if multi_channel => {
+                        switching.clone().in_channel(channel).into()
+                    }
+                    packet => packet.clone(),
+                };
+                out.push((*connection, packet));
+            }
+        }
+    }
+
+    fn broadcast_all(&self, out: &mut Vec<(ConnectionId, ServerPacket)>, packet: &ServerPacket) {
+        for member in self.members.values() {
+            for connection in &member.connections {
+                out.push((*connection, packet.clone()));
+            }
+        }
+    }
+
+    fn reply(
+        &mut self,
+        out: &mut Vec<(ConnectionId, ServerPacket)>,
+        connection: ConnectionId,
+        bot_message: BotMessage,
+    ) {
+        let sequence_id = self.next_sequence();
+        out.push((
+            connection,
+            ServerPacket::bot_message(&bot_message, &sequence_id),
+        ));
+    }
+
+    fn connection_user(&self, connection: ConnectionId) -> Option<&str> {
+        self.connections.get(&connection)?.user_id.as_deref()
+    }
+
+    fn next_sequence(&mut self) -> String {
+        self.sequence += 1;
+        self.sequence.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{fs, time::Instant};
+
+    use super::*;
+    use crate::{
+        client::{ChatState, Session},
+        clock::ManualClock,
+        packets::{client::PingPacket, types::BadAuthReason},
+        server::{
+            auth::UserProfile, channels::CREATE_PERMANENT, flood::FloodConfig, moderation::BanList,
+        },
+    };
+
+    struct NumberedAuth;
+
+    impl AuthProvider for NumberedAuth {
+        fn authenticate(
+            &mut self,
+            _method: &str,
+            authkey: &str,
+        ) -> Result<UserProfile, BadAuthReason> {
+            let user_id = authkey
+                .strip_prefix("user")
+                .ok_or(BadAuthReason::AuthFail)?;
+            let mut profile = UserProfile::new(user_id, authkey);
+            profile.user_permissions.can_moderate = ["1", "4"].contains(&user_id);
+            profile.user_permissions.rank = match user_id {
+                "1" => 5,
+                "4" => 1,
+                _ => 0,
+            };
+            profile.user_permissions.channel_permissions = match user_id {
+                "1" => CREATE_PERMANENT,
+                "2" => CREATE_PERMANENT - 1,
+                _ => 0,
+            };
+            Ok(profile)
+        }
+    }
+
+    fn engine() -> ServerEngine<NumberedAuth> {
+        let mut config = ServerConfig::default();
+        config.channels.push(ChannelContext {
+            channel_name: "games".to_string(),
+            ..Default::default()
+        });
+        ServerEngine::new(NumberedAuth, config)
+    }
+
+    fn login(
+        engine: &mut ServerEngine<NumberedAuth>,
+        authkey: &str,
+    ) -> (ConnectionId, Vec<(ConnectionId, ServerPacket)>) {
+        let connection = engine.connect();
+        let out = engine.handle(
+            connection,
+            &ClientPacket::Authentication(AuthenticationPacket::new("Misuzu", authkey)),
+        );
+        (connection, out)
+    }
+
+    fn say(user_id: &str, message: &str) -> ClientPacket {
+        ClientPacket::Message(MessagePacket {
+            user_id: user_id.to_string(),
+            message: message.to_string(),
+        })
+    }
+
+    #[test]
+    fn rejects_bad_auth_and_answers_pings() {
+        let mut engine = engine();
+        let (connection, out) = login(&mut engine, "nobody");
+        assert!(matches!(
+            out.as_slice(),
+            [(
+                _,
+                ServerPacket::JoinAuth(JoinAuthPacket::BadAuth {
+                    reason: BadAuthReason::AuthFail,
+                    ..
+                })
+            )]
+        ));
+        let out = engine.handle(
+            connection,
+            &ClientPacket::Ping(PingPacket {
+                user_id: "1".to_string(),
+            }),
+        );
+        assert_eq!(out, vec![(connection, ServerPacket::pong("pong"))]);
+        assert!(engine.handle(connection, &say("1", "hi")).is_empty());
+    }
+
+    #[test]
+    fn joins_chats_and_leaves() {
+        let mut engine = engine();
+        let (first, out) = login(&mut engine, "user1");
+        assert!(matches!(
+            out.as_slice(),
+            [
+                (_, ServerPacket::JoinAuth(JoinAuthPacket::GoodAuth { .. })),
+                (_, ServerPacket::ContextInformation(ContextInformationPacket::ExistingUsers { contexts })),
+                (_, ServerPacket::ContextInformation(ContextInformationPacket::Channels { .. })),
+            ] if contexts.is_empty()
+        ));
+
+        let out = engine.handle(first, &say("1", "hello"));
+        assert_eq!(out.len(), 1);
+
+        let (second, out) = login(&mut engine, "user2");
+        assert!(matches!(
+            &out[0],
+            (connection, ServerPacket::JoinAuth(JoinAuthPacket::Join { user_id, .. }))
+                if *connection == first && user_id == "2"
+        ));
+        assert!(matches!(
+            &out[2],
+            (_, ServerPacket::ContextInformation(ContextInformationPacket::ExistingUsers { contexts }))
+                if contexts.len() == 1
+        ));
+        assert!(matches!(
+            &out[3],
+            (_, ServerPacket::ContextInformation(ContextInformationPacket::ExistingMessage { message, .. }))
+                if message == "hello"
+        ));
+
+        let out = engine.handle(second, &say("2", "hi"));
+        let recipients = out
+            .iter()
+            .map(|(connection, _)| *connection)
+            .collect::<Vec<_>>();
+        assert_eq!(recipients, vec![first, second]);
+        assert!(engine.handle(second, &say("1", "spoofed")).is_empty());
+
+        let out = engine.disconnect(first, DisconnectReason::Leave);
+        assert!(matches!(
+            out.as_slice(),
+            [(connection, ServerPacket::UserDisconnect(packet))]
+                if *connection == second && packet.user_id == "1"
+        ));
+        assert!(engine.user("1").is_none());
+    }
+
+    #[test]
+    fn delivers_whispers_and_actions() {
+        let mut engine = engine();
+        let (first, _) = login(&mut engine, "user1");
+        let (second, _) = login(&mut engine, "user2");
+
+        let out = engine.handle(second, &say("2", "/msg USER1 psst, hi"));
+        assert!(matches!(
+            out.as_slice(),
+            [
+                (to, ServerPacket::ChatMessage(whisper)),
+                (back, ServerPacket::ChatMessage(echo)),
+            ] if *to == first
+                && whisper.user_id == "2"
+                && whisper.message == "psst, hi"
+                && whisper.message_flags.private
+                && *back == second
+                && echo.user_id == BOT_USER_ID
+                && echo.message == "0\u{c}whisper\u{c}user1\u{c}psst, hi"
+                && echo.message_flags.private
+        ));
+
+        let out = engine.handle(second, &say("2", "/me waves"));
+        let flags = MessageFlags {
+            cursive: true,
+            colon: false,
+            ..MessageFlags::default()
+        };
+        assert_eq!(out.len(), 2);
+        assert!(out.iter().all(|(_, packet)| matches!(
+            packet,
+            ServerPacket::ChatMessage(action)
+                if action.message == "waves" && action.message_flags == flags
+        )));
+
+        let out = engine.handle(second, &say("2", "/msg nobody hi"));
+        assert!(matches!(
+            out.as_slice(),
+            [(to, ServerPacket::ChatMessage(error))]
+                if *to == second && error.message == "1\u{c}usernf\u{c}nobody"
+        ));
+        let out = engine.handle(second, &say("2", "/msg user1"));
+        assert!(matches!(
+            out.as_slice(),
+            [(_, ServerPacket::ChatMessage(error))] if error.message == "1\u{c}cmderr"
+        ));
+    }
+
+    #[test]
+    fn kicks_flooders() {
+        let clock = ManualClock::new(0);
+        let flood = FloodProtection::with_clock(
+            FloodConfig {
+                burst: 1,
+                max_warnings: 1,
+                ban_seconds: 60,
+                ..Default::default()
+            },
+            clock.clone(),
+        );
+        let mut engine = engine()
+            .with_flood_protection(flood)
+            .with_bans(BanList::with_clock(clock));
+        let (first, _) = login(&mut engine, "user1");
+        let (second, _) = login(&mut engine, "user2");
+
+        assert_eq!(engine.handle(second, &say("2", "spam")).len(), 2);
+        let out = engine.handle(second, &say("2", "spam"));
+        assert!(matches!(
+            out.as_slice(),
+            [(connection, ServerPacket::ChatMessage(packet))]
+                if *connection == second && packet.message == "0\u{c}flwarn\u{c}0"
+        ));
+
+        let out = engine.handle(second, &say("2", "spam"));
+        assert!(matches!(
+            out.as_slice(),
+            [
+                (to_second, ServerPacket::ForcedDisconnect(ForcedDisconnectPacket { ban: true, timestamp: 60 })),
+                (to_first, ServerPacket::UserDisconnect(UserDisconnectPacket { reason: DisconnectReason::Flood, .. })),
+            ] if *to_second == second && *to_first == first
+        ));
+        assert!(engine.user("2").is_none());
+        assert!(engine.handle(second, &say("2", "spam")).is_empty());
+
+        let (_, out) = login(&mut engine, "user2");
+        assert!(matches!(
+            out.as_slice(),
+            [(
+                _,
+                ServerPacket::JoinAuth(JoinAuthPacket::BadAuth { timestamp: 60, .. })
+            )]
+        ));
+    }
+
+    #[test]
+    fn rejects_long_messages_with_or_without_flood_protection() {
+        let config = ServerConfig {
+            max_msg_length: 5,
+            ..ServerConfig::default()
+        };
+        let flood = FloodProtection::with_clock(
+            FloodConfig {
+                burst: 1,
+                max_warnings: 0,
+                ..Default::default()
+            },
+            ManualClock::new(0),
+        );
+        let mut plain = ServerEngine::new(NumberedAuth, config.clone());
+        let mut guarded = ServerEngine::new(NumberedAuth, config).with_flood_protection(flood);
+        for engine in [&mut plain, &mut guarded] {
+            let (connection, _) = login(engine, "user1");
+            let out = engine.handle(connection, &say("1", "too long"));
+            assert!(matches!(
+                out.as_slice(),
+                [(_, ServerPacket::ChatMessage(packet))] if packet.message == "1\u{c}msglen"
+            ));
+        }
+
+        let (connection, _) = login(&mut guarded, "user1");
+        let out = guarded.handle(connection, &say("1", "too long"));
+        assert!(matches!(
+            out.first(),
+            Some((_, ServerPacket::ForcedDisconnect(_)))
+        ));
+    }
+
+    #[test]
+    fn bans_and_pardons() {
+        let clock = ManualClock::new(0);
+        let mut engine = engine().with_bans(BanList::with_clock(clock.clone()));
+        let (first, _) = login(&mut engine, "user1");
+        let (second, _) = login(&mut engine, "user2");
+
+        let out = engine.handle(second, &say("2", "/kick user1"));
+        assert!(matches!(
+            out.as_slice(),
+            [(_, ServerPacket::ChatMessage(packet))] if packet.message == "1\u{c}cmdna\u{c}/kick"
+        ));
+
+        let out = engine.handle(first, &say("1", "/ban USER2 60"));
+        assert!(matches!(
+            out.as_slice(),
+            [
+                (to_second, ServerPacket::ForcedDisconnect(ForcedDisconnectPacket { ban: true, timestamp: 60 })),
+                (to_first, ServerPacket::UserDisconnect(UserDisconnectPacket { reason: DisconnectReason::Kick, .. })),
+            ] if *to_second == second && *to_first == first
+        ));
+
+        let (_, out) = login(&mut engine, "user2");
+        assert!(matches!(
+            out.as_slice(),
+            [(
+                _,
+                ServerPacket::JoinAuth(JoinAuthPacket::BadAuth {
+                    reason: BadAuthReason::JoinFail,
+                    timestamp: 60
+                })
+            )]
+        ));
+
+        let out = engine.handle(first, &say("1", "/bans"));
+        assert!(matches!(
+            out.as_slice(),
+            [(_, ServerPacket::ChatMessage(packet))] if packet.message == "0\u{c}banlist\u{c}user2"
+        ));
+        engine.handle(first, &say("1", "/pardon user2"));
+        let (_, out) = login(&mut engine, "user2");
+        assert!(matches!(
+            &out[1],
+            (_, ServerPacket::JoinAuth(JoinAuthPacket::GoodAuth { .. }))
+        ));
+    }
+
+    #[test]
+    fn kicks_only_lower_ranks_and_without_a_ban_list() {
+        let mut engine = engine();
+        let (first, _) = login(&mut engine, "user1");
+        let (fourth, _) = login(&mut engine, "user4");
+        let (second, _) = login(&mut engine, "user2");
+
+        for (connection, user_id) in [(fourth, "4"), (first, "1")] {
+            let out = engine.handle(connection, &say(user_id, "/kick user1"));
+            assert!(matches!(
+                out.as_slice(),
+                [(_, ServerPacket::ChatMessage(packet))] if packet.message == "1\u{c}kickna\u{c}user1"
+            ));
+        }
+        let out = engine.handle(fourth, &say("4", "/ban user2"));
+        assert!(matches!(
+            out.as_slice(),
+            [(_, ServerPacket::ChatMessage(packet))] if packet.message == "1\u{c}cmdna\u{c}/ban"
+        ));
+        let out = engine.handle(fourth, &say("4", "/kick user2"));
+        assert!(matches!(
+            &out[0],
+            (connection, ServerPacket::ForcedDisconnect(ForcedDisconnectPacket { ban: false, .. }))
+                if *connection == second
+        ));
+    }
+
+    #[test]
+    fn reports_ban_list_failures() {
+        let directory =
+            std::env::temp_dir().join(format!("kanii-engine-bans-{}", std::process::id()));
+        let path = directory.join("bans.txt");
+        let bans = BanList::with_clock(ManualClock::new(0))
+            .open(&path)
+            .unwrap();
+        // A directory where the file should be makes every save fail.
+        fs::create_dir_all(&path).unwrap();
+        let mut engine = engine().with_bans(bans);
+        let (first, _) = login(&mut engine, "user1");
+        login(&mut engine, "user2");
+
+        let out = engine.handle(first, &say("1", "/ban user2"));
+        assert!(matches!(
+            out.as_slice(),
+            [(_, ServerPacket::ChatMessage(packet))] if packet.message.starts_with("1\u{c}generr\u{c}")
+        ));
+        assert!(engine.user("2").is_some());
+        assert_eq!(engine.bans().unwrap().active().count(), 0);
+        fs::remove_dir_all(&directory).unwrap();
+    }
+
+    #[test]
+    fn moves_between_channels() {
+        let mut engine = engine();
+        let (first, _) = login(&mut engine, "user1");
+        let (second, _) = login(&mut engine, "user2");
+
+        let out = engine.handle(second, &say("2", "/join nowhere"));
+        assert!(matches!(
+            out.as_slice(),
+            [(_, ServerPacket::ChatMessage(packet))] if packet.message == "1\u{c}nochan\u{c}nowhere"
+        ));
+
+        let out = engine.handle(second, &say("2", "/join Games"));
+        assert!(matches!(
+            &out[0],
+            (connection, ServerPacket::ChannelSwitching(ChannelSwitchingPacket::Departure { .. }))
+                if *connection == first
+        ));
+        assert!(matches!(
+            &out[1],
+            (_, ServerPacket::ChannelSwitching(ChannelSwitchingPacket::ForcedSwitch { channel_name }))
+                if channel_name == "games"
+        ));
+        assert_eq!(engine.user_channel("2"), Some("games"));
+
+        let out = engine.handle(first, &say("1", "anyone?"));
+        assert_eq!(out.len(), 1);
+    }
+
+    #[test]
+    fn manages_user_channels() {
+        let mut engine = engine();
+        let (first, _) = login(&mut engine, "user1");
+        let (second, _) = login(&mut engine, "user2");
+        let (third, _) = login(&mut engine, "user3");
+
+        let out = engine.handle(third, &say("3", "/create nope"));
+        assert!(matches!(
+            out.as_slice(),
+            [(_, ServerPacket::ChatMessage(packet))] if packet.message == "1\u{c}ipchan\u{c}nope"
+        ));
+
+        let out = engine.handle(second, &say("2", "/create den hunter2"));
+        let created = out
+            .iter()
+            .filter(|(_, packet)| {
+                matches!(
+                    packet,
+                    ServerPacket::ChannelEvent(ChannelEventPacket::Creation {
+                        is_protected: true,
+                        is_temporary: true,
+                        ..
+                    })
+                )
+            })
+            .count();
+        assert_eq!(created, 3);
+        assert_eq!(engine.user_channel("2"), Some("den"));
+
+        let out = engine.handle(third, &say("3", "/join den"));
+        assert!(matches!(
+            out.as_slice(),
+            [(_, ServerPacket::ChatMessage(packet))] if packet.message == "1\u{c}ipwchan\u{c}den"
+        ));
+        engine.handle(third, &say("3", "/join den hunter2"));
+        assert_eq!(engine.user_channel("3"), Some("den"));
+
+        let out = engine.disconnect(second, DisconnectReason::Leave);
+        assert!(out.iter().any(|(connection, packet)| *connection == first
+            && matches!(
+                packet,
+                ServerPacket::ChannelEvent(ChannelEventPacket::Deletion { .. })
+            )));
+        assert_eq!(engine.user_channel("3"), Some("lounge"));
+        assert_eq!(engine.channels().count(), 2);
+    }
+
+    #[test]
+    fn creates_temporary_channels_by_default_and_renames_them() {
+        let mut engine = engine();
+        let (first, _) = login(&mut engine, "user1");
+        let (third, _) = login(&mut engine, "user3");
+
+        engine.handle(first, &say("1", "/create hall"));
+        engine.handle(first, &say("1", "/create -p atrium"));
+        let temporary = |engine: &ServerEngine<NumberedAuth>, name: &str| {
+            engine
+                .channels()
+                .find(|channel| channel.channel_name == name)
+                .map(|channel| channel.temporary)
+        };
+        assert_eq!(temporary(&engine, "hall"), Some(true));
+        assert_eq!(temporary(&engine, "atrium"), Some(false));
+
+        engine.handle(first, &say("1", "hello atrium"));
+        let out = engine.handle(third, &say("3", "/rename foyer"));
+        assert!(matches!(
+            out.as_slice(),
+            [(_, ServerPacket::ChatMessage(packet))] if packet.message == "1\u{c}ipchan\u{c}lounge"
+        ));
+        let out = engine.handle(first, &say("1", "/rename foyer"));
+        assert!(out.iter().any(|(connection, packet)| *connection == third
+            && matches!(
+                packet,
+                ServerPacket::ChannelEvent(ChannelEventPacket::Update { channel_name, new_name, .. })
+                    if channel_name == "atrium" && new_name == "foyer"
+            )));
+        assert_eq!(engine.user_channel("1"), Some("foyer"));
+
+        let out = engine.handle(third, &say("3", "/join foyer"));
+        assert!(out.iter().any(|(connection, packet)| *connection == third
+            && matches!(
+                packet,
+                ServerPacket::ContextInformation(ContextInformationPacket::ExistingMessage { message, .. })
+                    if message == "hello atrium"
+            )));
+    }
+
+    /// Passes the packet `build` makes from `session` to the engine and feeds back whatever
+    /// reaches its connection.
+    fn exchange(
+        engine: &mut ServerEngine<NumberedAuth>,
+        connection: ConnectionId,
+        session: &mut Session,
+        state: &mut ChatState,
+        build: impl FnOnce(&mut Session) -> ClientPacket,
+    ) -> Vec<(ConnectionId, ServerPacket)> {
+        let out = engine.handle(connection, &build(session));
+        for (to, packet) in &out {
+            if *to == connection {
+                session.handle(packet, Instant::now());
+                state.handle(packet);
+            }
+        }
+        out
+    }
+
+    #[test]
+    fn negotiates_multi_channel() {
+        let mut engine = engine();
+        let (third, out) = login(&mut engine, "user3");
+        assert!(matches!(
+            &out[0],
+            (_, ServerPacket::JoinAuth(good_auth)) if !good_auth.supports(MCHAN)
+        ));
+
+        let mut session = Session::new("Misuzu", "user2").with_capabilities(&[MCHAN]);
+        let mut state = ChatState::new();
+        let second = engine.connect();
+        exchange(&mut engine, second, &mut session, &mut state, |session| {
+            session.authenticate()
+        });
+        assert!(session.is_multi_channel());
+
+        exchange(&mut engine, second, &mut session, &mut state, |session| {
+            session.join_channel("games", None).unwrap()
+        });
+        assert_eq!(engine.user_channels("2").unwrap(), ["lounge", "games"]);
+        assert_eq!(state.joined_channels(), ["lounge", "games"]);
+        assert_eq!(state.channel_name(), Some("games"));
+
+        let out = engine.handle(third, &say("3", "hi"));
+        let attributed = |connection| {
+            out.iter().find_map(|(to, packet)| match packet {
+                ServerPacket::ChatMessage(message) if *to == connection => {
+                    Some(message.channel_name.clone())
+                }
+                _ => None,
+            })
+        };
+        assert_eq!(attributed(second), Some(Some("lounge".to_string())));
+        assert_eq!(attributed(third), Some(None));
+
+        let out = exchange(&mut engine, second, &mut session, &mut state, |session| {
+            session.leave_channel("lounge").unwrap()
+        });
+        assert!(out.iter().any(|(connection, packet)| *connection == third
+            && matches!(
+                packet,
+                ServerPacket::ChannelSwitching(ChannelSwitchingPacket::Departure {
+                    channel_name: None,
+                    ..
+                })
+            )));
+        assert_eq!(engine.user_channels("2").unwrap(), ["games"]);
+        assert_eq!(state.joined_channels(), ["games"]);
+
+        let out = exchange(&mut engine, second, &mut session, &mut state, |session| {
+            session.leave_channel("games").unwrap()
+        });
+        assert!(matches!(
+            out.as_slice(),
+            [(_, ServerPacket::ChatMessage(packet))] if packet.message == "1\u{c}ipchan\u{c}games"
+        ));
+        let out = engine.handle(third, &say("3", "/leave lounge"));
+        assert!(matches!(
+            out.as_slice(),
+            [(_, ServerPacket::ChatMessage(packet))] if packet.message == "1\u{c}cmdna\u{c}/leave"
+        ));
+    }
+}
@@ -0,0 +1,528 @@
+use std::{
+    collections::{HashMap, VecDeque},
+    io,
+    net::{Shutdown, SocketAddr, TcpListener, TcpStream},
+    sync::{
+        atomic::{AtomicBool, AtomicU64, Ordering},
+        mpsc::{self, Receiver, Sender},
+        Arc, Mutex,
+    },
+    thread::{self, JoinHandle},
+    time::{Duration, Instant},
+};
+
+use tungstenite::{Message, WebSocket};
+
+use crate::packets::{
+    client::ClientPacket,
+    server::{ContextInformationPacket, JoinAuthPacket, ServerPacket},
+    types::{
+        unix_timestamp, BadAuthReason, ChannelContext, Color, MessageFlags, Sockchatable,
+        UserContext,
+    },
+};
+
+const POLL_INTERVAL: Duration = Duration::from_millis(5);
+
+/// How the fake server answers an authentication attempt.
+#[derive(Debug, Clone, PartialEq)]
+pub enum AuthOutcome {
+    Accept(UserContext),
+    Reject(BadAuthReason),
+    /// Never answers, to test client timeouts.
+    Ignore,
+}
+
+/// Everything a `FakeServer` tells its clients.
+///
+/// Unless told otherwise, any authkey is accepted as a user named after it,
+/// and chat messages are echoed back to every connection like a real chat
+/// would.
+#[derive(Debug, Clone)]
+pub struct FakeServerConfig {
+    channel_name: String,
+    max_msg_length: i64,
+    users: Vec<UserContext>,
+    backlog: Vec<ContextInformationPacket>,
+    channels: Vec<ChannelContext>,
+    auth: HashMap<String, AuthOutcome>,
+    scripted_auth: VecDeque<AuthOutcome>,
+    default_auth: Option<AuthOutcome>,
+    echo: bool,
+}
+
+impl Default for FakeServerConfig {
+    fn default() -> Self {
+        FakeServerConfig {
+            channel_name: "lounge".to_string(),
+            max_msg_length: 2000,
+            users: Vec::new(),
+            backlog: Vec::new(),
+            channels: vec![ChannelContext {
+                channel_name: "lounge".to_string(),
+                password_protected: false,
+                temporary: false,
+            }],
+            auth: HashMap::new(),
+            scripted_auth: VecDeque::new(),
+            default_auth: None,
+            echo: true,
+        }
+    }
+}
+
+impl FakeServerConfig {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_channel_name(mut self, channel_name: &str) -> Self {
+        self.channel_name = channel_name.to_string();
+        self
+    }
+
+    pub fn with_max_msg_length(mut self, max_msg_length: i64) -> Self {
+        self.max_msg_length = max_msg_length;
+        self
+    }
+
+    /// Adds a user to the list sent after authenticating.
+    pub fn with_user(mut self, user: UserContext) -> Self {
+        self.users.push(user);
+        self
+    }
+
+    /// Adds a backlog message, or any other context packet, sent after the
+    /// user list.
+    pub fn with_backlog(mut self, packet: ContextInformationPacket) -> Self {
+        self.backlog.push(packet);
+        self
+    }
+
+    /// Adds a backlog message from `user`, numbered in order.
+    pub fn with_message(self, timestamp: i64, user: &UserContext, text: &str) -> Self {
+        let sequence_id = format!("backlog-{}", self.backlog.len() + 1);
+        self.with_backlog(ContextInformationPacket::message(
+            timestamp,
+            user,
+            text,
+            &sequence_id,
+            false,
+            colon_flags(),
+        ))
+    }
+
+    /// Replaces the channel list with the given channels.
+    pub fn with_channels(mut self, channels: &[ChannelContext]) -> Self {
+        self.channels = channels.to_vec();
+        self
+    }
+
+    /// Answers attempts with `authkey` this way.
+    pub fn with_auth(mut self, authkey: &str, outcome: AuthOutcome) -> Self {
+        self.auth.insert(authkey.to_string(), outcome);
+        self
+    }
+
+    /// Answers the next attempt, whatever its authkey, this way; successive
+    /// calls line outcomes up for successive attempts.
+    pub fn script_auth(mut self, outcome: AuthOutcome) -> Self {
+        self.scripted_auth.push_back(outcome);
+        self
+    }
+
+    /// Answers authkeys without a configured outcome this way.
+    pub fn with_default_auth(mut self, outcome: AuthOutcome) -> Self {
+        self.default_auth = Some(outcome);
+        self
+    }
+
+    pub fn without_echo(mut self) -> Self {
+        self.echo = false;
+        self
+    }
+
+    /// Binds to a free port on localhost and starts serving in the background.
+    pub fn start(self) -> io::Result<FakeServer> {
+        FakeServer::start(self, "127.0.0.1:0")
+    }
+
+    pub fn start_on(self, address: &str) -> io::Result<FakeServer> {
+        FakeServer::start(self, address)
+    }
+
+    fn outcome(&mut self, authkey: &str, user_id: u64) -> AuthOutcome {
+        if let Some(outcome) = self.scripted_auth.pop_front() {
+            return outcome;
+        }
+        if let Some(outcome) = self.auth.get(authkey).or(self.default_auth.as_ref()) {
+            return outcome.clone();
+        }
+        AuthOutcome::Accept(UserContext {
+            user_id: user_id.to_string(),
+            username: authkey.to_string(),
+            color: Color {
+                value: "inherit".to_string(),
+            },
+            visible: true,
+            ..Default::default()
+        })
+    }
+}
+
+fn colon_flags() -> MessageFlags {
+    MessageFlags {
+        colon: true,
+        ..Default::default()
+    }
+}
+
+enum Outgoing {
+    Packet(ServerPacket),
+    Drop,
+}
+
+struct Shared {
+    config: Mutex<FakeServerConfig>,
+    connections: Mutex<Vec<(u64, Sender<Outgoing>)>>,
+    received: Mutex<Vec<ClientPacket>>,
+    running: AtomicBool,
+    counter: AtomicU64,
+}
+
+impl Shared {
+    fn next(&self) -> u64 {
+        self.counter.fetch_add(1, Ordering::Relaxed) + 1
+    }
+
+    fn broadcast(&self, packet: &ServerPacket, except: Option<u64>) {
+        self.connections.lock().unwrap().retain(|(id, sender)| {
+            Some(*id) == except || sender.send(Outgoing::Packet(packet.clone())).is_ok()
+        });
+    }
+}
+
+/// Sockchat server on localhost for integration tests.
+///
+/// It speaks the wire format over WebSocket but keeps no real chat logic:
+/// authentication follows the script in its `FakeServerConfig`, and the test
+/// drives everything else through `inject` and `drop_connections`. The
+/// server stops when dropped.
+pub struct FakeServer {
+    address: SocketAddr,
+    shared: Arc<Shared>,
+    accept: Option<JoinHandle<()>>,
+}
+
+impl FakeServer {
+    fn start(config: FakeServerConfig, address: &str) -> io::Result<Self> {
+        let listener = TcpListener::bind(address)?;
+        listener.set_nonblocking(true)?;
+        let address = listener.local_addr()?;
+        let shared = Arc::new(Shared {
+            config: Mutex::new(config),
+            connections: Mutex::new(Vec::new()),
+            received: Mutex::new(Vec::new()),
+            running: AtomicBool::new(true),
+            counter: AtomicU64::new(0),
+        });
+        let accept = {
+            let shared = shared.clone();
+            thread::spawn(move || accept(listener, shared))
+        };
+        Ok(FakeServer {
+            address,
+            shared,
+            accept: Some(accept),
+        })
+    }
+
+    pub fn address(&self) -> SocketAddr {
+        self.address
+    }
+
+    pub fn url(&self) -> String {
+        format!("ws://{}", self.address)
+    }
+
+    /// Sends `packet` to every open connection.
+    pub fn inject(&self, packet: ServerPacket) {
+        self.shared.broadcast(&packet, None);
+    }
+
+    /// Cuts every open connection without a close handshake, like a network
+    /// failure would.
+    pub fn drop_connections(&self) {
+        for (_, sender) in self.shared.connections.lock().unwrap().drain(..) {
+            let _ = sender.send(Outgoing::Drop);
+        }
+    }
+
+    pub fn connections(&self) -> usize {
+        self.shared.connections.lock().unwrap().len()
+    }
+
+    /// Every packet clients have sent so far, in order.
+    pub fn received(&self) -> Vec<ClientPacket> {
+        self.shared.received.lock().unwrap().clone()
+    }
+
+    /// Waits until `condition` holds for the received packets, giving up
+    /// after `timeout`.
+    pub fn wait_for<F>(&self, timeout: Duration, mut condition: F) -> bool
+    where
+        F: FnMut(&[ClientPacket]) -> bool,
+    {
+        let deadline = Instant::now() + timeout;
+        loop {
+            if condition(&self.shared.received.lock().unwrap()) {
+                return true;
+            }
+            if Instant::now() >= deadline {
+                return false;
+            }
+            thread::sleep(POLL_INTERVAL);
+        }
+    }
+}
+
+impl Drop for FakeServer {
+    fn drop(&mut self) {
+        self.shared.running.store(false, Ordering::Relaxed);
+        self.drop_connections();
+        if let Some(accept) = self.accept.take() {
+            let _ = accept.join();
+        }
+    }
+}
+
+fn accept(listener: TcpListener, shared: Arc<Shared>) {
+    while shared.running.load(Ordering::Relaxed) {
+        match listener.accept() {
+            Ok((stream, _)) => {
+                let id = shared.next();
+                let (sender, receiver) = mpsc::channel();
+                shared.connections.lock().unwrap().push((id, sender));
+                let shared = shared.clone();
+                thread::spawn(move || {
+                    let _ = serve(stream, id, &shared, receiver);
+                    shared
+                        .connections
+                        .lock()
+                        .unwrap()
+                        .retain(|(other, _)| *other != id);
+                });
+            }
+            Err(error) if error.kind() == io::ErrorKind::WouldBlock => thread::sleep(POLL_INTERVAL),
+            Err(_) => break,
+        }
+    }
+}
+
+fn serve(
+    stream: TcpStream,
+    id: u64,
+    shared: &Shared,
+    outgoing: Receiver<Outgoing>,
+) -> Result<(), Box<tungstenite::Error>> {
+    stream
+        .set_nonblocking(false)
+        .map_err(tungstenite::Error::Io)?;
+    let mut socket = tungstenite::accept(stream).map_err(|error| match error {
+        tungstenite::HandshakeError::Failure(error) => error,
+        tungstenite::HandshakeError::Interrupted(_) => tungstenite::Error::ConnectionClosed,
+    })?;
+    socket
+        .get_mut()
+        .set_read_timeout(Some(POLL_INTERVAL))
+        .map_err(tungstenite::Error::Io)?;
+    let mut user = None;
+    while shared.running.load(Ordering::Relaxed) {
+        match socket.read() {
+            Ok(Message::Text(text)) => {
+                let Ok(packet) = text.parse::<ClientPacket>() else {
+                    continue;
+                };
+                shared.received.lock().unwrap().push(packet.clone());
+                if !respond(&mut socket, id, shared, &mut user, packet)? {
+                    break;
+                }
+            }
+            Ok(Message::Close(_)) => break,
+            Ok(_) => {}
+            Err(tungstenite::Error::Io(error))
+                if matches!(
+                    error.kind(),
+                    io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut
+                ) => {}
+            Err(error) => return Err(error.into()),
+        }
+        while let Ok(message) = outgoing.try_recv() {
+            match message {
+                Outgoing::Packet(packet) => send(&mut socket, &packet)?,
+                Outgoing::Drop => {
+                    let _ = socket.get_mut().shutdown(Shutdown::Both);
+                    return Ok(());
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+fn send(
+    socket: &mut WebSocket<TcpStream>,
+    packet: &ServerPacket,
+) -> Result<(), Box<tungstenite::Error>> {
+    Ok(socket.send(Message::Text(packet.to_sockstr()))?)
+}
+
+/// Answers one client packet, returning false once the connection should close.
+fn respond(
+    socket: &mut WebSocket<TcpStream>,
+    id: u64,
+    shared: &Shared,
+    user: &mut Option<UserContext>,
+    packet: ClientPacket,
+) -> Result<bool, Box<tungstenite::Error>> {
+    match packet {
+        ClientPacket::Ping(_) => send(socket, &ServerPacket::pong("pong"))?,
+
+        ClientPacket::Authentication(auth) => {
+            let mut config = shared.config.lock().unwrap();
+            match config.outcome(&auth.authkey, 1000 + id) {
+                AuthOutcome::Accept(accepted) => {
+                    send(
+                        socket,
+                        &JoinAuthPacket::good_auth(
+                            &accepted,
+                            &config.channel_name,
+                            config.max_msg_length,
+                        )
+                        .into(),
+                    )?;
+                    send(
+                        socket,
+                        &ContextInformationPacket::users(config.users.iter().cloned()).into(),
+                    )?;
+                    for packet in &config.backlog {
+                        send(socket, &packet.clone().into())?;
+                    }
+                    send(
+                        socket,
+                        &ContextInformationPacket::channels(config.channels.iter().cloned()).into(),
+                    )?;
+                    drop(config);
+                    let join = JoinAuthPacket::join(
+                        unix_timestamp(),
+                        &accepted,
+                        &shared.next().to_string(),
+                    );
+                    shared.broadcast(&join.into(), Some(id));
+                    *user = Some(accepted);
+                }
+                AuthOutcome::Reject(reason) => {
+                    send(
+                        socket,
+                        &JoinAuthPacket::bad_auth(reason, unix_timestamp()).into(),
+                    )?;
+                    socket.close(None)?;
+                    return Ok(false);
+                }
+                AuthOutcome::Ignore => {}
+            }
+        }
+
+        ClientPacket::Message(message) => {
+            let echo = shared.config.lock().unwrap().echo;
+            if let (true, Some(user)) = (echo, user.as_ref()) {
+                shared.broadcast(
+                    &ServerPacket::chat_message(
+                        &user.user_id,
+                        &message.message,
+                        &shared.next().to_string(),
+                        colon_flags(),
+                    ),
+                    None,
+                );
+            }
+        }
+    }
+    Ok(true)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::client::{
+        blocking::{Client, Error},
+        view::{Action, ChatView},
+    };
+
+    fn user(user_id: &str, username: &str) -> UserContext {
+        UserContext {
+            user_id: user_id.to_string(),
+            username: username.to_string(),
+            visible: true,
+            ..Default::default()
+        }
+    }
+
+    fn recv(client: &mut Client) -> ServerPacket {
+        client
+            .recv_timeout(Duration::from_secs(5))
+            .unwrap()
+            .expect("no packet from the fake server")
+    }
+
+    #[test]
+    fn serves_a_scripted_chat() {
+        let flash = user("2", "flash");
+        let server = FakeServerConfig::new()
+            .with_user(flash.clone())
+            .with_message(100, &flash, "welcome")
+            .with_auth("banned", AuthOutcome::Reject(BadAuthReason::JoinFail))
+            .start()
+            .unwrap();
+
+        let mut rejected = Client::connect(&server.url(), "Misuzu", "banned").unwrap();
+        assert!(matches!(
+            recv(&mut rejected),
+            ServerPacket::JoinAuth(JoinAuthPacket::BadAuth {
+                reason: BadAuthReason::JoinFail,
+                ..
+            })
+        ));
+
+        let mut client = Client::connect(&server.url(), "Misuzu", "kanii").unwrap();
+        let mut view = ChatView::new();
+        for _ in 0..4 {
+            view.handle(&recv(&mut client));
+        }
+        assert!(client.session().is_connected());
+        assert_eq!(view.state().users().count(), 2);
+        assert_eq!(view.lines().len(), 1);
+
+        for c in "hello".chars() {
+            view.input.insert(c);
+        }
+        let Action::Send(packets) = view.submit(client.session()) else {
+            panic!("nothing to send");
+        };
+        for packet in packets {
+            client.send(packet).unwrap();
+        }
+        view.handle(&recv(&mut client));
+        assert_eq!(view.lines().len(), 2);
+
+        server.inject(ServerPacket::pong("injected"));
+        assert_eq!(recv(&mut client), ServerPacket::pong("injected"));
+        assert!(server.wait_for(Duration::from_secs(5), |received| received.len() == 3));
+
+        server.drop_connections();
+        assert!(matches!(
+            client.recv_timeout(Duration::from_secs(5)),
+            Err(Error::Closed) | Err(Error::WebSocket(_))
+        ));
+    }
+}
@@ -0,0 +1,220 @@
+use std::collections::HashMap;
+
+use crate::packets::bot_message::BotMessage;
+
+use crate::clock::{Clock, SystemClock};
+
+#[derive(Debug, Clone)]
+pub struct FloodConfig {
+    /// Messages a user may send in a quick burst.
+    pub burst: u32,
+    /// Messages per second regained after a burst.
+    pub rate: f64,
+    /// Warnings handed out before a flooding user gets disconnected.
+    pub max_warnings: u32,
+    /// How long a flood disconnect bans for, in seconds; zero only kicks.
+    pub ban_seconds: i64,
+}
+
+impl Default for FloodConfig {
+    fn default() -> Self {
+        FloodConfig {
+            burst: 5,
+            rate: 1.0,
+            max_warnings: 2,
+            ban_seconds: 0,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum FloodVerdict {
+    Allow,
+    /// Message is longer than the server allows and has to be dropped; it
+    /// still counts against the sender's rate.
+    TooLong,
+    /// Message is dropped and the sender warned; `remaining` more get them disconnected.
+    Warn {
+        remaining: u32,
+    },
+    /// Sender kept flooding and has to be disconnected with `DisconnectReason::Flood`.
+    Flood,
+}
+
+impl FloodVerdict {
+    /// Bot message to show the sender, if any.
+    pub fn bot_message(&self) -> Option<BotMessage> {
+        match self {
+            Self::Allow | Self::Flood => None,
+            Self::TooLong => Some(BotMessage::error("msglen", &[])),
+            Self::Warn { remaining } => Some(BotMessage::info("flwarn", &[&remaining.to_string()])),
+        }
+    }
+}
+
+#[derive(Debug)]
+struct Bucket {
+    tokens: f64,
+    updated: i64,
+    warnings: u32,
+}
+
+/// Per-user token bucket rate limiter for incoming `MessagePacket`s.
+pub struct FloodProtection {
+    config: FloodConfig,
+    clock: Box<dyn Clock>,
+    buckets: HashMap<String, Bucket>,
+}
+
+impl FloodProtection {
+    pub fn new(config: FloodConfig) -> Self {
+        Self::with_clock(config, SystemClock)
+    }
+
+    pub fn with_clock<C: Clock + 'static>(config: FloodConfig, clock: C) -> Self {
+        FloodProtection {
+            config,
+            clock: Box::new(clock),
+            buckets: HashMap::new(),
+        }
+    }
+
+    pub fn config(&self) -> &FloodConfig {
+        &self.config
+    }
+
+    pub fn clock(&self) -> &dyn Clock {
+        self.clock.as_ref()
+    }
+
+    /// Charges a message from `user_id` against their rate, rejecting it
+    /// if it is longer than `max_msg_length` characters.
+    pub fn check(&mut self, user_id: &str, message: &str, max_msg_length: usize) -> FloodVerdict {
+        let now = self.clock.now_millis();
+        let burst = self.config.burst as f64;
+        let bucket = self.buckets.entry(user_id.to_string()).or_insert(Bucket {
+            tokens: burst,
+            updated: now,
+            warnings: 0,
+        });
+        let elapsed = (now - bucket.updated).max(0) as f64 / 1000.0;
+        bucket.tokens = (bucket.tokens + elapsed * self.config.rate).min(burst);
+        bucket.updated = now;
+        if bucket.tokens >= burst {
+            bucket.warnings = 0;
+        }
+
+        if bucket.tokens >= 1.0 {
+            bucket.tokens -= 1.0;
+            return match message.chars().count() > max_msg_length {
+                true => FloodVerdict::TooLong,
+                false => FloodVerdict::Allow,
+            };
+        }
+
+        bucket.warnings += 1;
+        if bucket.warnings > self.config.max_warnings {
+            FloodVerdict::Flood
+        } else {
+            FloodVerdict::Warn {
+                remaining: self.config.max_warnings - bucket.warnings,
+            }
+        }
+    }
+
+    /// Drops the state kept for a user who left, unless their bucket has not
+    /// refilled yet, so that reconnecting does not wipe the slate clean.
+    pub fn forget(&mut self, user_id: &str) {
+        let now = self.clock.now_millis();
+        let (burst, rate) = (self.config.burst as f64, self.config.rate);
+        let Some(bucket) = self.buckets.get(user_id) else {
+            return;
+        };
+        let elapsed = (now - bucket.updated).max(0) as f64 / 1000.0;
+        if bucket.tokens + elapsed * rate >= burst {
+            self.buckets.remove(user_id);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{clock::ManualClock, packets::types::Sockchatable};
+
+    fn protection(clock: &ManualClock) -> FloodProtection {
+        FloodProtection::with_clock(
+            FloodConfig {
+                burst: 3,
+                rate: 1.0,
+                max_warnings: 1,
+                ban_seconds: 0,
+            },
+            clock.clone(),
+        )
+    }
+
+    #[test]
+    fn allows_bursts_then_refills() {
+        let clock = ManualClock::new(0);
+        let mut flood = protection(&clock);
+        for _ in 0..3 {
+            assert_eq!(flood.check("1", "hi", 10), FloodVerdict::Allow);
+        }
+        assert_eq!(
+            flood.check("1", "hi", 10),
+            FloodVerdict::Warn { remaining: 0 }
+        );
+        assert_eq!(flood.check("2", "hi", 10), FloodVerdict::Allow);
+
+        clock.advance(1000);
+        assert_eq!(flood.check("1", "hi", 10), FloodVerdict::Allow);
+        assert_eq!(flood.check("1", "hi", 10), FloodVerdict::Flood);
+        assert_eq!(flood.check("1", "hi", 10), FloodVerdict::Flood);
+
+        flood.forget("1");
+        assert_eq!(flood.check("1", "hi", 10), FloodVerdict::Flood);
+        clock.advance(3000);
+        flood.forget("1");
+        assert_eq!(flood.check("1", "hi", 10), FloodVerdict::Allow);
+    }
+
+    #[test]
+    fn forgives_warnings_after_calming_down() {
+        let clock = ManualClock::new(0);
+        let mut flood = protection(&clock);
+        for _ in 0..3 {
+            flood.check("1", "hi", 10);
+        }
+        assert!(matches!(
+            flood.check("1", "hi", 10),
+            FloodVerdict::Warn { .. }
+        ));
+        clock.advance(3000);
+        for _ in 0..3 {
+            assert_eq!(flood.check("1", "hi", 10), FloodVerdict::Allow);
+        }
+        assert!(matches!(
+            flood.check("1", "hi", 10),
+            FloodVerdict::Warn { .. }
+        ));
+    }
+
+    #[test]
+    fn rejects_long_messages() {
+        let clock = ManualClock::new(0);
+        let mut flood = protection(&clock);
+        assert_eq!(flood.check("1", "ünïcödé ok", 10), FloodVerdict::Allow);
+        assert_eq!(flood.check("1", "way too long", 10), FloodVerdict::TooLong);
+        assert_eq!(flood.check("1", "way too long", 10), FloodVerdict::TooLong);
+        assert_eq!(
+            flood.check("1", "way too long", 10),
+            FloodVerdict::Warn { remaining: 0 }
+        );
+        assert_eq!(flood.check("1", "way too long", 10), FloodVerdict::Flood);
+        assert_eq!(
+            FloodVerdict::TooLong.bot_message().unwrap().to_sockstr(),
+            "1\u{c}msglen"
+        );
+    }
+}
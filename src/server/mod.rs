@@ -0,0 +1,16 @@
+pub mod auth;
+pub mod channels;
+pub mod engine;
+#[cfg(feature = "blocking")]
+pub mod fake;
+pub mod flood;
+pub mod moderation;
+
+pub use crate::clock::{Clock, ManualClock, SystemClock};
+pub use auth::{AuthProvider, MockAuth, StaticAuth, UserProfile};
+pub use channels::{ChannelError, ChannelManager};
+pub use engine::{ConnectionId, ServerConfig, ServerEngine};
+#[cfg(feature = "blocking")]
+pub use fake::{AuthOutcome, FakeServer, FakeServerConfig};
+pub use flood::{FloodConfig, FloodProtection, FloodVerdict};
+pub use moderation::{Ban, BanList, BanParseError, BanTarget};
@@ -0,0 +1,353 @@
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+    str::FromStr,
+};
+
+use crate::packets::{bot_message::BotMessage, types::BadAuthReason};
+
+use crate::clock::{Clock, SystemClock};
+
+/// Expiry timestamp the protocol uses for bans that never run out.
+pub const PERMANENT: i64 = -1;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum BanTarget {
+    User(String),
+    Address(String),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Ban {
+    pub target: BanTarget,
+    /// Name shown in ban listings, usually the username at the time of the ban.
+    pub name: String,
+    /// Unix timestamp the ban runs out at, or `PERMANENT`.
+    pub expires: i64,
+}
+
+impl Ban {
+    pub fn is_active(&self, now: i64) -> bool {
+        self.expires == PERMANENT || self.expires > now
+    }
+
+    /// The ban as a line of a ban list file, without the line break.
+    ///
+    /// Tabs and line breaks in the user id, address or name become spaces.
+    pub fn to_line(&self) -> String {
+        let (kind, key) = match &self.target {
+            BanTarget::User(user_id) => ("user", user_id.as_str()),
+            BanTarget::Address(address) => ("address", address.as_str()),
+        };
+        let clean = |value: &str| value.replace(['\t', '\r', '\n'], " ");
+        format!(
+            "{}\t{}\t{}\t{}",
+            kind,
+            clean(key),
+            clean(&self.name),
+            self.expires
+        )
+    }
+}
+
+/// Why a line of a ban list file does not hold a ban.
+#[derive(Debug, Clone, PartialEq)]
+pub enum BanParseError {
+    MissingField(&'static str),
+    /// Neither `user` nor `address`.
+    UnknownTarget(String),
+    InvalidExpiry(String),
+}
+
+impl FromStr for Ban {
+    type Err = BanParseError;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut iter = s.split('\t').map(str::to_string);
+        let mut field = |name| iter.next().ok_or(BanParseError::MissingField(name));
+        let (kind, key, name, expires) = (
+            field("kind")?,
+            field("key")?,
+            field("name")?,
+            field("expires")?,
+        );
+        let target = match kind.as_str() {
+            "user" => BanTarget::User(key),
+            "address" => BanTarget::Address(key),
+            _ => return Err(BanParseError::UnknownTarget(kind)),
+        };
+        let expires = expires
+            .parse::<i64>()
+            .map_err(|_| BanParseError::InvalidExpiry(expires))?;
+        Ok(Ban {
+            target,
+            name,
+            expires,
+        })
+    }
+}
+
+/// Bans by user id or connection address, optionally persisted to a file.
+///
+/// The file holds one tab-separated ban per line and is rewritten after
+/// every change when the list was opened with `open`.
+pub struct BanList {
+    bans: Vec<Ban>,
+    clock: Box<dyn Clock>,
+    path: Option<PathBuf>,
+}
+
+impl Default for BanList {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl BanList {
+    pub fn new() -> Self {
+        Self::with_clock(SystemClock)
+    }
+
+    pub fn with_clock<C: Clock + 'static>(clock: C) -> Self {
+        BanList {
+            bans: Vec::new(),
+            clock: Box::new(clock),
+            path: None,
+        }
+    }
+
+    /// Reads the bans stored at `path`, which need not exist yet, and keeps
+    /// saving back to it.
+    pub fn open<P: AsRef<Path>>(mut self, path: P) -> io::Result<Self> {
+        let path = path.as_ref().to_path_buf();
+        match fs::read_to_string(&path) {
+            Ok(contents) => {
+                self.bans = contents
+                    .lines()
+                    .enumerate()
+                    .filter(|(_, line)| !line.is_empty())
+                    .map(|(index, line)| {
+                        line.parse::<Ban>().map_err(|error| {
+                            let message = format!("line {}: {:?}", index + 1, error);
+                            io::Error::new(io::ErrorKind::InvalidData, message)
+                        })
+                    })
+                    .collect::<io::Result<_>>()?;
+            }
+            Err(error) if error.kind() == io::ErrorKind::NotFound => {}
+            Err(error) => return Err(error),
+        }
+        self.path = Some(path);
+        Ok(self)
+    }
+
+    pub fn save(&self) -> io::Result<()> {
+        self.write(&self.bans)
+    }
+
+    /// Bans `target` for `seconds`, or for good if `None`, replacing any
+    /// earlier ban of the same target.
+    ///
+    /// Like every change, the ban only takes effect once it has been saved.
+    pub fn ban(&mut self, target: BanTarget, name: &str, seconds: Option<i64>) -> io::Result<Ban> {
+        let expires = seconds.map_or(PERMANENT, |seconds| self.clock.now() + seconds);
+        let ban = Ban {
+            target,
+            name: name.to_string(),
+            expires,
+        };
+        let mut bans = self.bans.clone();
+        bans.retain(|other| other.target != ban.target);
+        bans.push(ban.clone());
+        self.replace(bans)?;
+        Ok(ban)
+    }
+
+    /// Lifts every ban whose name, user id or address equals `name`.
+    pub fn pardon(&mut self, name: &str) -> io::Result<bool> {
+        let mut bans = self.bans.clone();
+        bans.retain(|ban| {
+            let key = match &ban.target {
+                BanTarget::User(user_id) => user_id,
+                BanTarget::Address(address) => address,
+            };
+            !ban.name.eq_ignore_ascii_case(name) && key != name
+        });
+        let pardoned = bans.len() != self.bans.len();
+        if pardoned {
+            self.replace(bans)?;
+        }
+        Ok(pardoned)
+    }
+
+    /// Drops expired bans.
+    pub fn purge(&mut self) -> io::Result<()> {
+        let now = self.clock.now();
+        let mut bans = self.bans.clone();
+        bans.retain(|ban| ban.is_active(now));
+        if bans.len() != self.bans.len() {
+            self.replace(bans)?;
+        }
+        Ok(())
+    }
+
+    /// Saves `bans` and only then starts using them.
+    fn replace(&mut self, bans: Vec<Ban>) -> io::Result<()> {
+        self.write(&bans)?;
+        self.bans = bans;
+        Ok(())
+    }
+
+    fn write(&self, bans: &[Ban]) -> io::Result<()> {
+        let Some(path) = &self.path else {
+            return Ok(());
+        };
+        let mut contents = String::new();
+        for ban in bans {
+            contents.push_str(&ban.to_line());
+            contents.push('\n');
+        }
+        fs::write(path, contents)
+    }
+
+    pub fn active(&self) -> impl Iterator<Item = &Ban> {
+        let now = self.clock.now();
+        self.bans.iter().filter(move |ban| ban.is_active(now))
+    }
+
+    /// Looks up the ban keeping an authenticating user out, if any.
+    pub fn find(&self, user_id: &str, address: Option<&str>) -> Option<&Ban> {
+        self.active().find(|ban| match &ban.target {
+            BanTarget::User(banned) => banned == user_id,
+            BanTarget::Address(banned) => Some(banned.as_str()) == address,
+        })
+    }
+
+    /// Fails with the reason and expiry to put in a `JoinAuthPacket::BadAuth`
+    /// when the user is banned.
+    pub fn check(&self, user_id: &str, address: Option<&str>) -> Result<(), (BadAuthReason, i64)> {
+        match self.find(user_id, address) {
+            Some(ban) => Err((BadAuthReason::JoinFail, ban.expires)),
+            None => Ok(()),
+        }
+    }
+
+    /// Active bans in the form of the `banlist` bot message.
+    pub fn bot_message(&self) -> BotMessage {
+        let names = self
+            .active()
+            .map(|ban| ban.name.as_str())
+            .collect::<Vec<_>>();
+        BotMessage::info("banlist", &[&names.join(", ")])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{clock::ManualClock, packets::types::Sockchatable};
+
+    #[test]
+    fn bans_expire_and_can_be_pardoned() {
+        let clock = ManualClock::new(1_000_000);
+        let mut bans = BanList::with_clock(clock.clone());
+        bans.ban(BanTarget::User("2".to_string()), "kanii", Some(60))
+            .unwrap();
+        bans.ban(BanTarget::Address("10.0.0.1".to_string()), "10.0.0.1", None)
+            .unwrap();
+
+        assert_eq!(bans.check("2", None), Err((BadAuthReason::JoinFail, 1060)));
+        assert_eq!(
+            bans.check("3", Some("10.0.0.1")),
+            Err((BadAuthReason::JoinFail, PERMANENT))
+        );
+        assert_eq!(bans.check("3", Some("10.0.0.2")), Ok(()));
+        assert_eq!(
+            bans.bot_message().to_sockstr(),
+            "0\u{c}banlist\u{c}kanii, 10.0.0.1"
+        );
+
+        clock.advance(60_000);
+        assert_eq!(bans.check("2", None), Ok(()));
+        assert!(bans.pardon("10.0.0.1").unwrap());
+        assert!(!bans.pardon("10.0.0.1").unwrap());
+        assert_eq!(bans.active().count(), 0);
+    }
+
+    #[test]
+    fn persists_to_file() {
+        let path = std::env::temp_dir().join(format!("kanii-bans-{}.txt", std::process::id()));
+        let _ = fs::remove_file(&path);
+
+        let clock = ManualClock::new(0);
+        let mut bans = BanList::with_clock(clock.clone()).open(&path).unwrap();
+        bans.ban(BanTarget::User("2".to_string()), "kanii", Some(60))
+            .unwrap();
+        bans.ban(BanTarget::User("3".to_string()), "flash", None)
+            .unwrap();
+        bans.pardon("flash").unwrap();
+
+        let reopened = BanList::with_clock(clock).open(&path).unwrap();
+        assert_eq!(
+            reopened.active().collect::<Vec<_>>(),
+            bans.active().collect::<Vec<_>>()
+        );
+        assert_eq!(reopened.active().count(), 1);
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn reads_and_writes_ban_lines() {
+        let ban = Ban {
+            target: BanTarget::User("2".to_string()),
+            name: "kanii\tthe\ncat".to_string(),
+            expires: 60,
+        };
+        assert_eq!(ban.to_line(), "user\t2\tkanii the cat\t60");
+        assert_eq!(
+            "address\t10.0.0.1\t10.0.0.1\t-1"
+                .parse::<Ban>()
+                .unwrap()
+                .target,
+            BanTarget::Address("10.0.0.1".to_string())
+        );
+        assert_eq!(
+            "user\t2\tkanii".parse::<Ban>(),
+            Err(BanParseError::MissingField("expires"))
+        );
+        assert_eq!(
+            "group\t2\tkanii\t60".parse::<Ban>(),
+            Err(BanParseError::UnknownTarget("group".to_string()))
+        );
+        assert_eq!(
+            "user\t2\tkanii\tsoon".parse::<Ban>(),
+            Err(BanParseError::InvalidExpiry("soon".to_string()))
+        );
+    }
+
+    #[test]
+    fn failed_saves_change_nothing() {
+        let directory = std::env::temp_dir().join(format!("kanii-bans-{}", std::process::id()));
+        fs::create_dir_all(&directory).unwrap();
+        let path = directory.join("bans.txt");
+        let mut bans = BanList::with_clock(ManualClock::new(0))
+            .open(&path)
+            .unwrap();
+        bans.ban(BanTarget::User("2".to_string()), "kanii", None)
+            .unwrap();
+
+        // A directory where the file should be makes every write fail.
+        fs::remove_file(&path).unwrap();
+        fs::create_dir(&path).unwrap();
+        assert!(bans
+            .ban(BanTarget::User("3".to_string()), "flash", None)
+            .is_err());
+        assert!(bans.pardon("kanii").is_err());
+        assert_eq!(
+            bans.active()
+                .map(|ban| ban.name.as_str())
+                .collect::<Vec<_>>(),
+            ["kanii"]
+        );
+        fs::remove_dir_all(&directory).unwrap();
+    }
+}
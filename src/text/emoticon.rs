@@ -0,0 +1,209 @@
+use std::ops::Range;
+#[cfg(feature = "json")]
+use std::{fs, io, path::Path};
+
+#[cfg(feature = "json")]
+use serde::Deserialize;
+
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "json", derive(Deserialize))]
+pub struct Emoticon {
+    /// Every text the emoticon can be typed as, e.g. `:happy:` or `^^`.
+    #[cfg_attr(feature = "json", serde(rename = "Text"))]
+    pub texts: Vec<String>,
+    /// Image url, or whatever a renderer substitutes the emoticon with.
+    #[cfg_attr(feature = "json", serde(rename = "Image"))]
+    pub image: String,
+    /// Lowest `UserPermissions::rank` allowed to use the emoticon.
+    #[cfg_attr(feature = "json", serde(rename = "Hierarchy", default))]
+    pub min_rank: u8,
+}
+
+impl Emoticon {
+    pub fn new(texts: &[&str], image: &str) -> Self {
+        Emoticon {
+            texts: texts.iter().map(|text| text.to_string()).collect(),
+            image: image.to_string(),
+            min_rank: 0,
+        }
+    }
+
+    pub fn with_min_rank(mut self, min_rank: u8) -> Self {
+        self.min_rank = min_rank;
+        self
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct EmoticonMatch<'a> {
+    /// Byte range of the emoticon text.
+    pub range: Range<usize>,
+    pub emoticon: &'a Emoticon,
+}
+
+/// Emoticons known to a chat, recognised in message text.
+///
+/// Emoticon texts only count when they are not glued to letters or digits,
+/// so `:/` inside `http://` is left alone. When several texts match at the
+/// same spot the longest one wins.
+#[derive(Debug, Clone, Default)]
+pub struct EmoticonRegistry {
+    emoticons: Vec<Emoticon>,
+    /// Every emoticon text with the index of its emoticon, longest first.
+    texts: Vec<(String, usize)>,
+}
+
+impl EmoticonRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Parses the emoticon list the way the reference chat serves it:
+    ///
+    /// ```json
+    /// [{"Text": [":happy:", "^^"], "Image": "/emoticons/happy.png", "Hierarchy": 0}]
+    /// ```
+    #[cfg(feature = "json")]
+    pub fn from_json(s: &str) -> Result<Self, serde_json::Error> {
+        let emoticons = serde_json::from_str::<Vec<Emoticon>>(s)?;
+        Ok(emoticons.into_iter().collect())
+    }
+
+    #[cfg(feature = "json")]
+    pub fn load<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        Self::from_json(&fs::read_to_string(path)?)
+            .map_err(|error| io::Error::new(io::ErrorKind::InvalidData, error))
+    }
+
+    pub fn insert(&mut self, emoticon: Emoticon) {
+        let index = self.emoticons.len();
+        self.texts.extend(
+            emoticon
+                .texts
+                .iter()
+                .filter(|text| !text.is_empty())
+                .map(|text| (text.clone(), index)),
+        );
+        self.texts
+            .sort_by_key(|(text, _)| std::cmp::Reverse(text.len()));
+        self.emoticons.push(emoticon);
+    }
+
+    pub fn emoticons(&self) -> &[Emoticon] {
+        &self.emoticons
+    }
+
+    /// Emoticons a user of `rank` may use, e.g. to fill a picker.
+    pub fn available(&self, rank: u8) -> impl Iterator<Item = &Emoticon> {
+        self.emoticons
+            .iter()
+            .filter(move |emoticon| emoticon.min_rank <= rank)
+    }
+
+    /// Emoticons in `text` posted by a user of `rank`, ordered by position.
+    pub fn find(&self, text: &str, rank: u8) -> Vec<EmoticonMatch<'_>> {
+        let is_word = |c: char| c.is_alphanumeric();
+        let mut matches = Vec::new();
+        let mut position = 0;
+        while position < text.len() {
+            let rest = &text[position..];
+            let glued = text[..position].chars().next_back().is_some_and(is_word);
+            let found = self.texts.iter().find(|(emoticon_text, index)| {
+                !glued
+                    && self.emoticons[*index].min_rank <= rank
+                    && rest.starts_with(emoticon_text.as_str())
+                    && !rest[emoticon_text.len()..]
+                        .chars()
+                        .next()
+                        .is_some_and(is_word)
+            });
+            match found {
+                Some((emoticon_text, index)) => {
+                    let end = position + emoticon_text.len();
+                    matches.push(EmoticonMatch {
+                        range: position..end,
+                        emoticon: &self.emoticons[*index],
+                    });
+                    position = end;
+                }
+                None => position += rest.chars().next().map_or(1, char::len_utf8),
+            }
+        }
+        matches
+    }
+
+    /// Rebuilds `text` with every emoticon replaced by what `substitute` returns.
+    pub fn replace<F>(&self, text: &str, rank: u8, mut substitute: F) -> String
+    where
+        F: FnMut(&Emoticon, &str) -> String,
+    {
+        let mut replaced = String::with_capacity(text.len());
+        let mut last = 0;
+        for emoticon_match in self.find(text, rank) {
+            replaced.push_str(&text[last..emoticon_match.range.start]);
+            replaced.push_str(&substitute(
+                emoticon_match.emoticon,
+                &text[emoticon_match.range.clone()],
+            ));
+            last = emoticon_match.range.end;
+        }
+        replaced.push_str(&text[last..]);
+        replaced
+    }
+}
+
+impl FromIterator<Emoticon> for EmoticonRegistry {
+    fn from_iter<I: IntoIterator<Item = Emoticon>>(iter: I) -> Self {
+        let mut registry = EmoticonRegistry::new();
+        for emoticon in iter {
+            registry.insert(emoticon);
+        }
+        registry
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn finds_emoticons_by_rank() {
+        let registry = [
+            Emoticon::new(&[":)"], "smile.png"),
+            Emoticon::new(&[":))", ":happy:"], "happy.png"),
+            Emoticon::new(&[":/"], "meh.png"),
+            Emoticon::new(&[":crown:"], "crown.png").with_min_rank(5),
+        ]
+        .into_iter()
+        .collect::<EmoticonRegistry>();
+
+        let text = "hi :)) see http://x :/ :crown:";
+        let found = registry
+            .find(text, 0)
+            .iter()
+            .map(|found| &text[found.range.clone()])
+            .collect::<Vec<_>>();
+        assert_eq!(found, vec![":))", ":/"]);
+        assert_eq!(registry.find(text, 5).len(), 3);
+        assert_eq!(registry.available(0).count(), 3);
+        assert_eq!(
+            registry.replace("a :happy: b", 0, |emoticon, _| format!(
+                "[img]{}[/img]",
+                emoticon.image
+            )),
+            "a [img]happy.png[/img] b"
+        );
+    }
+
+    #[cfg(feature = "json")]
+    #[test]
+    fn parses_reference_format() {
+        let registry = EmoticonRegistry::from_json(
+            r#"[{"Text": [":happy:", "^^"], "Image": "/happy.png", "Hierarchy": 1}, {"Text": [":o"], "Image": "/o.png"}]"#,
+        )
+        .unwrap();
+        assert_eq!(registry.emoticons()[0].min_rank, 1);
+        assert_eq!(registry.find("^^ :o", 0).len(), 1);
+        assert_eq!(registry.find("^^ :o", 1).len(), 2);
+    }
+}
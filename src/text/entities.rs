@@ -0,0 +1,82 @@
+/// Decodes the HTML entities and `<br/>` line breaks sockchat servers put in
+/// message bodies.
+///
+/// Unknown or malformed entities are left as they are.
+pub fn decode(s: &str) -> String {
+    let s = s
+        .replace("<br/>", "\n")
+        .replace("<br />", "\n")
+        .replace("<br>", "\n");
+    let mut decoded = String::with_capacity(s.len());
+    let mut rest = s.as_str();
+    while let Some(start) = rest.find('&') {
+        decoded.push_str(&rest[..start]);
+        rest = &rest[start..];
+        let entity = rest
+            .find(';')
+            .filter(|end| *end <= 10)
+            .and_then(|end| Some((decode_entity(&rest[1..end])?, end)));
+        match entity {
+            Some((c, end)) => {
+                decoded.push(c);
+                rest = &rest[end + 1..];
+            }
+            None => {
+                decoded.push('&');
+                rest = &rest[1..];
+            }
+        }
+    }
+    decoded.push_str(rest);
+    decoded
+}
+
+/// Escapes text the way servers expect message bodies, the inverse of `decode`.
+pub fn encode(s: &str) -> String {
+    let mut encoded = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '&' => encoded.push_str("&amp;"),
+            '<' => encoded.push_str("&lt;"),
+            '>' => encoded.push_str("&gt;"),
+            '"' => encoded.push_str("&quot;"),
+            '\'' => encoded.push_str("&#39;"),
+            '\n' => encoded.push_str("<br/>"),
+            c => encoded.push(c),
+        }
+    }
+    encoded
+}
+
+fn decode_entity(name: &str) -> Option<char> {
+    match name {
+        "amp" => Some('&'),
+        "lt" => Some('<'),
+        "gt" => Some('>'),
+        "quot" => Some('"'),
+        "apos" => Some('\''),
+        "nbsp" => Some('\u{a0}'),
+        _ => {
+            let number = name.strip_prefix('#')?;
+            let code = match number.strip_prefix(['x', 'X']) {
+                Some(hex) => u32::from_str_radix(hex, 16).ok()?,
+                None => number.parse::<u32>().ok()?,
+            };
+            char::from_u32(code)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn decodes_entities_and_breaks() {
+        assert_eq!(
+            decode("a &lt;b&gt; &amp;amp; &#39;c&#x27; <br/>d &bogus; & e"),
+            "a <b> &amp; 'c' \nd &bogus; & e"
+        );
+        assert_eq!(decode(&encode("<3 & 'you'")), "<3 & 'you'");
+    }
+}
@@ -0,0 +1,256 @@
+use std::ops::Range;
+
+use regex::{Regex, RegexBuilder};
+
+use crate::packets::{
+    bot_message::BOT_USER_ID,
+    server::{ChatMessagePacket, JoinAuthPacket, ServerPacket},
+};
+
+use super::entities::decode;
+
+/// What made part of a message stand out.
+#[derive(Debug, Clone, PartialEq)]
+pub enum HighlightKind {
+    Username,
+    /// Index of the keyword in the order they were added.
+    Keyword(usize),
+    /// Index of the pattern in the order they were added.
+    Pattern(usize),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Highlight {
+    /// Byte range into the decoded message body.
+    pub range: Range<usize>,
+    pub kind: HighlightKind,
+}
+
+/// Plain-text keyword to look out for.
+#[derive(Debug, Clone)]
+pub struct Keyword {
+    pub text: String,
+    pub case_sensitive: bool,
+    /// Only match when not surrounded by letters, digits or underscores.
+    pub whole_word: bool,
+}
+
+impl Keyword {
+    /// Case-insensitive, whole-word keyword.
+    pub fn new(text: &str) -> Self {
+        Keyword {
+            text: text.to_string(),
+            case_sensitive: false,
+            whole_word: true,
+        }
+    }
+
+    pub fn case_sensitive(mut self) -> Self {
+        self.case_sensitive = true;
+        self
+    }
+
+    pub fn anywhere(mut self) -> Self {
+        self.whole_word = false;
+        self
+    }
+
+    fn compile(&self) -> Regex {
+        RegexBuilder::new(&regex::escape(&self.text))
+            .case_insensitive(!self.case_sensitive)
+            .build()
+            .expect("escaped keywords are valid patterns")
+    }
+}
+
+/// Decides which messages should ping the user and where.
+///
+/// The username is picked up from `GoodAuth` and kept current through
+/// `UserUpdate` when every server packet is passed to `handle`.
+#[derive(Debug, Default)]
+pub struct Highlighter {
+    self_id: Option<String>,
+    username: Option<(Keyword, Regex)>,
+    keywords: Vec<(Keyword, Regex)>,
+    patterns: Vec<Regex>,
+}
+
+impl Highlighter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_username(mut self, username: &str) -> Self {
+        self.set_username(username);
+        self
+    }
+
+    pub fn keyword(mut self, keyword: Keyword) -> Self {
+        let regex = keyword.compile();
+        self.keywords.push((keyword, regex));
+        self
+    }
+
+    pub fn regex(mut self, pattern: Regex) -> Self {
+        self.patterns.push(pattern);
+        self
+    }
+
+    pub fn username(&self) -> Option<&str> {
+        self.username
+            .as_ref()
+            .map(|(keyword, _)| keyword.text.as_str())
+    }
+
+    pub fn handle(&mut self, packet: &ServerPacket) {
+        match packet {
+            ServerPacket::JoinAuth(JoinAuthPacket::GoodAuth {
+                user_id, username, ..
+            }) => {
+                self.self_id = Some(user_id.clone());
+                self.set_username(username);
+            }
+            ServerPacket::UserUpdate(packet) if Some(&packet.user_id) == self.self_id.as_ref() => {
+                self.set_username(&packet.username);
+            }
+            _ => {}
+        }
+    }
+
+    /// Highlights in a live message, skipping our own and bot messages.
+    pub fn check(&self, packet: &ChatMessagePacket) -> Vec<Highlight> {
+        if packet.user_id == BOT_USER_ID || Some(&packet.user_id) == self.self_id.as_ref() {
+            return Vec::new();
+        }
+        self.find(&decode(&packet.message))
+    }
+
+    /// Non-overlapping highlights in already decoded `text`, ordered by position.
+    pub fn find(&self, text: &str) -> Vec<Highlight> {
+        let mut highlights = Vec::new();
+        if let Some((keyword, regex)) = &self.username {
+            Self::find_keyword(
+                text,
+                keyword,
+                regex,
+                HighlightKind::Username,
+                &mut highlights,
+            );
+        }
+        for (index, (keyword, regex)) in self.keywords.iter().enumerate() {
+            Self::find_keyword(
+                text,
+                keyword,
+                regex,
+                HighlightKind::Keyword(index),
+                &mut highlights,
+            );
+        }
+        for (index, pattern) in self.patterns.iter().enumerate() {
+            highlights.extend(pattern.find_iter(text).filter(|m| !m.is_empty()).map(|m| {
+                Highlight {
+                    range: m.range(),
+                    kind: HighlightKind::Pattern(index),
+                }
+            }));
+        }
+
+        highlights
+            .sort_by_key(|highlight| (highlight.range.start, usize::MAX - highlight.range.end));
+        let mut end = 0;
+        highlights.retain(|highlight| {
+            let keep = highlight.range.start >= end;
+            if keep {
+                end = highlight.range.end;
+            }
+            keep
+        });
+        highlights
+    }
+
+    pub fn is_highlighted(&self, packet: &ChatMessagePacket) -> bool {
+        !self.check(packet).is_empty()
+    }
+
+    fn set_username(&mut self, username: &str) {
+        let keyword = Keyword::new(username);
+        let regex = keyword.compile();
+        self.username = Some((keyword, regex));
+    }
+
+    fn find_keyword(
+        text: &str,
+        keyword: &Keyword,
+        regex: &Regex,
+        kind: HighlightKind,
+        highlights: &mut Vec<Highlight>,
+    ) {
+        if keyword.text.is_empty() {
+            return;
+        }
+        let is_word = |c: char| c.is_alphanumeric() || c == '_';
+        highlights.extend(
+            regex
+                .find_iter(text)
+                .filter(|m| {
+                    !keyword.whole_word
+                        || (!text[..m.start()].chars().next_back().is_some_and(is_word)
+                            && !text[m.end()..].chars().next().is_some_and(is_word))
+                })
+                .map(|m| Highlight {
+                    range: m.range(),
+                    kind: kind.clone(),
+                }),
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn follows_renames_and_word_boundaries() {
+        let mut highlighter = Highlighter::new()
+            .keyword(Keyword::new("rust"))
+            .keyword(Keyword::new("CAPS").case_sensitive().anywhere())
+            .regex(Regex::new(r"#\d+").unwrap());
+        highlighter.handle(
+            &"1\ty\t1\tkanii\t#f00\t0 0 0 0 0\tlounge\t2000"
+                .parse::<ServerPacket>()
+                .unwrap(),
+        );
+
+        let text = "Kanii: rusty Rust, CAPSLOCK caps #12";
+        let ranges = highlighter
+            .find(text)
+            .into_iter()
+            .map(|highlight| (&text[highlight.range], highlight.kind))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            ranges,
+            vec![
+                ("Kanii", HighlightKind::Username),
+                ("Rust", HighlightKind::Keyword(0)),
+                ("CAPS", HighlightKind::Keyword(1)),
+                ("#12", HighlightKind::Pattern(0)),
+            ]
+        );
+
+        highlighter.handle(
+            &"10\t1\tflash\t#f00\t0 0 0 0 0"
+                .parse::<ServerPacket>()
+                .unwrap(),
+        );
+        assert_eq!(highlighter.username(), Some("flash"));
+        let packet = ChatMessagePacket::new(0, "2", "hey &lt;FLASH&gt;", "1", Default::default());
+        assert_eq!(highlighter.check(&packet)[0].range, 5..10);
+        assert!(!highlighter.is_highlighted(&ChatMessagePacket::new(
+            0,
+            "1",
+            "flash",
+            "2",
+            Default::default()
+        )));
+    }
+}
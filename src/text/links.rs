@@ -0,0 +1,278 @@
+use std::{ops::Range, sync::OnceLock};
+
+use regex::Regex;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum LinkKind {
+    Url,
+    Image,
+    Video,
+    Audio,
+}
+
+impl LinkKind {
+    fn from_tag(tag: &str) -> Option<Self> {
+        match tag.to_ascii_lowercase().as_str() {
+            "url" => Some(Self::Url),
+            "img" => Some(Self::Image),
+            "video" => Some(Self::Video),
+            "audio" => Some(Self::Audio),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Link {
+    pub kind: LinkKind,
+    pub url: String,
+    /// Byte range of the whole tag, or of the url itself when posted bare.
+    pub range: Range<usize>,
+}
+
+impl Link {
+    /// Lowercased host part of the url, without userinfo or port.
+    pub fn host(&self) -> Option<String> {
+        host(&self.url)
+    }
+}
+
+fn opening_regex() -> &'static Regex {
+    static OPENING: OnceLock<Regex> = OnceLock::new();
+    OPENING.get_or_init(|| Regex::new(r"(?i)\[(url|img|video|audio)(?:=([^\]]*))?\]").unwrap())
+}
+
+fn bare_regex() -> &'static Regex {
+    static BARE: OnceLock<Regex> = OnceLock::new();
+    BARE.get_or_init(|| Regex::new(r#"(?i)\b(?:https?|ftp)://[^\s\[\]<>"]+"#).unwrap())
+}
+
+/// Every link in a decoded message body, ordered by position.
+///
+/// Picks up `[url]`, `[url=...]`, `[img]`, `[video]` and `[audio]` tags as
+/// well as bare `http`, `https` and `ftp` urls outside of them. The visible
+/// text of a `[url=...]` tag is searched too, and so is a `[url]` wrapping
+/// other tags. Tags without a matching closing tag count as plain text.
+/// Trailing punctuation is not considered part of a bare url.
+pub fn extract(text: &str) -> Vec<Link> {
+    let mut links = Vec::new();
+    scan(text, 0, &mut links);
+    links.sort_by_key(|link| link.range.start);
+    links
+}
+
+/// Adds the links in `text`, which starts `offset` bytes into the message.
+fn scan(text: &str, offset: usize, links: &mut Vec<Link>) {
+    let (mut searched, mut scanned) = (0, 0);
+    while let Some(captures) = opening_regex().captures_at(text, searched) {
+        let opening = captures.get(0).unwrap();
+        let tag = &captures[1];
+        let closing = format!("[/{}]", tag.to_ascii_lowercase());
+        let Some(content_end) = text[opening.end()..]
+            .to_ascii_lowercase()
+            .find(&closing)
+            .map(|index| opening.end() + index)
+        else {
+            searched = opening.end();
+            continue;
+        };
+        bare(&text[scanned..opening.start()], offset + scanned, links);
+
+        let kind = LinkKind::from_tag(tag).unwrap();
+        let content = &text[opening.end()..content_end];
+        let end = content_end + closing.len();
+        let range = offset + opening.start()..offset + end;
+        match (kind, captures.get(2)) {
+            (LinkKind::Url, Some(target)) => {
+                links.push(Link {
+                    kind,
+                    url: target.as_str().trim().to_string(),
+                    range,
+                });
+                scan(content, offset + opening.end(), links);
+            }
+            (LinkKind::Url, None) if opening_regex().is_match(content) => {
+                scan(content, offset + opening.end(), links);
+            }
+            _ => links.push(Link {
+                kind,
+                url: content.trim().to_string(),
+                range,
+            }),
+        }
+        (searched, scanned) = (end, end);
+    }
+    bare(&text[scanned..], offset + scanned, links);
+}
+
+fn bare(text: &str, offset: usize, links: &mut Vec<Link>) {
+    for found in bare_regex().find_iter(text) {
+        let url = found
+            .as_str()
+            .trim_end_matches(['.', ',', '!', '?', ';', ':', ')', '\'']);
+        let start = offset + found.start();
+        links.push(Link {
+            kind: LinkKind::Url,
+            url: url.to_string(),
+            range: start..start + url.len(),
+        });
+    }
+}
+
+fn host(url: &str) -> Option<String> {
+    let (_, rest) = url.split_once("://")?;
+    let authority = rest.split(['/', '?', '#']).next()?;
+    let host = authority.rsplit('@').next()?;
+    let host = host.split(':').next()?;
+    (!host.is_empty()).then(|| host.to_ascii_lowercase())
+}
+
+/// Allow and deny lists of domains, each entry also covering its subdomains.
+///
+/// A link passes when its domain is not denied and, if the allow list is not
+/// empty, is allowed. Links without a recognisable host only pass when the
+/// allow list is empty.
+#[derive(Debug, Clone, Default)]
+pub struct DomainPolicy {
+    allow: Vec<String>,
+    deny: Vec<String>,
+}
+
+impl DomainPolicy {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn allow(mut self, domain: &str) -> Self {
+        self.allow.push(domain.to_ascii_lowercase());
+        self
+    }
+
+    pub fn deny(mut self, domain: &str) -> Self {
+        self.deny.push(domain.to_ascii_lowercase());
+        self
+    }
+
+    pub fn permits(&self, link: &Link) -> bool {
+        let Some(host) = link.host() else {
+            return self.allow.is_empty();
+        };
+        let covers = |domain: &String| {
+            host == *domain
+                || host
+                    .strip_suffix(domain.as_str())
+                    .is_some_and(|sub| sub.ends_with('.'))
+        };
+        !self.deny.iter().any(covers) && (self.allow.is_empty() || self.allow.iter().any(covers))
+    }
+
+    /// Links in `text` the policy rejects.
+    pub fn violations(&self, text: &str) -> Vec<Link> {
+        extract(text)
+            .into_iter()
+            .filter(|link| !self.permits(link))
+            .collect()
+    }
+
+    /// Replaces every rejected link, tag included, with `replacement`.
+    pub fn rewrite(&self, text: &str, replacement: &str) -> String {
+        let mut rewritten = String::with_capacity(text.len());
+        let mut last = 0;
+        for link in self.violations(text) {
+            // Links inside a tag that was replaced already went with it.
+            if link.range.start < last {
+                continue;
+            }
+            rewritten.push_str(&text[last..link.range.start]);
+            rewritten.push_str(replacement);
+            last = link.range.end;
+        }
+        rewritten.push_str(&text[last..]);
+        rewritten
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn extracts_tagged_and_bare_links() {
+        let text = "see [url=https://a.example/x]this[/url], [IMG]https://i.example/c.png[/img] \
+                    and https://b.example/page. [video]https://v.example/v[/audio]";
+        let links = extract(text)
+            .into_iter()
+            .map(|link| (link.kind, link.url))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            links,
+            vec![
+                (LinkKind::Url, "https://a.example/x".to_string()),
+                (LinkKind::Image, "https://i.example/c.png".to_string()),
+                (LinkKind::Url, "https://b.example/page".to_string()),
+                (LinkKind::Url, "https://v.example/v".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn looks_inside_tags() {
+        let text = "[url=https://a.example]https://evil.example/x[/url]";
+        let links = extract(text)
+            .into_iter()
+            .map(|link| (link.kind, link.url, link.range))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            links,
+            vec![
+                (
+                    LinkKind::Url,
+                    "https://a.example".to_string(),
+                    0..text.len()
+                ),
+                (LinkKind::Url, "https://evil.example/x".to_string(), 23..45),
+            ]
+        );
+        assert_eq!(
+            DomainPolicy::new()
+                .deny("evil.example")
+                .rewrite(text, "[removed]"),
+            "[url=https://a.example][removed][/url]"
+        );
+
+        let kinds = |text| {
+            extract(text)
+                .into_iter()
+                .map(|link| (link.kind, link.url))
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(
+            kinds("[url]https://a.example [img]https://i.example/c.png[/img]"),
+            vec![
+                (LinkKind::Url, "https://a.example".to_string()),
+                (LinkKind::Image, "https://i.example/c.png".to_string()),
+            ]
+        );
+        assert_eq!(
+            kinds("[URL][IMG]https://i.example/c.png[/img][/url]"),
+            vec![(LinkKind::Image, "https://i.example/c.png".to_string())]
+        );
+    }
+
+    #[test]
+    fn applies_domain_policy() {
+        let policy = DomainPolicy::new().deny("evil.example");
+        let text = "ok https://good.example but [img]http://cdn.evil.example/x.png[/img] https://notevil.example";
+        assert_eq!(policy.violations(text).len(), 1);
+        assert_eq!(
+            policy.rewrite(text, "[removed]"),
+            "ok https://good.example but [removed] https://notevil.example"
+        );
+
+        let policy = DomainPolicy::new().allow("good.example");
+        let link = &extract("https://user@www.GOOD.example:8080/path")[0];
+        assert_eq!(link.host().as_deref(), Some("www.good.example"));
+        assert!(policy.permits(link));
+        assert_eq!(policy.violations(text).len(), 2);
+    }
+}
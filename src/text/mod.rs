@@ -0,0 +1,9 @@
+pub mod emoticon;
+pub mod entities;
+pub mod highlight;
+pub mod links;
+
+pub use emoticon::{Emoticon, EmoticonMatch, EmoticonRegistry};
+pub use entities::{decode, encode};
+pub use highlight::{Highlight, HighlightKind, Highlighter, Keyword};
+pub use links::{DomainPolicy, Link, LinkKind};
@@ -0,0 +1,185 @@
+use std::{collections::BTreeSet, env, fs};
+
+use kanii_lib::{
+    packets::{inspect::inspect, server::ServerPacket, types::Sockchatable},
+    recording::Direction,
+    script::render,
+};
+
+const CORPUS: &str = "tests/corpus/v1";
+
+/// Every server packet layout the corpus has to cover, across dialects.
+const LAYOUTS: &[&str] = &[
+    "Pong",
+    "JoinAuth::GoodAuth",
+    "JoinAuth::BadAuth",
+    "JoinAuth::Join",
+    "ChatMessage",
+    "UserDisconnect",
+    "ChannelEvent::Creation",
+    "ChannelEvent::Update",
+    "ChannelEvent::Deletion",
+    "ChannelSwitching::Join",
+    "ChannelSwitching::Departure",
+    "ChannelSwitching::ForcedSwitch",
+    "MessageDeletion",
+    "ContextInformation::ExistingUsers",
+    "ContextInformation::ExistingMessage",
+    "ContextInformation::Channels",
+    "ContextClearing",
+    "ForcedDisconnect",
+    "UserUpdate",
+];
+
+/// Decodes one dialect's `server.txt` into the text `server.expected` should
+/// hold, noting every layout seen.
+fn decode(raw: &str, seen: &mut BTreeSet<&'static str>) -> Result<String, String> {
+    let mut out = String::from("# decoded from server.txt; KANII_BLESS=1 rewrites this file\n");
+    for (index, line) in raw.lines().enumerate() {
+        let number = index + 1;
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let inspection = inspect(Direction::Server, line);
+        if let Some(issue) = &inspection.issue {
+            return Err(format!("line {}: {:?}", number, issue));
+        }
+        seen.extend(inspection.name);
+        let packet = line
+            .parse::<ServerPacket>()
+            .map_err(|error| format!("line {}: {:?}", number, error))?;
+        let reencoded = packet.to_sockstr();
+        let reparsed = reencoded
+            .parse::<ServerPacket>()
+            .map_err(|error| format!("line {}: re-encoded packet: {:?}", number, error))?;
+        if reparsed.to_sockstr() != reencoded {
+            return Err(format!(
+                "line {}: re-encoding is not stable\n  {}\n  {}",
+                number,
+                render(&reencoded),
+                render(&reparsed.to_sockstr())
+            ));
+        }
+        out.push_str(&format!("== {}: {}\n{:#?}\n", number, render(line), packet));
+    }
+    Ok(out)
+}
+
+/// Splits expectations into per-packet blocks, keyed by their `==` line.
+fn blocks(text: &str) -> Vec<(&str, Vec<&str>)> {
+    let mut blocks: Vec<(&str, Vec<&str>)> = Vec::new();
+    for line in text.lines() {
+        match blocks.last_mut() {
+            _ if line.starts_with("== ") => blocks.push((line, Vec::new())),
+            Some((_, lines)) => lines.push(line),
+            None => {}
+        }
+    }
+    blocks
+}
+
+/// Lists the packets whose decoding changed, line by line.
+fn diff(expected: &str, actual: &str) -> String {
+    let expected = blocks(expected);
+    let actual = blocks(actual);
+    let mut out = String::new();
+    for index in 0..expected.len().max(actual.len()) {
+        match (expected.get(index), actual.get(index)) {
+            (Some(old), Some(new)) if old == new => {}
+            (Some((old_header, old)), Some((new_header, new))) if old_header == new_header => {
+                out.push_str(&format!("{}\n", old_header));
+                for line in 0..old.len().max(new.len()) {
+                    match (old.get(line), new.get(line)) {
+                        (Some(old), Some(new)) if old == new => {}
+                        (old, new) => {
+                            if let Some(old) = old {
+                                out.push_str(&format!("-{}\n", old));
+                            }
+                            if let Some(new) = new {
+                                out.push_str(&format!("+{}\n", new));
+                            }
+                        }
+                    }
+                }
+            }
+            (old, new) => {
+                if let Some((header, _)) = old {
+                    out.push_str(&format!("-{}\n", header));
+                }
+                if let Some((header, _)) = new {
+                    out.push_str(&format!("+{}\n", header));
+                }
+            }
+        }
+    }
+    out
+}
+
+#[test]
+fn corpus_decodes_as_expected() {
+    let bless = env::var_os("KANII_BLESS").is_some();
+    let mut seen = BTreeSet::new();
+    let mut failures = Vec::new();
+    let mut dialects = fs::read_dir(CORPUS)
+        .expect("corpus directory")
+        .map(|entry| entry.unwrap().path())
+        .filter(|path| path.is_dir())
+        .collect::<Vec<_>>();
+    dialects.sort();
+    assert!(!dialects.is_empty());
+
+    for dialect in &dialects {
+        let name = dialect.file_name().unwrap().to_string_lossy().to_string();
+        let raw = fs::read_to_string(dialect.join("server.txt")).expect("server.txt");
+        let actual = match decode(&raw, &mut seen) {
+            Ok(actual) => actual,
+            Err(error) => {
+                failures.push(format!("{}: {}", name, error));
+                continue;
+            }
+        };
+        let expected_path = dialect.join("server.expected");
+        if bless {
+            fs::write(&expected_path, &actual).unwrap();
+            continue;
+        }
+        let expected = fs::read_to_string(&expected_path).unwrap_or_default();
+        if expected != actual {
+            failures.push(format!(
+                "{}: decoding changed\n{}",
+                name,
+                diff(&expected, &actual)
+            ));
+        }
+    }
+
+    let missing = LAYOUTS
+        .iter()
+        .filter(|layout| !seen.contains(*layout))
+        .collect::<Vec<_>>();
+    assert!(missing.is_empty(), "corpus lacks {:?}", missing);
+    assert!(
+        failures.is_empty(),
+        "{}\nrun with KANII_BLESS=1 if the changes are intended",
+        failures.join("\n")
+    );
+}
+
+#[test]
+fn diff_points_at_changed_fields() {
+    let old = "== 1: 0 pong\nPong(\n    text: \"pong\",\n)\n";
+    let new = "== 1: 0 pong\nPong(\n    text: \"ping\",\n)\n";
+    assert_eq!(
+        diff(old, new),
+        "== 1: 0 pong\n-    text: \"pong\",\n+    text: \"ping\",\n"
+    );
+}
+
+#[test]
+fn decode_reports_truncated_lines() {
+    let mut seen = BTreeSet::new();
+    for raw in ["5\n", "7\t9\n", "7\t0\t100000000000\n"] {
+        let error = decode(raw, &mut seen).unwrap_err();
+        assert!(error.starts_with("line 1: "), "{}", error);
+    }
+}